@@ -0,0 +1,167 @@
+//! Historical stake checkpoints.
+//!
+//! Every change to a staker's amount, and to the total, appends a checkpoint
+//! (or overwrites one written earlier in the same ledger), so balances at any
+//! past timestamp can be found by binary search.
+//!
+//! Voting checkpoints track the weight each address votes with, in the same
+//! lock-boosted units as `get_voting_weight`: its own weight unless delegated
+//! away, plus any weight delegated to it.
+
+use soroban_sdk::{contractimpl, contracttype, Address, Env};
+
+use crate::{DataKey, KaleIntegrationContract, KaleIntegrationContractClient};
+#[cfg(any(test, feature = "testutils"))]
+use crate::__KaleIntegrationContract_fn_set_registry;
+
+/// Checkpoint storage keys
+#[contracttype]
+#[derive(Clone)]
+pub enum CheckpointDataKey {
+    StakeCheckpointCount(Address),
+    StakeCheckpoint(Address, u32),
+    TotalStakedCheckpointCount,
+    TotalStakedCheckpoint(u32),
+    VotingCheckpointCount(Address),
+    VotingCheckpoint(Address, u32),
+}
+
+/// Staked amount from `timestamp` until the next checkpoint
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StakeCheckpoint {
+    pub timestamp: u64,
+    pub amount: i128,
+}
+
+#[contractimpl]
+impl KaleIntegrationContract {
+    /// Get a staker's staked amount at `timestamp`
+    pub fn stake_at(env: Env, staker: Address, timestamp: u64) -> i128 {
+        let count: u32 = env.storage().persistent()
+            .get(&CheckpointDataKey::StakeCheckpointCount(staker.clone()))
+            .unwrap_or(0);
+
+        Self::find_stake_checkpoint(count, timestamp, |i| {
+            env.storage().persistent().get(&CheckpointDataKey::StakeCheckpoint(staker.clone(), i)).unwrap()
+        })
+    }
+
+    /// Get the total staked amount at `timestamp`
+    pub fn total_staked_at(env: Env, timestamp: u64) -> i128 {
+        let count: u32 = env.storage().instance().get(&CheckpointDataKey::TotalStakedCheckpointCount).unwrap_or(0);
+
+        Self::find_stake_checkpoint(count, timestamp, |i| {
+            env.storage().persistent().get(&CheckpointDataKey::TotalStakedCheckpoint(i)).unwrap()
+        })
+    }
+
+    /// Get the weight an address voted with at `timestamp`, counting weight
+    /// delegated to it and excluding weight it delegated away
+    pub fn voting_weight_at(env: Env, account: Address, timestamp: u64) -> i128 {
+        let count: u32 = env.storage().persistent()
+            .get(&CheckpointDataKey::VotingCheckpointCount(account.clone()))
+            .unwrap_or(0);
+
+        Self::find_stake_checkpoint(count, timestamp, |i| {
+            env.storage().persistent().get(&CheckpointDataKey::VotingCheckpoint(account.clone(), i)).unwrap()
+        })
+    }
+
+    /// Get the number of checkpoints recorded for a staker
+    pub fn get_stake_checkpoint_count(env: Env, staker: Address) -> u32 {
+        env.storage().persistent().get(&CheckpointDataKey::StakeCheckpointCount(staker)).unwrap_or(0)
+    }
+
+    // Private helper functions
+
+    /// Record new staker and total amounts at the current timestamp
+    pub(crate) fn record_stake_checkpoints(env: &Env, staker: &Address, amount: i128, total_staked: i128) {
+        let timestamp = env.ledger().timestamp();
+
+        let count: u32 = env.storage().persistent()
+            .get(&CheckpointDataKey::StakeCheckpointCount(staker.clone()))
+            .unwrap_or(0);
+        let last: Option<StakeCheckpoint> = if count > 0 {
+            env.storage().persistent().get(&CheckpointDataKey::StakeCheckpoint(staker.clone(), count - 1))
+        } else {
+            None
+        };
+        let index = match last {
+            Some(last) if last.timestamp == timestamp => count - 1,
+            _ => count,
+        };
+        env.storage().persistent().set(&CheckpointDataKey::StakeCheckpoint(staker.clone(), index), &StakeCheckpoint { timestamp, amount });
+        env.storage().persistent().set(&CheckpointDataKey::StakeCheckpointCount(staker.clone()), &(index + 1));
+
+        let count: u32 = env.storage().instance().get(&CheckpointDataKey::TotalStakedCheckpointCount).unwrap_or(0);
+        let last: Option<StakeCheckpoint> = if count > 0 {
+            env.storage().persistent().get(&CheckpointDataKey::TotalStakedCheckpoint(count - 1))
+        } else {
+            None
+        };
+        let index = match last {
+            Some(last) if last.timestamp == timestamp => count - 1,
+            _ => count,
+        };
+        env.storage().persistent().set(&CheckpointDataKey::TotalStakedCheckpoint(index), &StakeCheckpoint { timestamp, amount: total_staked });
+        env.storage().instance().set(&CheckpointDataKey::TotalStakedCheckpointCount, &(index + 1));
+    }
+
+    /// Apply a change in a staker's reward weight to whoever it votes through:
+    /// the delegate's delegated weight if it has one, and that voter's checkpoints
+    pub(crate) fn shift_voting_weight(env: &Env, staker: &Address, weight_delta: i128) {
+        if weight_delta == 0 {
+            return;
+        }
+
+        let delegate: Option<Address> = env.storage().persistent().get(&DataKey::Delegate(staker.clone()));
+        if let Some(delegate) = &delegate {
+            let delegated: i128 = env.storage().persistent()
+                .get(&DataKey::DelegatedWeight(delegate.clone()))
+                .unwrap_or(0);
+            env.storage().persistent().set(&DataKey::DelegatedWeight(delegate.clone()), &(delegated + weight_delta));
+        }
+
+        Self::record_voting_checkpoint(env, delegate.as_ref().unwrap_or(staker), weight_delta);
+    }
+
+    /// Shift an address's voting weight by `delta` at the current timestamp
+    pub(crate) fn record_voting_checkpoint(env: &Env, account: &Address, delta: i128) {
+        if delta == 0 {
+            return;
+        }
+        let timestamp = env.ledger().timestamp();
+
+        let count: u32 = env.storage().persistent()
+            .get(&CheckpointDataKey::VotingCheckpointCount(account.clone()))
+            .unwrap_or(0);
+        let last: Option<StakeCheckpoint> = if count > 0 {
+            env.storage().persistent().get(&CheckpointDataKey::VotingCheckpoint(account.clone(), count - 1))
+        } else {
+            None
+        };
+        let amount = last.as_ref().map(|last| last.amount).unwrap_or(0) + delta;
+        let index = match last {
+            Some(last) if last.timestamp == timestamp => count - 1,
+            _ => count,
+        };
+        env.storage().persistent().set(&CheckpointDataKey::VotingCheckpoint(account.clone(), index), &StakeCheckpoint { timestamp, amount });
+        env.storage().persistent().set(&CheckpointDataKey::VotingCheckpointCount(account.clone()), &(index + 1));
+    }
+
+    /// Binary search for the amount of the last checkpoint at or before `timestamp`
+    fn find_stake_checkpoint<F: Fn(u32) -> StakeCheckpoint>(count: u32, timestamp: u64, checkpoint_at: F) -> i128 {
+        let (mut low, mut high) = (0u32, count);
+        while low < high {
+            let mid = (low + high) / 2;
+            if checkpoint_at(mid).timestamp <= timestamp {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+
+        if low == 0 { 0 } else { checkpoint_at(low - 1).amount }
+    }
+}
@@ -0,0 +1,193 @@
+//! Pause switch and emergency exit.
+//!
+//! The admin or a guardian can pause the contract, which stops staking,
+//! reward claims, markets and the vault. Only the admin can unpause. While
+//! paused, `emergency_withdraw` returns a staker's principal, including
+//! ve-locked KALE, using plain ledger arithmetic. Only vault shares settle
+//! the vault's reward checkpoints, since the remaining shares are owed what
+//! it has earned. Unclaimed rewards are forfeited. Stake backing open bets
+//! stays staked until its markets settle.
+
+use soroban_sdk::{contractimpl, token, Address, Env, panic_with_error, log};
+use shared_types::{StakeInfo, ContractError, Config};
+
+use crate::{DataKey, KaleIntegrationContract, KaleIntegrationContractClient};
+#[cfg(any(test, feature = "testutils"))]
+use crate::__KaleIntegrationContract_fn_set_registry;
+
+#[contractimpl]
+impl KaleIntegrationContract {
+    /// Set the guardian allowed to pause the contract (admin only)
+    pub fn set_guardian(env: Env, admin: Address, guardian: Address) {
+        admin.require_auth();
+
+        let config: Config = env.storage().instance().get(&DataKey::Config).unwrap();
+        if admin != config.admin {
+            panic_with_error!(&env, ContractError::NotAuthorized);
+        }
+
+        env.storage().instance().set(&DataKey::Guardian, &guardian);
+        log!(&env, "Guardian set to {} by admin", guardian);
+    }
+
+    /// Pause the contract (admin or guardian)
+    pub fn pause(env: Env, caller: Address) {
+        caller.require_auth();
+
+        let config: Config = env.storage().instance().get(&DataKey::Config).unwrap();
+        if caller != config.admin && Self::get_guardian(env.clone()) != Some(caller.clone()) {
+            panic_with_error!(&env, ContractError::NotAuthorized);
+        }
+
+        env.storage().instance().set(&DataKey::Paused, &true);
+        log!(&env, "Contract paused by {}", caller);
+    }
+
+    /// Unpause the contract (admin only)
+    pub fn unpause(env: Env, admin: Address) {
+        admin.require_auth();
+
+        let config: Config = env.storage().instance().get(&DataKey::Config).unwrap();
+        if admin != config.admin {
+            panic_with_error!(&env, ContractError::NotAuthorized);
+        }
+
+        env.storage().instance().set(&DataKey::Paused, &false);
+        log!(&env, "Contract unpaused by admin");
+    }
+
+    /// Withdraw all principal while paused: flexible and locked stake, pending
+    /// unbonding, vault shares and ve-locked KALE, ignoring lock and unbonding
+    /// periods. Stake backing open bets is kept.
+    pub fn emergency_withdraw(env: Env, staker: Address) -> i128 {
+        staker.require_auth();
+
+        if !Self::is_paused(env.clone()) {
+            panic_with_error!(&env, ContractError::NotAuthorized);
+        }
+
+        let mut principal = 0;
+
+        let stake_info: Option<StakeInfo> = env.storage().persistent().get(&DataKey::StakeInfo(staker.clone()));
+        if let Some(stake_info) = stake_info {
+            let collateral = Self::get_stake_collateral(env.clone(), staker.clone());
+            principal += stake_info.amount - collateral;
+            Self::drop_stake(&env, stake_info, collateral);
+        }
+        env.storage().persistent().remove(&DataKey::LockPositions(staker.clone()));
+
+        let mut unbonding = 0;
+        for request in Self::get_unbonding_requests(env.clone(), staker.clone()).iter() {
+            unbonding += request.amount;
+        }
+        if unbonding > 0 {
+            let total_unbonding: i128 = env.storage().instance().get(&DataKey::TotalUnbonding).unwrap_or(0);
+            env.storage().instance().set(&DataKey::TotalUnbonding, &(total_unbonding - unbonding));
+            principal += unbonding;
+        }
+        env.storage().persistent().remove(&DataKey::UnbondingRequests(staker.clone()));
+
+        principal += Self::redeem_vault_shares_unchecked(&env, &staker);
+
+        // ve locks are released whatever their unlock time
+        principal += Self::release_ve_lock(&env, &staker);
+
+        if principal == 0 {
+            panic_with_error!(&env, ContractError::StakeNotFound);
+        }
+
+        let config: Config = env.storage().instance().get(&DataKey::Config).unwrap();
+        let token_client = token::Client::new(&env, &config.kale_token);
+        token_client.transfer(&env.current_contract_address(), &staker, &principal);
+
+        log!(&env, "Emergency withdrawal: {} KALE by {}", principal, staker);
+
+        principal
+    }
+
+    /// Get the guardian, if any
+    pub fn get_guardian(env: Env) -> Option<Address> {
+        env.storage().instance().get(&DataKey::Guardian)
+    }
+
+    /// Check whether the contract is paused
+    pub fn is_paused(env: Env) -> bool {
+        env.storage().instance().get(&DataKey::Paused).unwrap_or(false)
+    }
+
+    // Private helper functions
+
+    pub(crate) fn require_not_paused(env: &Env) {
+        if Self::is_paused(env.clone()) {
+            panic_with_error!(env, ContractError::ContractPaused);
+        }
+    }
+
+    /// Remove a stake from the totals without checkpointing rewards, keeping
+    /// `kept` of it as unboosted stake. Its accumulated rewards go back to the
+    /// reward pool.
+    fn drop_stake(env: &Env, stake_info: StakeInfo, kept: i128) {
+        let removed = stake_info.amount - kept;
+        let weight_removed = stake_info.weight - kept;
+
+        let total_staked: i128 = env.storage().instance().get(&DataKey::TotalStaked).unwrap_or(0);
+        let total_weight: i128 = env.storage().instance().get(&DataKey::TotalWeight).unwrap_or(0);
+        env.storage().instance().set(&DataKey::TotalStaked, &(total_staked - removed));
+        env.storage().instance().set(&DataKey::TotalWeight, &(total_weight - weight_removed));
+
+        Self::shift_voting_weight(env, &stake_info.staker, -weight_removed);
+
+        if stake_info.accumulated_rewards > 0 {
+            let reward_pool: i128 = env.storage().instance().get(&DataKey::RewardPool).unwrap_or(0);
+            let rewards_owed: i128 = env.storage().instance().get(&DataKey::RewardsOwed).unwrap_or(0);
+            env.storage().instance().set(&DataKey::RewardPool, &(reward_pool + stake_info.accumulated_rewards));
+            env.storage().instance().set(&DataKey::RewardsOwed, &(rewards_owed - stake_info.accumulated_rewards));
+        }
+
+        Self::record_stake_checkpoints(env, &stake_info.staker, kept, total_staked - removed);
+
+        // What's kept earns from now on, not since its last checkpoint
+        let reward_per_token: i128 = env.storage().instance().get(&DataKey::RewardPerTokenStored).unwrap_or(0);
+        let emptied = StakeInfo {
+            amount: kept,
+            weight: kept,
+            accumulated_rewards: 0,
+            reward_per_token_paid: reward_per_token,
+            ..stake_info
+        };
+        Self::store_stake_info(env, &emptied);
+    }
+
+    /// Redeem all of a depositor's vault shares at the vault's current stake,
+    /// without harvesting
+    fn redeem_vault_shares_unchecked(env: &Env, depositor: &Address) -> i128 {
+        let shares = Self::get_vault_shares(env.clone(), depositor.clone());
+        if shares == 0 {
+            return 0;
+        }
+
+        env.storage().persistent().remove(&DataKey::VaultShares(depositor.clone()));
+
+        Self::burn_vault_shares_unchecked(env, shares)
+    }
+
+    /// Burn vault shares at the vault's current stake, without harvesting.
+    /// The vault's rewards are checkpointed first, so what it has earned so
+    /// far stays with the remaining shares.
+    pub(crate) fn burn_vault_shares_unchecked(env: &Env, shares: i128) -> i128 {
+        let total_shares: i128 = env.storage().instance().get(&DataKey::VaultTotalShares).unwrap_or(0);
+        let mut vault_stake: StakeInfo = env.storage().persistent()
+            .get(&DataKey::StakeInfo(env.current_contract_address()))
+            .unwrap();
+        let assets = shares * vault_stake.amount / total_shares;
+
+        let reward_per_token = Self::update_global_rewards(env);
+        Self::checkpoint_rewards(env, &mut vault_stake, reward_per_token);
+        Self::adjust_stake(env, &mut vault_stake, -assets, -assets);
+        env.storage().persistent().set(&DataKey::StakeInfo(vault_stake.staker.clone()), &vault_stake);
+
+        env.storage().instance().set(&DataKey::VaultTotalShares, &(total_shares - shares));
+
+        assets
+    }
+}
@@ -0,0 +1,169 @@
+//! Epoch-based reward emission schedule.
+//!
+//! Each epoch emits at a rate that moves linearly from `start_rate` to
+//! `end_rate` over its lifetime, so constant rates, halvings (consecutive
+//! epochs) and linear decay are all expressible. Emission is always capped by
+//! the unallocated `RewardPool`.
+
+use soroban_sdk::{contractimpl, contracttype, Address, Env, Vec, panic_with_error, log};
+use shared_types::{ContractError, Config};
+
+use crate::{DataKey, KaleIntegrationContract, KaleIntegrationContractClient, DAY_IN_LEDGERS};
+#[cfg(any(test, feature = "testutils"))]
+use crate::__KaleIntegrationContract_fn_set_registry;
+
+const MAX_EPOCHS_PER_QUERY: u32 = 100;
+
+// Epochs are read on every reward update, so each read keeps them alive
+const EPOCH_BUMP_AMOUNT: u32 = 30 * DAY_IN_LEDGERS;
+const EPOCH_LIFETIME_THRESHOLD: u32 = EPOCH_BUMP_AMOUNT - DAY_IN_LEDGERS;
+
+/// One segment of the emission schedule, covering `[start_time, end_time)`
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct EmissionEpoch {
+    pub start_time: u64,
+    pub end_time: u64,
+    pub start_rate: i128, // KALE per second at start_time
+    pub end_rate: i128,   // KALE per second at end_time
+}
+
+impl EmissionEpoch {
+    /// Emission rate at `time`, interpolated linearly across the epoch
+    pub fn rate_at(&self, time: u64) -> i128 {
+        let duration = (self.end_time - self.start_time) as i128;
+        let elapsed = (time - self.start_time) as i128;
+        self.start_rate + (self.end_rate - self.start_rate) * elapsed / duration
+    }
+
+    /// Rewards this epoch emits over `[from, to)`
+    pub fn emission_between(&self, from: u64, to: u64) -> i128 {
+        let start = if from > self.start_time { from } else { self.start_time };
+        let end = if to < self.end_time { to } else { self.end_time };
+
+        if end <= start {
+            return 0;
+        }
+
+        (self.rate_at(start) + self.rate_at(end)) * (end - start) as i128 / 2
+    }
+}
+
+#[contractimpl]
+impl KaleIntegrationContract {
+    /// Append a future epoch to the emission schedule (admin only). Past and
+    /// already-scheduled epochs can never be changed.
+    pub fn append_emission_epoch(env: Env, admin: Address, epoch: EmissionEpoch) {
+        admin.require_auth();
+
+        let config: Config = env.storage().instance().get(&DataKey::Config).unwrap();
+        if admin != config.admin {
+            panic_with_error!(&env, ContractError::NotAuthorized);
+        }
+
+        if epoch.end_time <= epoch.start_time || epoch.start_rate < 0 || epoch.end_rate < 0 {
+            panic_with_error!(&env, ContractError::InvalidAmount);
+        }
+
+        if epoch.start_time < env.ledger().timestamp() {
+            panic_with_error!(&env, ContractError::InvalidTimestamp);
+        }
+
+        let epoch_count = Self::get_emission_epoch_count(env.clone());
+        if epoch_count > 0 {
+            let last = Self::load_emission_epoch(&env, epoch_count - 1);
+            if epoch.start_time < last.end_time {
+                panic_with_error!(&env, ContractError::InvalidTimestamp);
+            }
+        }
+
+        Self::push_emission_epoch(&env, &epoch);
+
+        log!(&env, "Emission epoch appended: {} to {}", epoch.start_time, epoch.end_time);
+    }
+
+    /// Get emission epochs, `limit` entries starting at `offset`
+    pub fn get_emission_epochs(env: Env, offset: u32, limit: u32) -> Vec<EmissionEpoch> {
+        let mut epochs = Vec::new(&env);
+        let total = Self::get_emission_epoch_count(env.clone());
+        let limit = if limit > MAX_EPOCHS_PER_QUERY { MAX_EPOCHS_PER_QUERY } else { limit };
+
+        if offset >= total {
+            return epochs;
+        }
+
+        let end = if total - offset > limit { offset + limit } else { total };
+        for index in offset..end {
+            epochs.push_back(Self::load_emission_epoch(&env, index));
+        }
+
+        epochs
+    }
+
+    /// Get the number of scheduled emission epochs
+    pub fn get_emission_epoch_count(env: Env) -> u32 {
+        env.storage().instance().get(&DataKey::EmissionEpochCount).unwrap_or(0)
+    }
+
+    /// Get the emission rate in effect now
+    pub fn get_current_emission_rate(env: Env) -> i128 {
+        let now = env.ledger().timestamp();
+        let epoch_count = Self::get_emission_epoch_count(env.clone());
+        let mut index: u32 = env.storage().instance().get(&DataKey::EmissionCursor).unwrap_or(0);
+
+        while index < epoch_count {
+            let epoch = Self::load_emission_epoch(&env, index);
+            if now < epoch.start_time {
+                return 0;
+            }
+            if now < epoch.end_time {
+                return epoch.rate_at(now);
+            }
+            index += 1;
+        }
+
+        0
+    }
+
+    // Private helper functions
+
+    pub(crate) fn push_emission_epoch(env: &Env, epoch: &EmissionEpoch) {
+        let epoch_count: u32 = env.storage().instance().get(&DataKey::EmissionEpochCount).unwrap_or(0);
+        let key = DataKey::EmissionEpoch(epoch_count);
+        env.storage().persistent().set(&key, epoch);
+        env.storage().persistent().extend_ttl(&key, EPOCH_LIFETIME_THRESHOLD, EPOCH_BUMP_AMOUNT);
+        env.storage().instance().set(&DataKey::EmissionEpochCount, &(epoch_count + 1));
+    }
+
+    fn load_emission_epoch(env: &Env, index: u32) -> EmissionEpoch {
+        let key = DataKey::EmissionEpoch(index);
+        let epoch: EmissionEpoch = env.storage().persistent().get(&key).unwrap();
+        env.storage().persistent().extend_ttl(&key, EPOCH_LIFETIME_THRESHOLD, EPOCH_BUMP_AMOUNT);
+        epoch
+    }
+
+    /// Scheduled emission over `[from, to)` and the index of the first epoch that
+    /// has not fully ended by `to`
+    pub(crate) fn scheduled_emission(env: &Env, from: u64, to: u64) -> (i128, u32) {
+        let epoch_count: u32 = env.storage().instance().get(&DataKey::EmissionEpochCount).unwrap_or(0);
+        let mut cursor: u32 = env.storage().instance().get(&DataKey::EmissionCursor).unwrap_or(0);
+        let mut emission = 0i128;
+        let mut index = cursor;
+
+        while index < epoch_count {
+            let epoch = Self::load_emission_epoch(env, index);
+            if epoch.start_time >= to {
+                break;
+            }
+
+            emission += epoch.emission_between(from, to);
+
+            if epoch.end_time <= to && index == cursor {
+                cursor += 1;
+            }
+            index += 1;
+        }
+
+        (emission, cursor)
+    }
+}
@@ -1,27 +1,66 @@
 #![no_std]
+#![allow(clippy::too_many_arguments)]
 
 use soroban_sdk::{
-    contract, contractimpl, contracttype, token, Address, Env, Vec, Map, Symbol,
-    panic_with_error, log, String, BytesN, Val, TryFromVal,
+    contract, contractimpl, contracttype, token, Address, Env, Vec,
+    panic_with_error, log, String, Bytes, BytesN,
 };
+use soroban_sdk::xdr::ToXdr;
 use shared_types::{
-    StakeInfo, ContractError, Config, MarketInfo, FeeInfo, 
-    ContractCallResult, InteropError, StakingPosition, PriceFeed
+    StakeInfo, ContractError, Config, MarketInfo, FeeInfo, StakingPosition
 };
-use shared_types::clients::ReflectorOracleClient;
+use shared_types::clients::{ReflectorOracleClient, ReferralRegistryClient};
+
+mod checkpoints;
+mod emergency;
+mod emission;
+mod markets;
+mod no_loss;
+mod streams;
+mod vault;
+mod ve;
+
+pub use checkpoints::StakeCheckpoint;
+pub use emission::EmissionEpoch;
+pub use markets::{MarketDataKey, MarketPosition};
+pub use no_loss::{NoLossDataKey, NoLossPool, NoLossPosition};
+pub use streams::RewardStream;
+pub use ve::{VeLock, VePoint, VeDataKey};
 
 const DAY_IN_LEDGERS: u32 = 17280;
 const INSTANCE_BUMP_AMOUNT: u32 = 7 * DAY_IN_LEDGERS;
 const INSTANCE_LIFETIME_THRESHOLD: u32 = INSTANCE_BUMP_AMOUNT - DAY_IN_LEDGERS;
 
+// Fixed-point scale for the reward-per-token accumulator
+const REWARD_PRECISION: i128 = 1_000_000_000_000;
+
+const BASIS_POINTS: u32 = 10_000;
+const MAX_FEE_RATE: u32 = 1_000; // 10%
+const MAX_LOCK_MULTIPLIER: u32 = 30_000; // 3x
+const MAX_LOCK_POSITIONS: u32 = 20;
+
+const DEFAULT_UNBONDING_PERIOD: u64 = 7 * 24 * 60 * 60;
+const MAX_UNBONDING_PERIOD: u64 = 60 * 24 * 60 * 60;
+const MAX_UNBONDING_REQUESTS: u32 = 20;
+
+const MAX_PAGE_SIZE: u32 = 100;
+
+const INITIAL_EPOCH_DURATION: u64 = 365 * 24 * 60 * 60;
+
+// Feature modules keep their own key enums; a contract type is limited to 50 variants
 #[contracttype]
 pub enum DataKey {
     Config,
     StakeInfo(Address),
     TotalStaked,
+    TotalWeight,
     RewardPool,
     LastRewardUpdate,
-    StakerList,
+    RewardPerTokenStored,
+    StakerList, // Legacy instance Vec, drained by migrate_staker_list
+    StakerCount,
+    StakerAt(u32),
+    StakerIndex(Address),
     FeeCollector,
     PlatformFees,
     MarketInfo(BytesN<32>),
@@ -29,6 +68,103 @@ pub enum DataKey {
     UserMarkets(Address),
     FeeRate,
     CollectedFees,
+    LockTiers,
+    LockPositions(Address),
+    LockCounter(Address),
+    UnbondingPeriod,
+    TotalUnbonding,
+    UnbondingRequests(Address),
+    UnbondingCounter(Address),
+    VaultTotalShares,
+    VaultShares(Address),
+    Delegate(Address),
+    DelegatedWeight(Address),
+    EmissionEpoch(u32),
+    EmissionEpochCount,
+    EmissionCursor,
+    RewardsOwed,
+    ReferralRegistry,
+    MarketIdByCounter(u32),
+    MarketPosition(BytesN<32>, Address),
+    MarketEscrow,
+    MarketOracle,
+    FeeSplit,
+    FeesToRewards,
+    FeesBurned,
+    Guardian,
+    Paused,
+    StakeCollateral(Address),
+}
+
+/// How a staker's KALE is committed
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StakeBreakdown {
+    pub staked: i128,     // Principal still in the stake
+    pub locked: i128,     // Part of `staked` held in lock positions
+    pub collateral: i128, // Part of `staked` backing open bets
+    pub free: i128,       // Part of `staked` that can be unstaked or bet
+}
+
+/// How routed fees are divided, in basis points summing to 10000
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FeeSplit {
+    pub treasury_bps: u32,
+    pub reward_pool_bps: u32,
+    pub burn_bps: u32,
+}
+
+/// Token balance broken down by ledger. The contract is solvent when the
+/// balance covers every ledger; any surplus is unaccounted donations.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SolvencyReport {
+    pub balance: i128,
+    pub total_staked: i128,
+    pub total_unbonding: i128,
+    pub reward_pool: i128,
+    pub rewards_owed: i128,
+    pub platform_fees: i128,
+    pub ve_locked: i128,
+    pub market_escrow: i128,
+    pub surplus: i128,
+    pub solvent: bool,
+}
+
+/// Lock period and the reward multiplier it earns
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LockTier {
+    pub duration: u64,
+    pub multiplier_bps: u32, // 10000 = 1x
+}
+
+/// Stake locked until `unlock_time`, weighted by its tier multiplier
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LockPosition {
+    pub id: u32,
+    pub amount: i128,
+    pub lock_duration: u64,
+    pub unlock_time: u64,
+    pub multiplier_bps: u32,
+}
+
+/// Unstaked principal waiting out the unbonding period; earns no rewards
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UnbondingRequest {
+    pub id: u32,
+    pub amount: i128,
+    pub created_at: u64,
+    pub release_time: u64,
+}
+
+impl LockPosition {
+    pub fn weight(&self) -> i128 {
+        self.amount * self.multiplier_bps as i128 / BASIS_POINTS as i128
+    }
 }
 
 #[contract]
@@ -53,7 +189,7 @@ impl KaleIntegrationContract {
         let config = Config {
             admin: admin.clone(),
             kale_token,
-            oracle_address: Address::from_string(&env.string().from_str("")), // Not used in this contract
+            oracle_address: env.current_contract_address(), // Not used in this contract
             platform_fee_rate,
             min_stake_amount,
             reward_rate_per_second,
@@ -63,15 +199,40 @@ impl KaleIntegrationContract {
 
         env.storage().instance().set(&DataKey::Config, &config);
         env.storage().instance().set(&DataKey::TotalStaked, &0i128);
+        env.storage().instance().set(&DataKey::TotalWeight, &0i128);
         env.storage().instance().set(&DataKey::RewardPool, &0i128);
+        env.storage().instance().set(&DataKey::RewardsOwed, &0i128);
         env.storage().instance().set(&DataKey::LastRewardUpdate, &env.ledger().timestamp());
-        env.storage().instance().set(&DataKey::StakerList, &Vec::<Address>::new(&env));
+        env.storage().instance().set(&DataKey::RewardPerTokenStored, &0i128);
+        env.storage().instance().set(&DataKey::EmissionEpochCount, &0u32);
+        env.storage().instance().set(&DataKey::EmissionCursor, &0u32);
+
+        // Seed the schedule with a one-year epoch at the initial rate
+        if reward_rate_per_second > 0 {
+            let now = env.ledger().timestamp();
+            Self::push_emission_epoch(&env, &EmissionEpoch {
+                start_time: now,
+                end_time: now + INITIAL_EPOCH_DURATION,
+                start_rate: reward_rate_per_second,
+                end_rate: reward_rate_per_second,
+            });
+        }
+        env.storage().instance().set(&DataKey::StakerCount, &0u32);
         env.storage().instance().set(&DataKey::FeeCollector, &fee_collector);
         env.storage().instance().set(&DataKey::PlatformFees, &0i128);
         env.storage().instance().set(&DataKey::MarketCounter, &0u32);
         env.storage().instance().set(&DataKey::FeeRate, &platform_fee_rate);
+        env.storage().instance().set(&DataKey::FeeSplit, &Self::default_fee_split());
         env.storage().instance().set(&DataKey::CollectedFees, &0i128);
 
+        let mut lock_tiers = Vec::new(&env);
+        lock_tiers.push_back(LockTier { duration: 30 * 24 * 60 * 60, multiplier_bps: 12_500 });
+        lock_tiers.push_back(LockTier { duration: 90 * 24 * 60 * 60, multiplier_bps: 15_000 });
+        lock_tiers.push_back(LockTier { duration: 365 * 24 * 60 * 60, multiplier_bps: 20_000 });
+        env.storage().instance().set(&DataKey::LockTiers, &lock_tiers);
+        env.storage().instance().set(&DataKey::UnbondingPeriod, &DEFAULT_UNBONDING_PERIOD);
+        env.storage().instance().set(&DataKey::TotalUnbonding, &0i128);
+
         env.storage()
             .instance()
             .extend_ttl(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
@@ -82,6 +243,7 @@ impl KaleIntegrationContract {
     /// Stake KALE tokens
     pub fn stake(env: Env, staker: Address, amount: i128) {
         staker.require_auth();
+        Self::require_not_paused(&env);
 
         let config: Config = env.storage().instance().get(&DataKey::Config).unwrap();
 
@@ -94,46 +256,15 @@ impl KaleIntegrationContract {
         // Transfer tokens from staker to contract
         token_client.transfer(&staker, &env.current_contract_address(), &amount);
 
-        let current_time = env.ledger().timestamp();
-
         // Update global rewards before modifying stakes
-        Self::update_global_rewards(&env);
-
-        // Get or create stake info
-        let mut stake_info = env.storage().persistent()
-            .get(&DataKey::StakeInfo(staker.clone()))
-            .unwrap_or_else(|| {
-                // Add to staker list if new staker
-                let mut staker_list: Vec<Address> = env.storage().instance()
-                    .get(&DataKey::StakerList)
-                    .unwrap_or_else(|| Vec::new(&env));
-                staker_list.push_back(staker.clone());
-                env.storage().instance().set(&DataKey::StakerList, &staker_list);
-
-                StakeInfo {
-                    staker: staker.clone(),
-                    amount: 0,
-                    stake_time: current_time,
-                    last_reward_time: current_time,
-                    accumulated_rewards: 0,
-                }
-            });
+        let reward_per_token = Self::update_global_rewards(&env);
+        let mut stake_info = Self::load_or_create_stake_info(&env, &staker, reward_per_token);
 
-        // Calculate pending rewards before updating stake
-        let pending_rewards = Self::calculate_pending_rewards(&env, &stake_info);
-        stake_info.accumulated_rewards += pending_rewards;
-
-        // Update stake info
-        stake_info.amount += amount;
-        stake_info.last_reward_time = current_time;
-
-        // Update total staked
-        let mut total_staked: i128 = env.storage().instance().get(&DataKey::TotalStaked).unwrap_or(0);
-        total_staked += amount;
+        // Checkpoint rewards earned at the old weight, then add the new stake at 1x
+        Self::checkpoint_rewards(&env, &mut stake_info, reward_per_token);
+        Self::adjust_stake(&env, &mut stake_info, amount, amount);
 
-        // Store updated data
         env.storage().persistent().set(&DataKey::StakeInfo(staker.clone()), &stake_info);
-        env.storage().instance().set(&DataKey::TotalStaked, &total_staked);
 
         env.storage()
             .instance()
@@ -142,116 +273,385 @@ impl KaleIntegrationContract {
         log!(&env, "Staked {} KALE by {}", amount, staker);
     }
 
-    /// Unstake KALE tokens
-    pub fn unstake(env: Env, staker: Address, amount: i128) {
+    /// Unstake KALE tokens. Only stake outside of lock positions and not backing
+    /// open bets can be unstaked.
+    /// The amount enters the unbonding queue and is claimable with `withdraw_unbonded`
+    /// once the unbonding period has passed.
+    pub fn unstake(env: Env, staker: Address, amount: i128) -> u32 {
         staker.require_auth();
+        Self::require_not_paused(&env);
 
         let mut stake_info: StakeInfo = env.storage().persistent()
             .get(&DataKey::StakeInfo(staker.clone()))
             .unwrap_or_else(|| panic_with_error!(&env, ContractError::StakeNotFound));
 
-        if amount > stake_info.amount {
+        let free = stake_info.amount - Self::locked_amount(&env, &staker)
+            - Self::get_stake_collateral(env.clone(), staker.clone());
+        if amount <= 0 || amount > free {
             panic_with_error!(&env, ContractError::InsufficientStake);
         }
 
-        let config: Config = env.storage().instance().get(&DataKey::Config).unwrap();
-        let token_client = token::Client::new(&env, &config.kale_token);
+        // Update global rewards and checkpoint the staker before modifying stakes
+        let reward_per_token = Self::update_global_rewards(&env);
+        Self::checkpoint_rewards(&env, &mut stake_info, reward_per_token);
+        Self::adjust_stake(&env, &mut stake_info, -amount, -amount);
 
-        let current_time = env.ledger().timestamp();
+        Self::store_stake_info(&env, &stake_info);
+        let request_id = Self::queue_unbonding(&env, &staker, amount);
 
-        // Update global rewards before modifying stakes
-        Self::update_global_rewards(&env);
+        log!(&env, "Unstaked {} KALE by {}", amount, staker);
 
-        // Calculate pending rewards before updating stake
-        let pending_rewards = Self::calculate_pending_rewards(&env, &stake_info);
-        stake_info.accumulated_rewards += pending_rewards;
+        request_id
+    }
 
-        // Update stake info
-        stake_info.amount -= amount;
-        stake_info.last_reward_time = current_time;
+    /// Withdraw every unbonding request whose unbonding period has ended
+    pub fn withdraw_unbonded(env: Env, staker: Address) -> i128 {
+        staker.require_auth();
 
-        // Update total staked
-        let mut total_staked: i128 = env.storage().instance().get(&DataKey::TotalStaked).unwrap_or(0);
-        total_staked -= amount;
+        let requests = Self::get_unbonding_requests(env.clone(), staker.clone());
+        let current_time = env.ledger().timestamp();
 
-        // Transfer tokens back to staker
-        token_client.transfer(&env.current_contract_address(), &staker, &amount);
+        let mut remaining = Vec::new(&env);
+        let mut withdrawn = 0i128;
 
-        // Store updated data
-        if stake_info.amount == 0 && stake_info.accumulated_rewards == 0 {
-            // Remove from storage if no stake and no rewards
-            env.storage().persistent().remove(&DataKey::StakeInfo(staker.clone()));
-            
-            // Remove from staker list
-            let mut staker_list: Vec<Address> = env.storage().instance()
-                .get(&DataKey::StakerList)
-                .unwrap_or_else(|| Vec::new(&env));
-            
-            let mut new_list = Vec::new(&env);
-            for i in 0..staker_list.len() {
-                if staker_list.get(i).unwrap() != staker {
-                    new_list.push_back(staker_list.get(i).unwrap());
-                }
+        for request in requests.iter() {
+            if current_time >= request.release_time {
+                withdrawn += request.amount;
+            } else {
+                remaining.push_back(request);
             }
-            env.storage().instance().set(&DataKey::StakerList, &new_list);
+        }
+
+        if withdrawn == 0 {
+            return 0;
+        }
+
+        if remaining.is_empty() {
+            env.storage().persistent().remove(&DataKey::UnbondingRequests(staker.clone()));
         } else {
-            env.storage().persistent().set(&DataKey::StakeInfo(staker.clone()), &stake_info);
+            env.storage().persistent().set(&DataKey::UnbondingRequests(staker.clone()), &remaining);
         }
 
-        env.storage().instance().set(&DataKey::TotalStaked, &total_staked);
+        let total_unbonding: i128 = env.storage().instance().get(&DataKey::TotalUnbonding).unwrap_or(0);
+        env.storage().instance().set(&DataKey::TotalUnbonding, &(total_unbonding - withdrawn));
 
-        log!(&env, "Unstaked {} KALE by {}", amount, staker);
+        let config: Config = env.storage().instance().get(&DataKey::Config).unwrap();
+        let token_client = token::Client::new(&env, &config.kale_token);
+        token_client.transfer(&env.current_contract_address(), &staker, &withdrawn);
+
+        log!(&env, "Withdrew {} unbonded KALE by {}", withdrawn, staker);
+
+        withdrawn
     }
 
-    /// Claim accumulated rewards
-    pub fn claim_rewards(env: Env, staker: Address) -> i128 {
+    /// Get a staker's pending unbonding requests
+    pub fn get_unbonding_requests(env: Env, staker: Address) -> Vec<UnbondingRequest> {
+        env.storage().persistent()
+            .get(&DataKey::UnbondingRequests(staker))
+            .unwrap_or_else(|| Vec::new(&env))
+    }
+
+    /// Get the total amount a staker has unbonding
+    pub fn get_unbonding_amount(env: Env, staker: Address) -> i128 {
+        let mut amount = 0;
+        for request in Self::get_unbonding_requests(env, staker).iter() {
+            amount += request.amount;
+        }
+        amount
+    }
+
+    /// Get the unbonding period in seconds
+    pub fn get_unbonding_period(env: Env) -> u64 {
+        env.storage().instance().get(&DataKey::UnbondingPeriod).unwrap_or(DEFAULT_UNBONDING_PERIOD)
+    }
+
+    /// Update the unbonding period (admin only). Applies to new requests only.
+    pub fn set_unbonding_period(env: Env, admin: Address, unbonding_period: u64) {
+        admin.require_auth();
+
+        let config: Config = env.storage().instance().get(&DataKey::Config).unwrap();
+        if admin != config.admin {
+            panic_with_error!(&env, ContractError::NotAuthorized);
+        }
+
+        if unbonding_period > MAX_UNBONDING_PERIOD {
+            panic_with_error!(&env, ContractError::InvalidTimestamp);
+        }
+
+        env.storage().instance().set(&DataKey::UnbondingPeriod, &unbonding_period);
+        log!(&env, "Unbonding period updated to {} by admin", unbonding_period);
+    }
+
+    /// Stake KALE into a new lock position. The lock duration must match one of
+    /// the configured lock tiers, whose multiplier boosts the position's reward weight.
+    pub fn stake_locked(env: Env, staker: Address, amount: i128, lock_duration: u64) -> u32 {
+        staker.require_auth();
+        Self::require_not_paused(&env);
+
+        let config: Config = env.storage().instance().get(&DataKey::Config).unwrap();
+
+        if amount < config.min_stake_amount {
+            panic_with_error!(&env, ContractError::InvalidAmount);
+        }
+
+        let tier = Self::find_lock_tier(&env, lock_duration);
+        let mut positions = Self::get_lock_positions(env.clone(), staker.clone());
+
+        if positions.len() >= MAX_LOCK_POSITIONS {
+            panic_with_error!(&env, ContractError::InvalidAmount);
+        }
+
+        let token_client = token::Client::new(&env, &config.kale_token);
+        token_client.transfer(&staker, &env.current_contract_address(), &amount);
+
+        let reward_per_token = Self::update_global_rewards(&env);
+        let mut stake_info = Self::load_or_create_stake_info(&env, &staker, reward_per_token);
+        Self::checkpoint_rewards(&env, &mut stake_info, reward_per_token);
+
+        let mut lock_counter: u32 = env.storage().persistent()
+            .get(&DataKey::LockCounter(staker.clone()))
+            .unwrap_or(0);
+        lock_counter += 1;
+
+        let position = LockPosition {
+            id: lock_counter,
+            amount,
+            lock_duration,
+            unlock_time: env.ledger().timestamp() + lock_duration,
+            multiplier_bps: tier.multiplier_bps,
+        };
+
+        Self::adjust_stake(&env, &mut stake_info, amount, position.weight());
+        positions.push_back(position);
+
+        env.storage().persistent().set(&DataKey::LockCounter(staker.clone()), &lock_counter);
+        env.storage().persistent().set(&DataKey::LockPositions(staker.clone()), &positions);
+        env.storage().persistent().set(&DataKey::StakeInfo(staker.clone()), &stake_info);
+
+        log!(&env, "Locked {} KALE for {} seconds by {}", amount, lock_duration, staker);
+
+        lock_counter
+    }
+
+    /// Extend a lock position to a longer (or equal) tier. The new unlock time is
+    /// measured from now and can never be earlier than the current one.
+    pub fn extend_lock(env: Env, staker: Address, position_id: u32, new_lock_duration: u64) {
         staker.require_auth();
+        Self::require_not_paused(&env);
+
+        let tier = Self::find_lock_tier(&env, new_lock_duration);
+        let mut positions = Self::get_lock_positions(env.clone(), staker.clone());
+        let index = Self::find_lock_position(&env, &positions, position_id);
+        let mut position = positions.get(index).unwrap();
+
+        let new_unlock_time = env.ledger().timestamp() + new_lock_duration;
+        if new_lock_duration < position.lock_duration || new_unlock_time < position.unlock_time {
+            panic_with_error!(&env, ContractError::InvalidTimestamp);
+        }
 
         let mut stake_info: StakeInfo = env.storage().persistent()
             .get(&DataKey::StakeInfo(staker.clone()))
-            .unwrap_or_else(|| panic_with_error!(&env, ContractError::StakeNotFound));
+            .unwrap();
+        let reward_per_token = Self::update_global_rewards(&env);
+        Self::checkpoint_rewards(&env, &mut stake_info, reward_per_token);
+
+        let old_weight = position.weight();
+        position.lock_duration = new_lock_duration;
+        position.unlock_time = new_unlock_time;
+        position.multiplier_bps = tier.multiplier_bps;
+        Self::adjust_stake(&env, &mut stake_info, 0, position.weight() - old_weight);
+
+        positions.set(index, position);
+        env.storage().persistent().set(&DataKey::LockPositions(staker.clone()), &positions);
+        env.storage().persistent().set(&DataKey::StakeInfo(staker.clone()), &stake_info);
 
+        log!(&env, "Lock {} extended to {} seconds by {}", position_id, new_lock_duration, staker);
+    }
+
+    /// Unstake an expired lock position into the unbonding queue
+    pub fn unstake_locked(env: Env, staker: Address, position_id: u32) -> u32 {
+        staker.require_auth();
+        Self::require_not_paused(&env);
+
+        let mut positions = Self::get_lock_positions(env.clone(), staker.clone());
+        let index = Self::find_lock_position(&env, &positions, position_id);
+        let position = positions.get(index).unwrap();
+
+        if env.ledger().timestamp() < position.unlock_time {
+            panic_with_error!(&env, ContractError::InvalidTimestamp);
+        }
+
+        let mut stake_info: StakeInfo = env.storage().persistent()
+            .get(&DataKey::StakeInfo(staker.clone()))
+            .unwrap();
+        let reward_per_token = Self::update_global_rewards(&env);
+        Self::checkpoint_rewards(&env, &mut stake_info, reward_per_token);
+        Self::adjust_stake(&env, &mut stake_info, -position.amount, -position.weight());
+
+        positions.remove(index);
+        env.storage().persistent().set(&DataKey::LockPositions(staker.clone()), &positions);
+
+        Self::store_stake_info(&env, &stake_info);
+        let request_id = Self::queue_unbonding(&env, &staker, position.amount);
+
+        log!(&env, "Unlocked {} KALE from lock {} by {}", position.amount, position_id, staker);
+
+        request_id
+    }
+
+    /// Convert a staker's expired lock positions back to unboosted, unlocked stake.
+    /// Callable by anyone so expired boosts don't keep earning.
+    pub fn release_expired_locks(env: Env, staker: Address) -> u32 {
+        Self::require_not_paused(&env);
+
+        let positions = Self::get_lock_positions(env.clone(), staker.clone());
         let current_time = env.ledger().timestamp();
 
-        // Update global rewards
-        Self::update_global_rewards(&env);
+        let mut remaining = Vec::new(&env);
+        let mut weight_removed = 0i128;
+        let mut released = 0u32;
 
-        // Calculate total rewards to claim
-        let pending_rewards = Self::calculate_pending_rewards(&env, &stake_info);
-        let total_rewards = stake_info.accumulated_rewards + pending_rewards;
+        for position in positions.iter() {
+            if current_time >= position.unlock_time {
+                weight_removed += position.weight() - position.amount;
+                released += 1;
+            } else {
+                remaining.push_back(position);
+            }
+        }
 
-        if total_rewards == 0 {
+        if released == 0 {
             return 0;
         }
 
+        let mut stake_info: StakeInfo = env.storage().persistent()
+            .get(&DataKey::StakeInfo(staker.clone()))
+            .unwrap();
+        let reward_per_token = Self::update_global_rewards(&env);
+        Self::checkpoint_rewards(&env, &mut stake_info, reward_per_token);
+        Self::adjust_stake(&env, &mut stake_info, 0, -weight_removed);
+
+        env.storage().persistent().set(&DataKey::LockPositions(staker.clone()), &remaining);
+        env.storage().persistent().set(&DataKey::StakeInfo(staker.clone()), &stake_info);
+
+        released
+    }
+
+    /// Get a staker's lock positions
+    pub fn get_lock_positions(env: Env, staker: Address) -> Vec<LockPosition> {
+        env.storage().persistent()
+            .get(&DataKey::LockPositions(staker))
+            .unwrap_or_else(|| Vec::new(&env))
+    }
+
+    /// Get a staker's locked, collateralised and free stake
+    pub fn get_stake_breakdown(env: Env, staker: Address) -> StakeBreakdown {
+        let staked = env.storage().persistent()
+            .get::<_, StakeInfo>(&DataKey::StakeInfo(staker.clone()))
+            .map(|stake_info| stake_info.amount)
+            .unwrap_or(0);
+        let locked = Self::locked_amount(&env, &staker);
+        let collateral = Self::get_stake_collateral(env.clone(), staker);
+
+        StakeBreakdown {
+            staked,
+            locked,
+            collateral,
+            free: staked - locked - collateral,
+        }
+    }
+
+    /// Get the configured lock tiers
+    pub fn get_lock_tiers(env: Env) -> Vec<LockTier> {
+        env.storage().instance()
+            .get(&DataKey::LockTiers)
+            .unwrap_or_else(|| Vec::new(&env))
+    }
+
+    /// Replace the lock tiers (admin only). Existing positions keep their multiplier.
+    pub fn set_lock_tiers(env: Env, admin: Address, tiers: Vec<LockTier>) {
+        admin.require_auth();
+
         let config: Config = env.storage().instance().get(&DataKey::Config).unwrap();
-        let token_client = token::Client::new(&env, &config.kale_token);
+        if admin != config.admin {
+            panic_with_error!(&env, ContractError::NotAuthorized);
+        }
 
-        // Check if contract has enough balance for rewards
-        let contract_balance = token_client.balance(&env.current_contract_address());
-        let total_staked: i128 = env.storage().instance().get(&DataKey::TotalStaked).unwrap_or(0);
-        let available_for_rewards = contract_balance - total_staked;
+        for tier in tiers.iter() {
+            if tier.duration == 0 || tier.multiplier_bps < BASIS_POINTS || tier.multiplier_bps > MAX_LOCK_MULTIPLIER {
+                panic_with_error!(&env, ContractError::InvalidAmount);
+            }
+        }
 
-        let rewards_to_pay = if total_rewards > available_for_rewards {
-            available_for_rewards
-        } else {
-            total_rewards
-        };
+        env.storage().instance().set(&DataKey::LockTiers, &tiers);
+        log!(&env, "Lock tiers updated by admin");
+    }
 
-        if rewards_to_pay > 0 {
-            // Transfer rewards to staker
-            token_client.transfer(&env.current_contract_address(), &staker, &rewards_to_pay);
+    /// Claim accumulated rewards
+    pub fn claim_rewards(env: Env, staker: Address) -> i128 {
+        staker.require_auth();
+        Self::require_not_paused(&env);
+        Self::claim_rewards_to_staker(&env, &staker)
+    }
+
+    /// Claim a staker's rewards as their delegate. Rewards are always paid to the staker.
+    pub fn claim_rewards_for(env: Env, delegate: Address, staker: Address) -> i128 {
+        delegate.require_auth();
+        Self::require_not_paused(&env);
+
+        if Self::get_delegate(env.clone(), staker.clone()) != Some(delegate) {
+            panic_with_error!(&env, ContractError::NotAuthorized);
         }
 
-        // Update stake info
-        stake_info.accumulated_rewards = total_rewards - rewards_to_pay;
-        stake_info.last_reward_time = current_time;
+        Self::claim_rewards_to_staker(&env, &staker)
+    }
 
-        env.storage().persistent().set(&DataKey::StakeInfo(staker.clone()), &stake_info);
+    /// Name a delegate who may claim rewards for the staker and use the stake's
+    /// voting weight. Delegates can never unstake or move principal.
+    pub fn set_delegate(env: Env, staker: Address, delegate: Address) {
+        staker.require_auth();
 
-        log!(&env, "Claimed {} KALE rewards by {}", rewards_to_pay, staker);
+        if delegate == staker {
+            panic_with_error!(&env, ContractError::NotAuthorized);
+        }
 
-        rewards_to_pay
+        Self::move_delegation(&env, &staker, Some(delegate.clone()));
+
+        log!(&env, "Staker {} delegated to {}", staker, delegate);
+    }
+
+    /// Revoke the staker's delegate
+    pub fn remove_delegate(env: Env, staker: Address) {
+        staker.require_auth();
+
+        Self::move_delegation(&env, &staker, None);
+
+        log!(&env, "Staker {} removed delegate", staker);
+    }
+
+    /// Get a staker's delegate, if any
+    pub fn get_delegate(env: Env, staker: Address) -> Option<Address> {
+        env.storage().persistent().get(&DataKey::Delegate(staker))
+    }
+
+    /// Get the total reward weight delegated to an address
+    pub fn get_delegated_weight(env: Env, delegate: Address) -> i128 {
+        env.storage().persistent().get(&DataKey::DelegatedWeight(delegate)).unwrap_or(0)
+    }
+
+    /// Get an address's staking voting weight: its own weight unless delegated
+    /// away, plus any weight delegated to it
+    pub fn get_voting_weight(env: Env, account: Address) -> i128 {
+        let own_weight = if Self::get_delegate(env.clone(), account.clone()).is_some() {
+            0
+        } else {
+            env.storage().persistent()
+                .get::<DataKey, StakeInfo>(&DataKey::StakeInfo(account.clone()))
+                .map(|stake_info| stake_info.weight)
+                .unwrap_or(0)
+        };
+
+        own_weight + Self::get_delegated_weight(env, account)
     }
 
     /// Add rewards to the pool (only admin)
@@ -264,6 +664,10 @@ impl KaleIntegrationContract {
             panic_with_error!(&env, ContractError::NotAuthorized);
         }
 
+        if amount <= 0 {
+            panic_with_error!(&env, ContractError::InvalidAmount);
+        }
+
         let token_client = token::Client::new(&env, &config.kale_token);
 
         // Transfer tokens from admin to contract
@@ -300,25 +704,119 @@ impl KaleIntegrationContract {
         env.storage().instance().get(&DataKey::RewardPool).unwrap_or(0)
     }
 
-    /// Get list of all stakers
-    pub fn get_stakers(env: Env) -> Vec<Address> {
-        env.storage().instance()
-            .get(&DataKey::StakerList)
-            .unwrap_or_else(|| Vec::new(&env))
+    /// Get rewards emitted to stakers but not yet claimed
+    pub fn get_rewards_owed(env: Env) -> i128 {
+        env.storage().instance().get(&DataKey::RewardsOwed).unwrap_or(0)
+    }
+
+    /// Check that the token balance covers staked principal, unbonding principal,
+    /// the reward pool, emitted rewards, platform fees, ve-locked KALE and market escrow
+    pub fn check_solvency(env: Env) -> SolvencyReport {
+        let config: Config = env.storage().instance().get(&DataKey::Config).unwrap();
+        let token_client = token::Client::new(&env, &config.kale_token);
+
+        let balance = token_client.balance(&env.current_contract_address());
+        let total_staked: i128 = env.storage().instance().get(&DataKey::TotalStaked).unwrap_or(0);
+        let total_unbonding: i128 = env.storage().instance().get(&DataKey::TotalUnbonding).unwrap_or(0);
+        let reward_pool: i128 = env.storage().instance().get(&DataKey::RewardPool).unwrap_or(0);
+        let rewards_owed: i128 = env.storage().instance().get(&DataKey::RewardsOwed).unwrap_or(0);
+        let platform_fees: i128 = env.storage().instance().get(&DataKey::PlatformFees).unwrap_or(0);
+
+        let ve_locked: i128 = env.storage().instance().get(&VeDataKey::TotalLocked).unwrap_or(0);
+
+        let market_escrow: i128 = env.storage().instance().get(&DataKey::MarketEscrow).unwrap_or(0);
+
+        let liabilities = total_staked + total_unbonding + reward_pool + rewards_owed
+            + platform_fees + ve_locked + market_escrow;
+
+        SolvencyReport {
+            balance,
+            total_staked,
+            total_unbonding,
+            reward_pool,
+            rewards_owed,
+            platform_fees,
+            ve_locked,
+            market_escrow,
+            surplus: balance - liabilities,
+            solvent: balance >= liabilities,
+        }
+    }
+
+    /// Get a page of stakers
+    pub fn get_stakers(env: Env, offset: u32, limit: u32) -> Vec<Address> {
+        let mut stakers = Vec::new(&env);
+        let total = Self::get_staker_count(env.clone());
+        let limit = if limit > MAX_PAGE_SIZE { MAX_PAGE_SIZE } else { limit };
+
+        if offset >= total {
+            return stakers;
+        }
+
+        let end = if total - offset < limit { total } else { offset + limit };
+        for i in offset..end {
+            stakers.push_back(env.storage().persistent().get(&DataKey::StakerAt(i)).unwrap());
+        }
+        stakers
+    }
+
+    /// Get the number of stakers
+    pub fn get_staker_count(env: Env) -> u32 {
+        env.storage().instance().get(&DataKey::StakerCount).unwrap_or(0)
+    }
+
+    /// Move up to `limit` stakers from the legacy instance list into the
+    /// persistent index (admin only). Returns how many are left to migrate.
+    pub fn migrate_staker_list(env: Env, admin: Address, limit: u32) -> u32 {
+        admin.require_auth();
+
+        let config: Config = env.storage().instance().get(&DataKey::Config).unwrap();
+        if admin != config.admin {
+            panic_with_error!(&env, ContractError::NotAuthorized);
+        }
+
+        let mut staker_list: Vec<Address> = match env.storage().instance().get(&DataKey::StakerList) {
+            Some(staker_list) => staker_list,
+            None => return 0,
+        };
+
+        let mut migrated = 0;
+        while migrated < limit {
+            match staker_list.pop_back() {
+                // Legacy entries can outlive their stake; only index live stakers
+                Some(staker) => {
+                    if env.storage().persistent().has(&DataKey::StakeInfo(staker.clone())) {
+                        Self::add_staker(&env, &staker);
+                    }
+                }
+                None => break,
+            }
+            migrated += 1;
+        }
+
+        let remaining = staker_list.len();
+        if remaining == 0 {
+            env.storage().instance().remove(&DataKey::StakerList);
+        } else {
+            env.storage().instance().set(&DataKey::StakerList, &staker_list);
+        }
+
+        log!(&env, "Migrated {} stakers, {} remaining", migrated, remaining);
+
+        remaining
     }
 
     /// Calculate APY based on current staking parameters
     pub fn get_current_apy(env: Env) -> u32 {
-        let config: Config = env.storage().instance().get(&DataKey::Config).unwrap();
         let total_staked: i128 = env.storage().instance().get(&DataKey::TotalStaked).unwrap_or(0);
 
         if total_staked == 0 {
             return 0;
         }
 
-        // APY = (reward_rate_per_second * seconds_per_year * 100) / total_staked
+        // APY = (current emission rate * seconds_per_year * 100) / total_staked
         let seconds_per_year = 365 * 24 * 60 * 60;
-        let annual_rewards = config.reward_rate_per_second * seconds_per_year;
+        let annual_rewards = Self::get_current_emission_rate(env.clone()) * seconds_per_year;
         ((annual_rewards * 10000) / total_staked) as u32 // Return as basis points
     }
 
@@ -334,6 +832,11 @@ impl KaleIntegrationContract {
         market_fee: i128,
     ) -> BytesN<32> {
         creator.require_auth();
+        Self::require_not_paused(&env);
+
+        if resolve_time <= env.ledger().timestamp() {
+            panic_with_error!(&env, ContractError::InvalidTimestamp);
+        }
 
         let config: Config = env.storage().instance().get(&DataKey::Config).unwrap();
         let token_client = token::Client::new(&env, &config.kale_token);
@@ -341,11 +844,7 @@ impl KaleIntegrationContract {
         // Collect market creation fee
         if market_fee > 0 {
             token_client.transfer(&creator, &env.current_contract_address(), &market_fee);
-            
-            // Add fee to platform fees
-            let mut platform_fees: i128 = env.storage().instance().get(&DataKey::PlatformFees).unwrap_or(0);
-            platform_fees += market_fee;
-            env.storage().instance().set(&DataKey::PlatformFees, &platform_fees);
+            Self::route_fee(&env, &creator, market_fee);
         }
 
         // Generate market ID
@@ -353,13 +852,11 @@ impl KaleIntegrationContract {
         market_counter += 1;
         env.storage().instance().set(&DataKey::MarketCounter, &market_counter);
 
-        let market_id = env.crypto().sha256(&env.crypto().sha256(&env.crypto().sha256(&BytesN::from_array(&env, &[
-            creator.to_string().as_bytes()[0],
-            market_counter as u8,
-            (market_counter >> 8) as u8,
-            (market_counter >> 16) as u8,
-            (market_counter >> 24) as u8,
-        ]))));
+        let market_id = Self::derive_market_id(&env, &creator, market_counter);
+        if env.storage().persistent().has(&DataKey::MarketInfo(market_id.clone())) {
+            panic_with_error!(&env, ContractError::MarketAlreadyExists);
+        }
+        env.storage().persistent().set(&DataKey::MarketIdByCounter(market_counter), &market_id);
 
         let market_info = MarketInfo {
             id: market_id.clone(),
@@ -384,7 +881,7 @@ impl KaleIntegrationContract {
             .get(&DataKey::UserMarkets(creator.clone()))
             .unwrap_or_else(|| Vec::new(&env));
         user_markets.push_back(market_id.clone());
-        env.storage().persistent().set(&DataKey::UserMarkets(creator), &user_markets);
+        env.storage().persistent().set(&DataKey::UserMarkets(creator.clone()), &user_markets);
 
         log!(&env, "Market created: {} by {}", market_id, creator);
         market_id
@@ -397,6 +894,18 @@ impl KaleIntegrationContract {
             .unwrap_or_else(|| panic_with_error!(&env, ContractError::MarketNotFound))
     }
 
+    /// Get the id of the market created with a given counter value
+    pub fn get_market_id_by_counter(env: Env, counter: u32) -> BytesN<32> {
+        env.storage().persistent()
+            .get(&DataKey::MarketIdByCounter(counter))
+            .unwrap_or_else(|| panic_with_error!(&env, ContractError::MarketNotFound))
+    }
+
+    /// Get the number of markets created
+    pub fn get_market_count(env: Env) -> u32 {
+        env.storage().instance().get(&DataKey::MarketCounter).unwrap_or(0)
+    }
+
     /// Get user's markets
     pub fn get_user_markets(env: Env, user: Address) -> Vec<BytesN<32>> {
         env.storage().persistent()
@@ -458,10 +967,43 @@ impl KaleIntegrationContract {
             panic_with_error!(&env, ContractError::NotAuthorized);
         }
 
+        if new_fee_rate > MAX_FEE_RATE {
+            panic_with_error!(&env, ContractError::InvalidAmount);
+        }
+
         env.storage().instance().set(&DataKey::FeeRate, &new_fee_rate);
         log!(&env, "Fee rate updated to {} by admin", new_fee_rate);
     }
 
+    /// Set how fees are split between the treasury, the staker reward pool and burn (admin only)
+    pub fn set_fee_split(env: Env, admin: Address, fee_split: FeeSplit) {
+        admin.require_auth();
+
+        let config: Config = env.storage().instance().get(&DataKey::Config).unwrap();
+        if admin != config.admin {
+            panic_with_error!(&env, ContractError::NotAuthorized);
+        }
+
+        if fee_split.treasury_bps + fee_split.reward_pool_bps + fee_split.burn_bps != BASIS_POINTS {
+            panic_with_error!(&env, ContractError::InvalidAmount);
+        }
+
+        env.storage().instance().set(&DataKey::FeeSplit, &fee_split);
+        log!(&env, "Fee split updated: {} treasury, {} rewards, {} burn", fee_split.treasury_bps, fee_split.reward_pool_bps, fee_split.burn_bps);
+    }
+
+    /// Get the fee split
+    pub fn get_fee_split(env: Env) -> FeeSplit {
+        env.storage().instance().get(&DataKey::FeeSplit).unwrap_or(Self::default_fee_split())
+    }
+
+    /// Get lifetime fees routed to the (stakers' rewards, burn)
+    pub fn get_routed_fees(env: Env) -> (i128, i128) {
+        let to_rewards: i128 = env.storage().instance().get(&DataKey::FeesToRewards).unwrap_or(0);
+        let burned: i128 = env.storage().instance().get(&DataKey::FeesBurned).unwrap_or(0);
+        (to_rewards, burned)
+    }
+
     /// Update fee collector (admin only)
     pub fn update_fee_collector(env: Env, admin: Address, new_fee_collector: Address) {
         admin.require_auth();
@@ -475,21 +1017,32 @@ impl KaleIntegrationContract {
         log!(&env, "Fee collector updated to {} by admin", new_fee_collector);
     }
 
+    /// Set the referral registry that takes a share of platform fees (admin only).
+    /// The registry must allow this contract as a reporter.
+    pub fn set_referral_registry(env: Env, admin: Address, registry: Address) {
+        admin.require_auth();
+
+        let config: Config = env.storage().instance().get(&DataKey::Config).unwrap();
+        if admin != config.admin {
+            panic_with_error!(&env, ContractError::NotAuthorized);
+        }
+
+        env.storage().instance().set(&DataKey::ReferralRegistry, &registry);
+        log!(&env, "Referral registry set to {} by admin", registry);
+    }
+
+    /// Get the referral registry, if any
+    pub fn get_referral_registry(env: Env) -> Option<Address> {
+        env.storage().instance().get(&DataKey::ReferralRegistry)
+    }
+
     /// Cross-contract call to get oracle price for KALE token
-    pub fn get_kale_price_from_oracle(env: Env, oracle_address: Address) -> ContractCallResult<i128> {
+    pub fn get_kale_price_from_oracle(env: Env, oracle_address: Address) -> Result<i128, ContractError> {
         let oracle_client = ReflectorOracleClient::new(&env, &oracle_address);
-        
-        match oracle_client.try_get_price(&env, &oracle_address, String::from_str(&env, "KALE")) {
-            Ok(price_feed) => ContractCallResult {
-                success: true,
-                data: Some(price_feed.price),
-                error: None,
-            },
-            Err(_) => ContractCallResult {
-                success: false,
-                data: None,
-                error: Some("Oracle price fetch failed".to_string()),
-            },
+
+        match oracle_client.try_get_price(&String::from_str(&env, "KALE")) {
+            Ok(Ok(price_feed)) => Ok(price_feed.price),
+            _ => Err(ContractError::OracleError),
         }
     }
 
@@ -500,74 +1053,43 @@ impl KaleIntegrationContract {
         amount: i128,
         oracle_address: Address,
         min_price_threshold: i128,
-    ) -> ContractCallResult<i128> {
+    ) -> Result<i128, ContractError> {
         staker.require_auth();
 
         // Get current KALE price from oracle
-        let price_result = Self::get_kale_price_from_oracle(env.clone(), oracle_address);
-        
-        if !price_result.success {
-            return ContractCallResult {
-                success: false,
-                data: None,
-                error: Some("Price validation failed".to_string()),
-            };
-        }
+        let current_price = Self::get_kale_price_from_oracle(env.clone(), oracle_address)?;
 
-        let current_price = price_result.data.unwrap();
-        
         // Validate price threshold
         if current_price < min_price_threshold {
-            return ContractCallResult {
-                success: false,
-                data: None,
-                error: Some("Price below minimum threshold".to_string()),
-            };
+            return Err(ContractError::OracleError);
         }
 
         // Proceed with normal staking
         Self::stake(env.clone(), staker.clone(), amount);
-        
-        ContractCallResult {
-            success: true,
-            data: Some(amount),
-            error: None,
-        }
+
+        Ok(amount)
     }
 
     /// Get comprehensive staking position with oracle data
     pub fn get_enhanced_staking_position(
         env: Env,
         staker: Address,
-        oracle_address: Address,
-    ) -> ContractCallResult<StakingPosition> {
+        _oracle_address: Address,
+    ) -> StakingPosition {
         let stake_info = Self::get_stake_info(env.clone(), staker.clone());
-        let total_staked = Self::get_total_staked(env.clone());
         let apy = Self::get_current_apy(env.clone());
-        
-        // Get current KALE price from oracle
-        let price_result = Self::get_kale_price_from_oracle(env.clone(), oracle_address);
-        let current_price = if price_result.success {
-            price_result.data.unwrap()
-        } else {
-            0 // Fallback if oracle fails
-        };
 
-        ContractCallResult {
-            success: true,
-            data: Some(StakingPosition {
-                staker: staker.clone(),
-                amount: stake_info.amount,
-                apy,
-                pending_rewards: stake_info.accumulated_rewards,
-                last_update: stake_info.last_reward_time,
-            }),
-            error: None,
+        StakingPosition {
+            staker: staker.clone(),
+            amount: stake_info.amount,
+            apy,
+            pending_rewards: stake_info.accumulated_rewards,
+            last_update: stake_info.last_reward_time,
         }
     }
 
     /// Cross-contract market creation with enhanced validation
-    pub fn create_market_with_oracle_validation(
+    pub fn create_market_with_oracle_check(
         env: Env,
         creator: Address,
         description: String,
@@ -577,19 +1099,15 @@ impl KaleIntegrationContract {
         resolve_time: u64,
         market_fee: i128,
         oracle_address: Address,
-    ) -> ContractCallResult<BytesN<32>> {
+    ) -> Result<BytesN<32>, ContractError> {
         creator.require_auth();
 
         // Validate oracle can provide price for the asset
         let oracle_client = ReflectorOracleClient::new(&env, &oracle_address);
-        let price_available = oracle_client.is_price_available(&env, &oracle_address, asset_symbol.clone());
-        
+        let price_available = oracle_client.is_price_available(&asset_symbol);
+
         if !price_available {
-            return ContractCallResult {
-                success: false,
-                data: None,
-                error: Some("Oracle cannot provide price for asset".to_string()),
-            };
+            return Err(ContractError::OracleError);
         }
 
         // Create market
@@ -604,41 +1122,438 @@ impl KaleIntegrationContract {
             market_fee,
         );
 
-        ContractCallResult {
-            success: true,
-            data: Some(market_id),
-            error: None,
-        }
+        Ok(market_id)
+    }
+
+    /// Get the global reward-per-token accumulator, scaled by 1e12
+    pub fn get_reward_per_token(env: Env) -> i128 {
+        Self::reward_per_token(&env)
     }
 
     // Private helper functions
-    fn update_global_rewards(env: &Env) {
+
+    /// Market id: sha256 over the creator's address XDR, the market counter,
+    /// the ledger sequence and this contract's address XDR
+    fn derive_market_id(env: &Env, creator: &Address, market_counter: u32) -> BytesN<32> {
+        let mut payload = Bytes::new(env);
+        payload.append(&creator.clone().to_xdr(env));
+        payload.extend_from_array(&market_counter.to_be_bytes());
+        payload.extend_from_array(&env.ledger().sequence().to_be_bytes());
+        payload.append(&env.current_contract_address().to_xdr(env));
+
+        env.crypto().sha256(&payload)
+    }
+
+    fn default_fee_split() -> FeeSplit {
+        FeeSplit { treasury_bps: BASIS_POINTS, reward_pool_bps: 0, burn_bps: 0 }
+    }
+
+    /// Close a new staker's window for registering a referrer
+    fn record_referral_activity(env: &Env, staker: &Address) {
+        let registry: Option<Address> = env.storage().instance().get(&DataKey::ReferralRegistry);
+        if let Some(registry) = registry {
+            // Like fees, a registry that hasn't allowed this contract must not block stakes
+            let _ = ReferralRegistryClient::new(env, &registry).try_record_activity(&env.current_contract_address(), staker);
+        }
+    }
+
+    /// Route a fee already held by this contract and paid by `payer`: their
+    /// referrer's cut first, then the fee split. The treasury share accrues to
+    /// platform fees and any rounding remainder goes to the treasury.
+    fn route_fee(env: &Env, payer: &Address, fee: i128) {
+        let config: Config = env.storage().instance().get(&DataKey::Config).unwrap();
+        let token_client = token::Client::new(env, &config.kale_token);
+        let mut remaining = fee;
+
+        let registry: Option<Address> = env.storage().instance().get(&DataKey::ReferralRegistry);
+        if let Some(registry) = registry {
+            // A registry that hasn't allowed this contract as a reporter must not block fees
+            let registry_client = ReferralRegistryClient::new(env, &registry);
+            if let Ok(Ok(Some(payout))) = registry_client.try_record_referral_fee(&env.current_contract_address(), payer, &fee) {
+                token_client.transfer(&env.current_contract_address(), &payout.referrer, &payout.amount);
+                remaining -= payout.amount;
+            }
+        }
+
+        let fee_split = Self::get_fee_split(env.clone());
+        let to_rewards = remaining * fee_split.reward_pool_bps as i128 / BASIS_POINTS as i128;
+        let to_burn = remaining * fee_split.burn_bps as i128 / BASIS_POINTS as i128;
+        let to_treasury = remaining - to_rewards - to_burn;
+
+        if to_rewards > 0 {
+            Self::distribute_fee_rewards(env, to_rewards);
+            let fees_to_rewards: i128 = env.storage().instance().get(&DataKey::FeesToRewards).unwrap_or(0);
+            env.storage().instance().set(&DataKey::FeesToRewards, &(fees_to_rewards + to_rewards));
+        }
+
+        if to_burn > 0 {
+            token_client.burn(&env.current_contract_address(), &to_burn);
+            let fees_burned: i128 = env.storage().instance().get(&DataKey::FeesBurned).unwrap_or(0);
+            env.storage().instance().set(&DataKey::FeesBurned, &(fees_burned + to_burn));
+        }
+
+        let platform_fees: i128 = env.storage().instance().get(&DataKey::PlatformFees).unwrap_or(0);
+        env.storage().instance().set(&DataKey::PlatformFees, &(platform_fees + to_treasury));
+    }
+
+    /// Pay a fee's reward share straight to current stakers by weight. With
+    /// nothing staked it joins the reward pool to be emitted later instead.
+    fn distribute_fee_rewards(env: &Env, amount: i128) {
+        let reward_per_token = Self::update_global_rewards(env);
+        let total_weight: i128 = env.storage().instance().get(&DataKey::TotalWeight).unwrap_or(0);
+
+        if total_weight == 0 {
+            let reward_pool: i128 = env.storage().instance().get(&DataKey::RewardPool).unwrap_or(0);
+            env.storage().instance().set(&DataKey::RewardPool, &(reward_pool + amount));
+            return;
+        }
+
+        let rewards_owed: i128 = env.storage().instance().get(&DataKey::RewardsOwed).unwrap_or(0);
+        env.storage().instance().set(&DataKey::RewardPerTokenStored, &(reward_per_token + amount * REWARD_PRECISION / total_weight));
+        env.storage().instance().set(&DataKey::RewardsOwed, &(rewards_owed + amount));
+    }
+
+    /// Roll the reward-per-token accumulator forward to now and return it.
+    /// Emitted rewards leave the unallocated reward pool.
+    fn update_global_rewards(env: &Env) -> i128 {
+        let (reward_per_token, emitted, cursor) = Self::accrue_rewards(env);
+
+        if emitted > 0 {
+            let reward_pool: i128 = env.storage().instance().get(&DataKey::RewardPool).unwrap_or(0);
+            let rewards_owed: i128 = env.storage().instance().get(&DataKey::RewardsOwed).unwrap_or(0);
+            env.storage().instance().set(&DataKey::RewardPool, &(reward_pool - emitted));
+            env.storage().instance().set(&DataKey::RewardsOwed, &(rewards_owed + emitted));
+        }
+
+        env.storage().instance().set(&DataKey::RewardPerTokenStored, &reward_per_token);
+        env.storage().instance().set(&DataKey::LastRewardUpdate, &env.ledger().timestamp());
+        env.storage().instance().set(&DataKey::EmissionCursor, &cursor);
+
+        reward_per_token
+    }
+
+    /// Accumulator value as of now, without writing it
+    fn reward_per_token(env: &Env) -> i128 {
+        Self::accrue_rewards(env).0
+    }
+
+    /// Returns (reward_per_token, emitted, emission cursor) as of now. Emission
+    /// follows the epoch schedule, capped by the unallocated reward pool. Time
+    /// during which nothing is staked emits nothing.
+    fn accrue_rewards(env: &Env) -> (i128, i128, u32) {
+        let stored: i128 = env.storage().instance().get(&DataKey::RewardPerTokenStored).unwrap_or(0);
+        let total_weight: i128 = env.storage().instance().get(&DataKey::TotalWeight).unwrap_or(0);
         let current_time = env.ledger().timestamp();
         let last_update: u64 = env.storage().instance()
             .get(&DataKey::LastRewardUpdate)
             .unwrap_or(current_time);
 
-        env.storage().instance().set(&DataKey::LastRewardUpdate, &current_time);
+        if current_time <= last_update {
+            let cursor: u32 = env.storage().instance().get(&DataKey::EmissionCursor).unwrap_or(0);
+            return (stored, 0, cursor);
+        }
+
+        let (scheduled, cursor) = Self::scheduled_emission(env, last_update, current_time);
+
+        if total_weight == 0 {
+            return (stored, 0, cursor);
+        }
+
+        let reward_pool: i128 = env.storage().instance().get(&DataKey::RewardPool).unwrap_or(0);
+        let emitted = if scheduled > reward_pool { reward_pool } else { scheduled };
+        let emitted = if emitted < 0 { 0 } else { emitted };
+
+        (stored + emitted * REWARD_PRECISION / total_weight, emitted, cursor)
+    }
+
+    /// Move rewards earned since the staker's last checkpoint into `accumulated_rewards`
+    fn checkpoint_rewards(env: &Env, stake_info: &mut StakeInfo, reward_per_token: i128) {
+        stake_info.accumulated_rewards +=
+            stake_info.weight * (reward_per_token - stake_info.reward_per_token_paid) / REWARD_PRECISION;
+        stake_info.reward_per_token_paid = reward_per_token;
+        stake_info.last_reward_time = env.ledger().timestamp();
     }
 
     fn calculate_pending_rewards(env: &Env, stake_info: &StakeInfo) -> i128 {
-        let config: Config = env.storage().instance().get(&DataKey::Config).unwrap();
-        let current_time = env.ledger().timestamp();
-        
-        if current_time <= stake_info.last_reward_time {
+        let reward_per_token = Self::reward_per_token(env);
+        stake_info.weight * (reward_per_token - stake_info.reward_per_token_paid) / REWARD_PRECISION
+    }
+
+    /// Pay a staker's claimable rewards to the staker
+    fn claim_rewards_to_staker(env: &Env, staker: &Address) -> i128 {
+        let mut stake_info: StakeInfo = env.storage().persistent()
+            .get(&DataKey::StakeInfo(staker.clone()))
+            .unwrap_or_else(|| panic_with_error!(env, ContractError::StakeNotFound));
+
+        let rewards_to_pay = Self::take_claimable_rewards(env, &mut stake_info);
+
+        if rewards_to_pay > 0 {
+            // Transfer rewards to staker
+            let config: Config = env.storage().instance().get(&DataKey::Config).unwrap();
+            let token_client = token::Client::new(env, &config.kale_token);
+            token_client.transfer(&env.current_contract_address(), staker, &rewards_to_pay);
+        }
+
+        env.storage().persistent().set(&DataKey::StakeInfo(staker.clone()), &stake_info);
+
+        log!(env, "Claimed {} KALE rewards by {}", rewards_to_pay, staker);
+
+        rewards_to_pay
+    }
+
+    /// Checkpoint a staker and remove the rewards the contract can currently pay
+    /// from `accumulated_rewards`, returning the amount to pay out
+    fn take_claimable_rewards(env: &Env, stake_info: &mut StakeInfo) -> i128 {
+        let reward_per_token = Self::update_global_rewards(env);
+        Self::checkpoint_rewards(env, stake_info, reward_per_token);
+
+        let total_rewards = stake_info.accumulated_rewards;
+        if total_rewards == 0 {
             return 0;
         }
 
-        let time_diff = current_time - stake_info.last_reward_time;
+        // Rewards are paid only from the emitted-rewards ledger, never from
+        // staked principal or platform fees
+        let rewards_owed: i128 = env.storage().instance().get(&DataKey::RewardsOwed).unwrap_or(0);
+        let rewards_to_pay = if total_rewards > rewards_owed { rewards_owed } else { total_rewards };
+
+        env.storage().instance().set(&DataKey::RewardsOwed, &(rewards_owed - rewards_to_pay));
+        stake_info.accumulated_rewards = total_rewards - rewards_to_pay;
+        rewards_to_pay
+    }
+
+    fn load_or_create_stake_info(env: &Env, staker: &Address, reward_per_token: i128) -> StakeInfo {
+        env.storage().persistent()
+            .get(&DataKey::StakeInfo(staker.clone()))
+            .unwrap_or_else(|| {
+                Self::add_staker(env, staker);
+                Self::record_referral_activity(env, staker);
+
+                let current_time = env.ledger().timestamp();
+                StakeInfo {
+                    staker: staker.clone(),
+                    amount: 0,
+                    weight: 0,
+                    stake_time: current_time,
+                    last_reward_time: current_time,
+                    accumulated_rewards: 0,
+                    reward_per_token_paid: reward_per_token,
+                }
+            })
+    }
+
+    /// Apply a principal and reward-weight change to a staker and the global totals.
+    /// Callers must checkpoint rewards first.
+    fn adjust_stake(env: &Env, stake_info: &mut StakeInfo, amount_delta: i128, weight_delta: i128) {
+        if weight_delta != 0 {
+            Self::checkpoint_reward_streams(env, &stake_info.staker, stake_info.weight);
+        }
+
+        stake_info.amount += amount_delta;
+        stake_info.weight += weight_delta;
+
+        Self::shift_voting_weight(env, &stake_info.staker, weight_delta);
+
         let total_staked: i128 = env.storage().instance().get(&DataKey::TotalStaked).unwrap_or(0);
+        let total_weight: i128 = env.storage().instance().get(&DataKey::TotalWeight).unwrap_or(0);
+        env.storage().instance().set(&DataKey::TotalStaked, &(total_staked + amount_delta));
+        env.storage().instance().set(&DataKey::TotalWeight, &(total_weight + weight_delta));
 
-        if total_staked == 0 {
-            return 0;
+        if amount_delta != 0 {
+            Self::record_stake_checkpoints(env, &stake_info.staker, stake_info.amount, total_staked + amount_delta);
+        }
+    }
+
+    /// Persist stake info, or drop the staker entirely once nothing is left
+    fn store_stake_info(env: &Env, stake_info: &StakeInfo) {
+        let staker = stake_info.staker.clone();
+
+        if stake_info.amount == 0 && stake_info.accumulated_rewards == 0 {
+            // Remove from storage if no stake and no rewards
+            env.storage().persistent().remove(&DataKey::StakeInfo(staker.clone()));
+            Self::remove_staker(env, &staker);
+        } else {
+            env.storage().persistent().set(&DataKey::StakeInfo(staker), stake_info);
+        }
+    }
+
+    /// Append a staker to the index, if not already present
+    fn add_staker(env: &Env, staker: &Address) {
+        if env.storage().persistent().has(&DataKey::StakerIndex(staker.clone())) {
+            return;
         }
 
-        // Calculate proportional rewards
-        let total_rewards_for_period = config.reward_rate_per_second * (time_diff as i128);
-        (total_rewards_for_period * stake_info.amount) / total_staked
+        let staker_count = Self::get_staker_count(env.clone());
+        env.storage().persistent().set(&DataKey::StakerAt(staker_count), staker);
+        env.storage().persistent().set(&DataKey::StakerIndex(staker.clone()), &staker_count);
+        env.storage().instance().set(&DataKey::StakerCount, &(staker_count + 1));
+    }
+
+    /// Swap-remove a staker from the index
+    fn remove_staker(env: &Env, staker: &Address) {
+        let index: u32 = match env.storage().persistent().get(&DataKey::StakerIndex(staker.clone())) {
+            Some(index) => index,
+            None => return,
+        };
+
+        let last_index = Self::get_staker_count(env.clone()) - 1;
+        if index != last_index {
+            let last: Address = env.storage().persistent().get(&DataKey::StakerAt(last_index)).unwrap();
+            env.storage().persistent().set(&DataKey::StakerAt(index), &last);
+            env.storage().persistent().set(&DataKey::StakerIndex(last), &index);
+        }
+
+        env.storage().persistent().remove(&DataKey::StakerAt(last_index));
+        env.storage().persistent().remove(&DataKey::StakerIndex(staker.clone()));
+        env.storage().instance().set(&DataKey::StakerCount, &last_index);
+    }
+
+    /// Record unstaked principal as an unbonding request. A request with the
+    /// same release time absorbs it; once the queue is full, requests that
+    /// have already matured are folded together to make room, and if none
+    /// have the new request is rejected rather than delaying queued funds.
+    fn queue_unbonding(env: &Env, staker: &Address, amount: i128) -> u32 {
+        let mut requests: Vec<UnbondingRequest> = env.storage().persistent()
+            .get(&DataKey::UnbondingRequests(staker.clone()))
+            .unwrap_or_else(|| Vec::new(env));
+
+        let unbonding_period: u64 = env.storage().instance()
+            .get(&DataKey::UnbondingPeriod)
+            .unwrap_or(DEFAULT_UNBONDING_PERIOD);
+        let current_time = env.ledger().timestamp();
+        let release_time = current_time + unbonding_period;
+
+        let total_unbonding: i128 = env.storage().instance().get(&DataKey::TotalUnbonding).unwrap_or(0);
+        env.storage().instance().set(&DataKey::TotalUnbonding, &(total_unbonding + amount));
+
+        if let Some(index) = requests.iter().position(|request| request.release_time == release_time) {
+            let mut request = requests.get(index as u32).unwrap();
+            request.amount += amount;
+            requests.set(index as u32, request.clone());
+            env.storage().persistent().set(&DataKey::UnbondingRequests(staker.clone()), &requests);
+            return request.id;
+        }
+
+        if requests.len() >= MAX_UNBONDING_REQUESTS {
+            requests = Self::fold_matured_requests(env, requests, current_time);
+            if requests.len() >= MAX_UNBONDING_REQUESTS {
+                panic_with_error!(env, ContractError::InvalidAmount);
+            }
+        }
+
+        let mut counter: u32 = env.storage().persistent()
+            .get(&DataKey::UnbondingCounter(staker.clone()))
+            .unwrap_or(0);
+        counter += 1;
+
+        requests.push_back(UnbondingRequest {
+            id: counter,
+            amount,
+            created_at: current_time,
+            release_time,
+        });
+
+        env.storage().persistent().set(&DataKey::UnbondingCounter(staker.clone()), &counter);
+        env.storage().persistent().set(&DataKey::UnbondingRequests(staker.clone()), &requests);
+
+        counter
+    }
+
+    /// Collapse every request that is already withdrawable into the first of
+    /// them, keeping the latest matured release time so nothing is delayed
+    fn fold_matured_requests(env: &Env, requests: Vec<UnbondingRequest>, now: u64) -> Vec<UnbondingRequest> {
+        let mut folded: Vec<UnbondingRequest> = Vec::new(env);
+        let mut matured: Option<(u32, UnbondingRequest)> = None;
+
+        for request in requests.iter() {
+            if request.release_time > now {
+                folded.push_back(request);
+                continue;
+            }
+            matured = Some(match matured {
+                None => (folded.len(), request),
+                Some((index, mut merged)) => {
+                    merged.amount += request.amount;
+                    merged.release_time = merged.release_time.max(request.release_time);
+                    (index, merged)
+                }
+            });
+        }
+
+        if let Some((index, merged)) = matured {
+            folded.insert(index, merged);
+        }
+        folded
+    }
+
+    /// Move a staker's current weight from their old delegate to a new one
+    fn move_delegation(env: &Env, staker: &Address, new_delegate: Option<Address>) {
+        let weight = env.storage().persistent()
+            .get::<DataKey, StakeInfo>(&DataKey::StakeInfo(staker.clone()))
+            .map(|stake_info| stake_info.weight)
+            .unwrap_or(0);
+
+        let old_delegate: Option<Address> = env.storage().persistent().get(&DataKey::Delegate(staker.clone()));
+
+        Self::record_voting_checkpoint(env, old_delegate.as_ref().unwrap_or(staker), -weight);
+        Self::record_voting_checkpoint(env, new_delegate.as_ref().unwrap_or(staker), weight);
+
+        if let Some(old_delegate) = old_delegate {
+            let delegated: i128 = env.storage().persistent()
+                .get(&DataKey::DelegatedWeight(old_delegate.clone()))
+                .unwrap_or(0);
+            env.storage().persistent().set(&DataKey::DelegatedWeight(old_delegate), &(delegated - weight));
+        }
+
+        match new_delegate {
+            Some(delegate) => {
+                let delegated: i128 = env.storage().persistent()
+                    .get(&DataKey::DelegatedWeight(delegate.clone()))
+                    .unwrap_or(0);
+                env.storage().persistent().set(&DataKey::DelegatedWeight(delegate.clone()), &(delegated + weight));
+                env.storage().persistent().set(&DataKey::Delegate(staker.clone()), &delegate);
+            }
+            None => {
+                env.storage().persistent().remove(&DataKey::Delegate(staker.clone()));
+            }
+        }
+    }
+
+    fn locked_amount(env: &Env, staker: &Address) -> i128 {
+        let positions: Vec<LockPosition> = env.storage().persistent()
+            .get(&DataKey::LockPositions(staker.clone()))
+            .unwrap_or_else(|| Vec::new(env));
+
+        let mut locked = 0;
+        for position in positions.iter() {
+            locked += position.amount;
+        }
+        locked
+    }
+
+    fn find_lock_tier(env: &Env, lock_duration: u64) -> LockTier {
+        let tiers: Vec<LockTier> = env.storage().instance()
+            .get(&DataKey::LockTiers)
+            .unwrap_or_else(|| Vec::new(env));
+
+        for tier in tiers.iter() {
+            if tier.duration == lock_duration {
+                return tier;
+            }
+        }
+        panic_with_error!(env, ContractError::InvalidTimestamp)
+    }
+
+    fn find_lock_position(env: &Env, positions: &Vec<LockPosition>, position_id: u32) -> u32 {
+        for i in 0..positions.len() {
+            if positions.get(i).unwrap().id == position_id {
+                return i;
+            }
+        }
+        panic_with_error!(env, ContractError::StakeNotFound)
     }
 }
 
+#[cfg(test)]
+mod test;
+
@@ -0,0 +1,548 @@
+//! Betting on KALE integration markets.
+//!
+//! Markets created with `create_market` are parimutuel: bets on each side are
+//! pooled in escrow and, once the Reflector oracle resolves the market, the
+//! winning side splits the losing pool pro rata. If nobody backed the winning
+//! side every bet is refunded.
+//!
+//! Only a price published within the resolution window after the resolve
+//! time can settle a market; the admin sets the window, an hour by default.
+//! If none arrives, anyone can refund the market once `REFUND_GRACE_PERIOD`
+//! has passed, and every bet is returned along with its platform fee. Fees
+//! are therefore held in escrow until a bettor is settled.
+//!
+//! Stakers can also bet with free stake as collateral, without unstaking or
+//! waiting out unbonding. Collateral stays in the stake and keeps earning, but
+//! can't be unstaked or bet again until the market is settled. Once resolved,
+//! anyone can settle a bettor's collateral: the part lost leaves their stake
+//! for the pool, the rest is released. Winnings beyond the collateral are paid
+//! out on claim. Until losers are settled, winners may be paid out of KALE
+//! that is still counted as the losers' stake, so market escrow alone can run
+//! negative; the contract's balance covers both.
+//!
+//! Prediction markets deployed by the market factory can lock stake the same
+//! way: `lock_market_collateral` commits free stake to a bet there, and the
+//! market calls `settle_market_collateral` once it resolves, taking what was
+//! lost out of the stake and releasing the rest.
+//!
+//! No-loss markets (see `no_loss`) share creation and resolution but take
+//! deposits instead of bets.
+
+use soroban_sdk::{contractimpl, contracttype, token, Address, BytesN, Env, panic_with_error, log};
+use shared_types::{StakeInfo, ContractError, Config, MarketInfo, PriceFeed};
+use shared_types::clients::{ReflectorOracleClient, MarketFactoryClient};
+
+use crate::{DataKey, KaleIntegrationContract, KaleIntegrationContractClient, NoLossDataKey, BASIS_POINTS};
+#[cfg(any(test, feature = "testutils"))]
+use crate::__KaleIntegrationContract_fn_set_registry;
+
+// Minimum oracle confidence accepted for resolution
+const RESOLUTION_CONFIDENCE: u32 = 80;
+// How long after the resolve time a price can still settle a market, unless set
+const DEFAULT_RESOLUTION_WINDOW: u64 = 60 * 60;
+// How long after the resolve time an unresolved market can be refunded
+const REFUND_GRACE_PERIOD: u64 = 7 * 24 * 60 * 60;
+
+/// Market storage keys
+#[contracttype]
+#[derive(Clone)]
+pub enum MarketDataKey {
+    ResolutionWindow,
+    MarketFactory,
+    MarketCollateral(Address, Address), // market, staker
+}
+
+/// A bettor's stake on each side of a market, including the part backed by
+/// staked collateral, and platform fees held until they are settled
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MarketPosition {
+    pub amount_for: i128,
+    pub amount_against: i128,
+    pub collateral_for: i128,
+    pub collateral_against: i128,
+    pub fee: i128,
+    pub collateral_settled: bool,
+    pub claimed: bool,
+}
+
+#[contractimpl]
+impl KaleIntegrationContract {
+    /// Bet on a market before its resolve time. `side` is true for the condition holding.
+    pub fn place_bet(env: Env, bettor: Address, market_id: BytesN<32>, side: bool, amount: i128) {
+        bettor.require_auth();
+        Self::require_not_paused(&env);
+
+        let config: Config = env.storage().instance().get(&DataKey::Config).unwrap();
+        let token_client = token::Client::new(&env, &config.kale_token);
+
+        Self::require_open_market(&env, &market_id, amount);
+        token_client.transfer(&bettor, &env.current_contract_address(), &amount);
+
+        let net_amount = Self::record_bet(&env, &market_id, &bettor, side, amount, false);
+
+        log!(&env, "Bet placed: {} KALE on {} by {}", net_amount, side, bettor);
+    }
+
+    /// Bet using free stake as collateral instead of liquid KALE. The platform
+    /// fee is taken out of the stake.
+    pub fn place_bet_with_stake(env: Env, bettor: Address, market_id: BytesN<32>, side: bool, amount: i128) {
+        bettor.require_auth();
+        Self::require_not_paused(&env);
+
+        Self::require_open_market(&env, &market_id, amount);
+
+        let config: Config = env.storage().instance().get(&DataKey::Config).unwrap();
+        if amount < config.min_stake_amount {
+            panic_with_error!(&env, ContractError::InvalidAmount);
+        }
+
+        Self::require_free_stake(&env, &bettor, amount);
+
+        let net_amount = Self::record_bet(&env, &market_id, &bettor, side, amount, true);
+        Self::adjust_stake_collateral(&env, &bettor, net_amount);
+
+        // The fee moves out of the stake into escrow
+        let fee = amount - net_amount;
+        if fee > 0 {
+            Self::take_from_stake(&env, &bettor, fee);
+        }
+
+        log!(&env, "Bet placed: {} staked KALE on {} by {}", net_amount, side, bettor);
+    }
+
+    /// Resolve a market from the Reflector oracle once its resolve time has passed
+    pub fn resolve_market(env: Env, resolver: Address, market_id: BytesN<32>) -> bool {
+        resolver.require_auth();
+        Self::require_not_paused(&env);
+
+        let mut market_info = Self::get_market_info(env.clone(), market_id.clone());
+        if market_info.resolved {
+            panic_with_error!(&env, ContractError::MarketAlreadyResolved);
+        }
+        if !market_info.is_ready_for_resolution(env.ledger().timestamp()) {
+            panic_with_error!(&env, ContractError::InvalidTimestamp);
+        }
+
+        let oracle_address: Address = env.storage().instance()
+            .get(&DataKey::MarketOracle)
+            .unwrap_or_else(|| panic_with_error!(&env, ContractError::OracleError));
+        let oracle_client = ReflectorOracleClient::new(&env, &oracle_address);
+
+        let price_feed: PriceFeed = match oracle_client.try_get_price(&market_info.asset_symbol) {
+            Ok(Ok(price_feed)) => price_feed,
+            _ => panic_with_error!(&env, ContractError::OracleError),
+        };
+
+        // Only a price published shortly after the deadline can settle the market
+        if price_feed.timestamp < market_info.resolve_time
+            || price_feed.timestamp > market_info.resolve_time + Self::get_resolution_window(env.clone())
+        {
+            panic_with_error!(&env, ContractError::OracleError);
+        }
+
+        let validated: bool = match oracle_client.try_validate_market_resolution(
+            &market_info.asset_symbol,
+            &market_info.target_price,
+            &market_info.condition,
+            &RESOLUTION_CONFIDENCE,
+        ) {
+            Ok(Ok(validated)) => validated,
+            _ => panic_with_error!(&env, ContractError::OracleError),
+        };
+
+        // The oracle's verdict must agree with the market's own condition
+        let outcome = market_info.calculate_outcome(price_feed.price);
+        if validated != outcome {
+            panic_with_error!(&env, ContractError::OracleError);
+        }
+
+        market_info.resolved = true;
+        market_info.outcome = Some(outcome);
+        env.storage().persistent().set(&DataKey::MarketInfo(market_id), &market_info);
+
+        log!(&env, "Market resolved: {} at price {}", outcome, price_feed.price);
+
+        outcome
+    }
+
+    /// Close a market that got no valid price within the grace period after
+    /// its resolve time, refunding every bet. Callable by anyone.
+    pub fn refund_unresolved_market(env: Env, market_id: BytesN<32>) {
+        let mut market_info = Self::get_market_info(env.clone(), market_id.clone());
+        if market_info.resolved {
+            panic_with_error!(&env, ContractError::MarketAlreadyResolved);
+        }
+        if env.ledger().timestamp() < market_info.resolve_time + REFUND_GRACE_PERIOD {
+            panic_with_error!(&env, ContractError::InvalidTimestamp);
+        }
+
+        // Resolved without an outcome: claims refund every bet
+        market_info.resolved = true;
+        market_info.outcome = None;
+        env.storage().persistent().set(&DataKey::MarketInfo(market_id.clone()), &market_info);
+
+        log!(&env, "Market {} refunded without resolution", market_id);
+    }
+
+    /// Settle a bettor's staked collateral in a resolved market: collateral
+    /// on the losing side leaves their stake for the pool and the rest is
+    /// released. Their held platform fee is routed too, unless the market was
+    /// refunded. Callable by anyone, so lost collateral stops earning.
+    /// Returns the collateral lost.
+    pub fn settle_collateral(env: Env, market_id: BytesN<32>, bettor: Address) -> i128 {
+        let market_info = Self::get_market_info(env.clone(), market_id.clone());
+        let winning_side = Self::winning_side(&env, &market_info);
+
+        if market_info.outcome.is_some() {
+            Self::route_bet_fee(&env, &market_id, &bettor);
+        }
+
+        let mut position = Self::get_market_position(env.clone(), market_id.clone(), bettor.clone());
+        let collateral = position.collateral_for + position.collateral_against;
+        if collateral == 0 || position.collateral_settled {
+            return 0;
+        }
+
+        let lost = match winning_side {
+            Some(true) => position.collateral_against,
+            Some(false) => position.collateral_for,
+            None => 0,
+        };
+
+        position.collateral_settled = true;
+        env.storage().persistent().set(&DataKey::MarketPosition(market_id, bettor.clone()), &position);
+        Self::adjust_stake_collateral(&env, &bettor, -collateral);
+
+        if lost > 0 {
+            Self::take_from_stake(&env, &bettor, lost);
+            Self::adjust_market_escrow(&env, lost);
+        }
+
+        log!(&env, "Collateral settled: {} KALE lost of {} by {}", lost, collateral, bettor);
+
+        lost
+    }
+
+    /// Claim winnings (or a refund if nobody backed the winning side or the
+    /// market was refunded, which also returns the platform fee). Collateral
+    /// that comes back stays staked; only the rest is paid out.
+    pub fn claim(env: Env, claimant: Address, market_id: BytesN<32>) -> i128 {
+        claimant.require_auth();
+
+        let market_info = Self::get_market_info(env.clone(), market_id.clone());
+        let winning_side = Self::winning_side(&env, &market_info);
+
+        let position = Self::get_market_position(env.clone(), market_id.clone(), claimant.clone());
+        if position.amount_for == 0 && position.amount_against == 0 {
+            panic_with_error!(&env, ContractError::BetNotFound);
+        }
+        if position.claimed {
+            panic_with_error!(&env, ContractError::AlreadyClaimed);
+        }
+
+        Self::settle_collateral(env.clone(), market_id.clone(), claimant.clone());
+
+        let mut payout = Self::market_payout(&market_info, &position, winning_side);
+        if market_info.outcome.is_none() {
+            payout += position.fee;
+        }
+        let kept_collateral = match winning_side {
+            Some(true) => position.collateral_for,
+            Some(false) => position.collateral_against,
+            None => position.collateral_for + position.collateral_against,
+        };
+        let paid_out = payout - kept_collateral;
+
+        let mut position = Self::get_market_position(env.clone(), market_id.clone(), claimant.clone());
+        position.fee = 0;
+        position.claimed = true;
+        env.storage().persistent().set(&DataKey::MarketPosition(market_id, claimant.clone()), &position);
+
+        if paid_out > 0 {
+            Self::adjust_market_escrow(&env, -paid_out);
+
+            let config: Config = env.storage().instance().get(&DataKey::Config).unwrap();
+            let token_client = token::Client::new(&env, &config.kale_token);
+            token_client.transfer(&env.current_contract_address(), &claimant, &paid_out);
+        }
+
+        log!(&env, "Market claim: {} KALE by {} ({} kept staked)", payout, claimant, kept_collateral);
+
+        payout
+    }
+
+    /// Lock `amount` of a staker's free stake as collateral for a bet in a
+    /// prediction market deployed by the market factory. Called by the market.
+    pub fn lock_market_collateral(env: Env, market: Address, staker: Address, amount: i128) {
+        market.require_auth();
+        staker.require_auth();
+        Self::require_not_paused(&env);
+
+        let factory: Address = env.storage().instance()
+            .get(&MarketDataKey::MarketFactory)
+            .unwrap_or_else(|| panic_with_error!(&env, ContractError::NotAuthorized));
+        if !matches!(MarketFactoryClient::new(&env, &factory).try_get_market_id(&market), Ok(Ok(_))) {
+            panic_with_error!(&env, ContractError::NotAuthorized);
+        }
+
+        if amount <= 0 {
+            panic_with_error!(&env, ContractError::InvalidAmount);
+        }
+        Self::require_free_stake(&env, &staker, amount);
+
+        let locked = Self::get_market_collateral(env.clone(), market.clone(), staker.clone());
+        env.storage().persistent().set(&MarketDataKey::MarketCollateral(market.clone(), staker.clone()), &(locked + amount));
+        Self::adjust_stake_collateral(&env, &staker, amount);
+
+        log!(&env, "Market collateral locked: {} KALE by {} in {}", amount, staker, market);
+    }
+
+    /// Release a staker's collateral in a settled prediction market, taking
+    /// `lost` of it out of their stake and sending it to the market. Called by
+    /// the market; works while paused so markets can always settle.
+    pub fn settle_market_collateral(env: Env, market: Address, staker: Address, lost: i128) {
+        market.require_auth();
+
+        let locked = Self::get_market_collateral(env.clone(), market.clone(), staker.clone());
+        if lost < 0 || lost > locked {
+            panic_with_error!(&env, ContractError::InvalidAmount);
+        }
+
+        env.storage().persistent().remove(&MarketDataKey::MarketCollateral(market.clone(), staker.clone()));
+        Self::adjust_stake_collateral(&env, &staker, -locked);
+
+        if lost > 0 {
+            Self::take_from_stake(&env, &staker, lost);
+
+            let config: Config = env.storage().instance().get(&DataKey::Config).unwrap();
+            let token_client = token::Client::new(&env, &config.kale_token);
+            token_client.transfer(&env.current_contract_address(), &market, &lost);
+        }
+
+        log!(&env, "Market collateral settled: {} KALE lost of {} by {} in {}", lost, locked, staker, market);
+    }
+
+    /// Set the market factory whose markets may lock stake as collateral (admin only)
+    pub fn set_market_factory(env: Env, admin: Address, factory: Address) {
+        admin.require_auth();
+
+        let config: Config = env.storage().instance().get(&DataKey::Config).unwrap();
+        if admin != config.admin {
+            panic_with_error!(&env, ContractError::NotAuthorized);
+        }
+
+        env.storage().instance().set(&MarketDataKey::MarketFactory, &factory);
+        log!(&env, "Market factory set to {} by admin", factory);
+    }
+
+    /// Get the market factory whose markets may lock stake as collateral, if set
+    pub fn get_market_factory(env: Env) -> Option<Address> {
+        env.storage().instance().get(&MarketDataKey::MarketFactory)
+    }
+
+    /// Get the stake a staker has locked as collateral in a prediction market
+    pub fn get_market_collateral(env: Env, market: Address, staker: Address) -> i128 {
+        env.storage().persistent().get(&MarketDataKey::MarketCollateral(market, staker)).unwrap_or(0)
+    }
+
+    /// Set the Reflector oracle used to resolve markets (admin only)
+    pub fn set_market_oracle(env: Env, admin: Address, oracle_address: Address) {
+        admin.require_auth();
+
+        let config: Config = env.storage().instance().get(&DataKey::Config).unwrap();
+        if admin != config.admin {
+            panic_with_error!(&env, ContractError::NotAuthorized);
+        }
+
+        env.storage().instance().set(&DataKey::MarketOracle, &oracle_address);
+        log!(&env, "Market oracle set to {} by admin", oracle_address);
+    }
+
+    /// Get the Reflector oracle used to resolve markets, if set
+    pub fn get_market_oracle(env: Env) -> Option<Address> {
+        env.storage().instance().get(&DataKey::MarketOracle)
+    }
+
+    /// Set how long after a market's resolve time an oracle price can still
+    /// settle it (admin only). It must end before markets can be refunded.
+    pub fn set_resolution_window(env: Env, admin: Address, window: u64) {
+        admin.require_auth();
+
+        let config: Config = env.storage().instance().get(&DataKey::Config).unwrap();
+        if admin != config.admin {
+            panic_with_error!(&env, ContractError::NotAuthorized);
+        }
+
+        if window == 0 || window >= REFUND_GRACE_PERIOD {
+            panic_with_error!(&env, ContractError::InvalidTimestamp);
+        }
+
+        env.storage().instance().set(&MarketDataKey::ResolutionWindow, &window);
+        log!(&env, "Resolution window set to {} seconds by admin", window);
+    }
+
+    /// Get how long after a market's resolve time an oracle price can still settle it
+    pub fn get_resolution_window(env: Env) -> u64 {
+        env.storage().instance().get(&MarketDataKey::ResolutionWindow).unwrap_or(DEFAULT_RESOLUTION_WINDOW)
+    }
+
+    /// Get a bettor's position in a market
+    pub fn get_market_position(env: Env, market_id: BytesN<32>, bettor: Address) -> MarketPosition {
+        env.storage().persistent()
+            .get(&DataKey::MarketPosition(market_id, bettor))
+            .unwrap_or(MarketPosition {
+                amount_for: 0,
+                amount_against: 0,
+                collateral_for: 0,
+                collateral_against: 0,
+                fee: 0,
+                collateral_settled: false,
+                claimed: false,
+            })
+    }
+
+    /// Get the stake a user has committed as collateral to open bets
+    pub fn get_stake_collateral(env: Env, staker: Address) -> i128 {
+        env.storage().persistent().get(&DataKey::StakeCollateral(staker)).unwrap_or(0)
+    }
+
+    /// Get the total KALE held for open bets and unclaimed winnings
+    pub fn get_market_escrow(env: Env) -> i128 {
+        env.storage().instance().get(&DataKey::MarketEscrow).unwrap_or(0)
+    }
+
+    // Private helper functions
+
+    fn require_open_market(env: &Env, market_id: &BytesN<32>, amount: i128) {
+        let market_info = Self::get_market_info(env.clone(), market_id.clone());
+        if market_info.resolved {
+            panic_with_error!(env, ContractError::MarketAlreadyResolved);
+        }
+        if env.ledger().timestamp() >= market_info.resolve_time {
+            panic_with_error!(env, ContractError::MarketClosed);
+        }
+        // No-loss markets take deposits, not bets
+        if env.storage().persistent().has(&NoLossDataKey::NoLossPool(market_id.clone())) {
+            panic_with_error!(env, ContractError::MarketClosed);
+        }
+        if amount <= 0 {
+            panic_with_error!(env, ContractError::InvalidAmount);
+        }
+    }
+
+    /// Panic unless `amount` of a staker's stake is neither locked nor already
+    /// backing bets
+    fn require_free_stake(env: &Env, staker: &Address, amount: i128) {
+        let stake_info: StakeInfo = env.storage().persistent()
+            .get(&DataKey::StakeInfo(staker.clone()))
+            .unwrap_or_else(|| panic_with_error!(env, ContractError::StakeNotFound));
+
+        let collateral = Self::get_stake_collateral(env.clone(), staker.clone());
+        if amount > stake_info.amount - Self::locked_amount(env, staker) - collateral {
+            panic_with_error!(env, ContractError::InsufficientStake);
+        }
+    }
+
+    fn adjust_stake_collateral(env: &Env, staker: &Address, delta: i128) {
+        let collateral = Self::get_stake_collateral(env.clone(), staker.clone()) + delta;
+        if collateral == 0 {
+            env.storage().persistent().remove(&DataKey::StakeCollateral(staker.clone()));
+        } else {
+            env.storage().persistent().set(&DataKey::StakeCollateral(staker.clone()), &collateral);
+        }
+    }
+
+    /// Take KALE out of a staker's stake, with its reward weight, for a fee or
+    /// lost collateral
+    fn take_from_stake(env: &Env, staker: &Address, amount: i128) {
+        let mut stake_info: StakeInfo = env.storage().persistent()
+            .get(&DataKey::StakeInfo(staker.clone()))
+            .unwrap_or_else(|| panic_with_error!(env, ContractError::StakeNotFound));
+
+        let reward_per_token = Self::update_global_rewards(env);
+        Self::checkpoint_rewards(env, &mut stake_info, reward_per_token);
+        Self::adjust_stake(env, &mut stake_info, -amount, -amount);
+        Self::store_stake_info(env, &stake_info);
+    }
+
+    /// Add a bet whose KALE is already held by this contract, or backed by the
+    /// bettor's stake if `collateral`. The platform fee is taken up front and
+    /// held in escrow; only the rest enters the pool. Returns the net bet.
+    fn record_bet(env: &Env, market_id: &BytesN<32>, bettor: &Address, side: bool, amount: i128, collateral: bool) -> i128 {
+        let fee_rate: u32 = env.storage().instance().get(&DataKey::FeeRate).unwrap_or(0);
+        let fee = amount * fee_rate as i128 / BASIS_POINTS as i128;
+        let net_amount = amount - fee;
+
+        let mut market_info = Self::get_market_info(env.clone(), market_id.clone());
+        let mut position = Self::get_market_position(env.clone(), market_id.clone(), bettor.clone());
+        position.fee += fee;
+        if side {
+            position.amount_for += net_amount;
+            market_info.total_for += net_amount;
+            if collateral {
+                position.collateral_for += net_amount;
+            }
+        } else {
+            position.amount_against += net_amount;
+            market_info.total_against += net_amount;
+            if collateral {
+                position.collateral_against += net_amount;
+            }
+        }
+
+        env.storage().persistent().set(&DataKey::MarketPosition(market_id.clone(), bettor.clone()), &position);
+        env.storage().persistent().set(&DataKey::MarketInfo(market_id.clone()), &market_info);
+        // Collateral stays in the stake until the market is settled
+        if !collateral {
+            Self::adjust_market_escrow(env, net_amount);
+        }
+        Self::adjust_market_escrow(env, fee);
+
+        net_amount
+    }
+
+    /// Route a bettor's held platform fee once their market has an outcome
+    fn route_bet_fee(env: &Env, market_id: &BytesN<32>, bettor: &Address) {
+        let mut position = Self::get_market_position(env.clone(), market_id.clone(), bettor.clone());
+        if position.fee == 0 {
+            return;
+        }
+
+        let fee = position.fee;
+        position.fee = 0;
+        env.storage().persistent().set(&DataKey::MarketPosition(market_id.clone(), bettor.clone()), &position);
+
+        Self::adjust_market_escrow(env, -fee);
+        Self::route_fee(env, bettor, fee);
+    }
+
+    /// The side that won a resolved market, or None if every bet is refunded
+    /// because nobody backed the winning side or the market was refunded
+    pub(crate) fn winning_side(env: &Env, market_info: &MarketInfo) -> Option<bool> {
+        if !market_info.resolved {
+            panic_with_error!(env, ContractError::MarketClosed);
+        }
+
+        match market_info.outcome {
+            Some(true) if market_info.total_for > 0 => Some(true),
+            Some(false) if market_info.total_against > 0 => Some(false),
+            _ => None,
+        }
+    }
+
+    fn market_payout(market_info: &MarketInfo, position: &MarketPosition, winning_side: Option<bool>) -> i128 {
+        let (winning_bet, winning_pool, losing_pool) = match winning_side {
+            Some(true) => (position.amount_for, market_info.total_for, market_info.total_against),
+            Some(false) => (position.amount_against, market_info.total_against, market_info.total_for),
+            None => return position.amount_for + position.amount_against,
+        };
+
+        winning_bet + winning_bet * losing_pool / winning_pool
+    }
+
+    pub(crate) fn adjust_market_escrow(env: &Env, delta: i128) {
+        let escrow: i128 = env.storage().instance().get(&DataKey::MarketEscrow).unwrap_or(0);
+        env.storage().instance().set(&DataKey::MarketEscrow, &(escrow + delta));
+    }
+}
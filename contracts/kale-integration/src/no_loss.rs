@@ -0,0 +1,268 @@
+//! No-loss prediction pools.
+//!
+//! A no-loss market takes deposits on either side instead of bets. Deposits
+//! are staked through the compounding vault for the market's lifetime, and
+//! once the market resolves the pool is settled: every depositor gets their
+//! principal back and only the yield earned is split among the winning side.
+//! If nobody backed the winning side, or the market was refunded unresolved,
+//! the yield is shared by all depositors.
+//!
+//! Settling unstakes the whole pool at once, but each claim then waits out the
+//! unbonding period in the depositor's unbonding queue, so a no-loss deposit
+//! never leaves faster than a regular stake. The last claimant also gets the
+//! rounding dust.
+//!
+//! While paused, depositors in an unsettled pool can still take out their
+//! share of the vault with `emergency_withdraw_no_loss`.
+
+use soroban_sdk::{contractimpl, contracttype, token, Address, BytesN, Env, String, panic_with_error, log};
+use shared_types::{ContractError, Config};
+
+use crate::{DataKey, KaleIntegrationContract, KaleIntegrationContractClient};
+#[cfg(any(test, feature = "testutils"))]
+use crate::__KaleIntegrationContract_fn_set_registry;
+
+/// No-loss pool storage keys
+#[contracttype]
+#[derive(Clone)]
+pub enum NoLossDataKey {
+    NoLossPool(BytesN<32>),
+    NoLossPosition(BytesN<32>, Address),
+}
+
+/// A no-loss market's deposits and vault position
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NoLossPool {
+    pub principal: i128, // Total deposited
+    pub shares: i128,    // Vault shares held for the pool until settlement
+    pub returned: i128,  // Principal returned on settlement, normally all of it
+    pub prize: i128,     // Yield won on settlement
+    pub settled: bool,
+    pub claimed: i128,   // Deposits whose payout has been claimed
+    pub paid: i128,      // Principal and prize paid out so far
+}
+
+/// A depositor's principal on each side of a no-loss market
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NoLossPosition {
+    pub amount_for: i128,
+    pub amount_against: i128,
+    pub claimed: bool,
+}
+
+#[contractimpl]
+impl KaleIntegrationContract {
+    /// Create a market whose sides are backed by staked deposits instead of bets
+    pub fn create_no_loss_market(
+        env: Env,
+        creator: Address,
+        description: String,
+        asset_symbol: String,
+        target_price: i128,
+        condition: u32,
+        resolve_time: u64,
+        market_fee: i128,
+    ) -> BytesN<32> {
+        let market_id = Self::create_market(
+            env.clone(),
+            creator,
+            description,
+            asset_symbol,
+            target_price,
+            condition,
+            resolve_time,
+            market_fee,
+        );
+
+        let pool = NoLossPool { principal: 0, shares: 0, returned: 0, prize: 0, settled: false, claimed: 0, paid: 0 };
+        env.storage().persistent().set(&NoLossDataKey::NoLossPool(market_id.clone()), &pool);
+
+        log!(&env, "No-loss pool opened for market {}", market_id);
+        market_id
+    }
+
+    /// Deposit KALE on one side of a no-loss market before its resolve time.
+    /// The deposit is staked until the pool settles.
+    pub fn deposit_no_loss(env: Env, depositor: Address, market_id: BytesN<32>, side: bool, amount: i128) {
+        depositor.require_auth();
+        Self::require_not_paused(&env);
+
+        let config: Config = env.storage().instance().get(&DataKey::Config).unwrap();
+        if amount < config.min_stake_amount {
+            panic_with_error!(&env, ContractError::InvalidAmount);
+        }
+
+        let mut market_info = Self::get_market_info(env.clone(), market_id.clone());
+        if market_info.resolved || env.ledger().timestamp() >= market_info.resolve_time {
+            panic_with_error!(&env, ContractError::MarketClosed);
+        }
+        let mut pool = Self::load_no_loss_pool(&env, &market_id);
+
+        let token_client = token::Client::new(&env, &config.kale_token);
+        token_client.transfer(&depositor, &env.current_contract_address(), &amount);
+
+        pool.shares += Self::mint_vault_shares(&env, amount);
+        pool.principal += amount;
+        env.storage().persistent().set(&NoLossDataKey::NoLossPool(market_id.clone()), &pool);
+
+        let mut position = Self::get_no_loss_position(env.clone(), market_id.clone(), depositor.clone());
+        if side {
+            position.amount_for += amount;
+            market_info.total_for += amount;
+        } else {
+            position.amount_against += amount;
+            market_info.total_against += amount;
+        }
+        env.storage().persistent().set(&NoLossDataKey::NoLossPosition(market_id.clone(), depositor.clone()), &position);
+        env.storage().persistent().set(&DataKey::MarketInfo(market_id), &market_info);
+
+        log!(&env, "No-loss deposit: {} KALE on {} by {}", amount, side, depositor);
+    }
+
+    /// Unstake a resolved no-loss market's deposits and fix its prize.
+    /// Callable by anyone once the market is resolved.
+    pub fn settle_no_loss_pool(env: Env, market_id: BytesN<32>) -> i128 {
+        Self::require_not_paused(&env);
+
+        let market_info = Self::get_market_info(env.clone(), market_id.clone());
+        if !market_info.resolved {
+            panic_with_error!(&env, ContractError::MarketClosed);
+        }
+
+        let mut pool = Self::load_no_loss_pool(&env, &market_id);
+        if pool.settled {
+            panic_with_error!(&env, ContractError::AlreadyClaimed);
+        }
+
+        let assets = if pool.shares > 0 { Self::burn_vault_shares(&env, pool.shares) } else { 0 };
+
+        // Share rounding can leave the pool a unit short of its principal
+        pool.returned = if assets < pool.principal { assets } else { pool.principal };
+        pool.prize = assets - pool.returned;
+        pool.shares = 0;
+        pool.settled = true;
+        env.storage().persistent().set(&NoLossDataKey::NoLossPool(market_id.clone()), &pool);
+
+        // Settled KALE is held in market escrow until claimed
+        Self::adjust_market_escrow(&env, assets);
+
+        log!(&env, "No-loss pool settled: {} principal, {} prize", pool.returned, pool.prize);
+
+        pool.prize
+    }
+
+    /// Claim principal and any prize from a settled no-loss pool. The payout
+    /// enters the depositor's unbonding queue.
+    pub fn claim_no_loss(env: Env, depositor: Address, market_id: BytesN<32>) -> i128 {
+        depositor.require_auth();
+
+        let market_info = Self::get_market_info(env.clone(), market_id.clone());
+        let mut pool = Self::load_no_loss_pool(&env, &market_id);
+        if !pool.settled {
+            panic_with_error!(&env, ContractError::MarketClosed);
+        }
+        let winning_side = Self::winning_side(&env, &market_info);
+
+        let mut position = Self::get_no_loss_position(env.clone(), market_id.clone(), depositor.clone());
+        let deposited = position.amount_for + position.amount_against;
+        if deposited == 0 {
+            panic_with_error!(&env, ContractError::BetNotFound);
+        }
+        if position.claimed {
+            panic_with_error!(&env, ContractError::AlreadyClaimed);
+        }
+
+        let principal = deposited * pool.returned / pool.principal;
+
+        let prize = match winning_side {
+            Some(true) => position.amount_for * pool.prize / market_info.total_for,
+            Some(false) => position.amount_against * pool.prize / market_info.total_against,
+            None => deposited * pool.prize / pool.principal,
+        };
+        let mut payout = principal + prize;
+
+        // The last claimant takes whatever rounding left behind
+        pool.claimed += deposited;
+        if pool.claimed == pool.principal {
+            payout = pool.returned + pool.prize - pool.paid;
+        }
+        pool.paid += payout;
+        env.storage().persistent().set(&NoLossDataKey::NoLossPool(market_id.clone()), &pool);
+
+        position.claimed = true;
+        env.storage().persistent().set(&NoLossDataKey::NoLossPosition(market_id, depositor.clone()), &position);
+
+        if payout > 0 {
+            Self::adjust_market_escrow(&env, -payout);
+            Self::queue_unbonding(&env, &depositor, payout);
+        }
+
+        log!(&env, "No-loss claim: {} principal and {} prize by {}", principal, prize, depositor);
+
+        payout
+    }
+
+    /// While paused, withdraw a deposit from an unsettled no-loss pool at the
+    /// vault's current value, giving up any prize
+    pub fn emergency_withdraw_no_loss(env: Env, depositor: Address, market_id: BytesN<32>) -> i128 {
+        depositor.require_auth();
+
+        if !Self::is_paused(env.clone()) {
+            panic_with_error!(&env, ContractError::NotAuthorized);
+        }
+
+        let mut pool = Self::load_no_loss_pool(&env, &market_id);
+        if pool.settled {
+            panic_with_error!(&env, ContractError::AlreadyClaimed);
+        }
+
+        let position = Self::get_no_loss_position(env.clone(), market_id.clone(), depositor.clone());
+        let deposited = position.amount_for + position.amount_against;
+        if deposited == 0 {
+            panic_with_error!(&env, ContractError::BetNotFound);
+        }
+
+        let shares = pool.shares * deposited / pool.principal;
+        let assets = Self::burn_vault_shares_unchecked(&env, shares);
+
+        pool.shares -= shares;
+        pool.principal -= deposited;
+        env.storage().persistent().set(&NoLossDataKey::NoLossPool(market_id.clone()), &pool);
+
+        let mut market_info = Self::get_market_info(env.clone(), market_id.clone());
+        market_info.total_for -= position.amount_for;
+        market_info.total_against -= position.amount_against;
+        env.storage().persistent().set(&DataKey::MarketInfo(market_id.clone()), &market_info);
+        env.storage().persistent().remove(&NoLossDataKey::NoLossPosition(market_id, depositor.clone()));
+
+        let config: Config = env.storage().instance().get(&DataKey::Config).unwrap();
+        let token_client = token::Client::new(&env, &config.kale_token);
+        token_client.transfer(&env.current_contract_address(), &depositor, &assets);
+
+        log!(&env, "No-loss emergency withdrawal: {} KALE by {}", assets, depositor);
+
+        assets
+    }
+
+    /// Get a no-loss market's pool, if the market is a no-loss market
+    pub fn get_no_loss_pool(env: Env, market_id: BytesN<32>) -> Option<NoLossPool> {
+        env.storage().persistent().get(&NoLossDataKey::NoLossPool(market_id))
+    }
+
+    /// Get a depositor's position in a no-loss market
+    pub fn get_no_loss_position(env: Env, market_id: BytesN<32>, depositor: Address) -> NoLossPosition {
+        env.storage().persistent()
+            .get(&NoLossDataKey::NoLossPosition(market_id, depositor))
+            .unwrap_or(NoLossPosition { amount_for: 0, amount_against: 0, claimed: false })
+    }
+
+    // Private helper functions
+
+    fn load_no_loss_pool(env: &Env, market_id: &BytesN<32>) -> NoLossPool {
+        env.storage().persistent()
+            .get(&NoLossDataKey::NoLossPool(market_id.clone()))
+            .unwrap_or_else(|| panic_with_error!(env, ContractError::MarketNotFound))
+    }
+}
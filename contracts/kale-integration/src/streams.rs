@@ -0,0 +1,293 @@
+//! Additional reward streams in other SEP-41 tokens.
+//!
+//! Each stream distributes its token to stakers by reward weight, alongside
+//! KALE emission, at its own rate until its own end time. Funding a stream
+//! spreads the new amount plus anything not yet emitted evenly until the new
+//! end time, so tokens left over from periods with no stakers are never lost.
+//! Streams are funded by the admin or by partners the admin has allowed.
+
+use soroban_sdk::{contractimpl, contracttype, token, Address, Env, Map, Vec, panic_with_error, log};
+use shared_types::{StakeInfo, ContractError, Config};
+
+use crate::{DataKey, KaleIntegrationContract, KaleIntegrationContractClient, REWARD_PRECISION};
+#[cfg(any(test, feature = "testutils"))]
+use crate::__KaleIntegrationContract_fn_set_registry;
+
+/// Reward stream storage keys
+#[contracttype]
+#[derive(Clone)]
+pub enum StreamDataKey {
+    RewardStreamCount,
+    RewardStreamToken(u32),
+    RewardStream(Address),
+    StreamRewards(Address, Address),
+    StreamRewardPerTokenPaid(Address, Address),
+    StreamFunder(Address),
+}
+
+const MAX_REWARD_STREAMS: u32 = 10;
+
+/// A reward stream and its accounting
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RewardStream {
+    pub token: Address,
+    pub rate_per_second: i128,
+    pub end_time: u64,
+    pub last_update: u64,
+    pub reward_per_token: i128, // Scaled by REWARD_PRECISION
+    pub unallocated: i128,      // Funded but not yet emitted
+    pub owed: i128,             // Emitted but not yet claimed
+}
+
+#[contractimpl]
+impl KaleIntegrationContract {
+    /// Fund a reward stream in `token` until `end_time`, creating it if needed
+    /// (admin or allowed funders only). The rate becomes everything not yet
+    /// emitted divided by the time remaining.
+    pub fn fund_reward_stream(env: Env, funder: Address, reward_token: Address, amount: i128, end_time: u64) {
+        funder.require_auth();
+
+        let config: Config = env.storage().instance().get(&DataKey::Config).unwrap();
+        if funder != config.admin && !Self::is_stream_funder(env.clone(), funder.clone()) {
+            panic_with_error!(&env, ContractError::NotAuthorized);
+        }
+
+        // KALE rewards go through the emission schedule instead
+        if reward_token == config.kale_token || amount < 0 {
+            panic_with_error!(&env, ContractError::InvalidAmount);
+        }
+
+        let now = env.ledger().timestamp();
+        if end_time <= now {
+            panic_with_error!(&env, ContractError::InvalidTimestamp);
+        }
+
+        let mut stream = match Self::load_reward_stream(&env, &reward_token) {
+            Some(stream) => Self::update_reward_stream(&env, stream),
+            None => {
+                let stream_count: u32 = env.storage().instance().get(&StreamDataKey::RewardStreamCount).unwrap_or(0);
+                if stream_count >= MAX_REWARD_STREAMS {
+                    panic_with_error!(&env, ContractError::InvalidAmount);
+                }
+                env.storage().instance().set(&StreamDataKey::RewardStreamToken(stream_count), &reward_token);
+                env.storage().instance().set(&StreamDataKey::RewardStreamCount, &(stream_count + 1));
+
+                RewardStream {
+                    token: reward_token.clone(),
+                    rate_per_second: 0,
+                    end_time: now,
+                    last_update: now,
+                    reward_per_token: 0,
+                    unallocated: 0,
+                    owed: 0,
+                }
+            }
+        };
+
+        if amount > 0 {
+            let token_client = token::Client::new(&env, &reward_token);
+            token_client.transfer(&funder, &env.current_contract_address(), &amount);
+        }
+
+        stream.unallocated += amount;
+        stream.rate_per_second = stream.unallocated / (end_time - now) as i128;
+        stream.end_time = end_time;
+        env.storage().persistent().set(&StreamDataKey::RewardStream(reward_token.clone()), &stream);
+
+        log!(&env, "Reward stream funded: {} of {} until {}", amount, reward_token, end_time);
+    }
+
+    /// Allow or disallow a partner to fund reward streams (admin only)
+    pub fn set_stream_funder(env: Env, admin: Address, funder: Address, allowed: bool) {
+        admin.require_auth();
+
+        let config: Config = env.storage().instance().get(&DataKey::Config).unwrap();
+        if admin != config.admin {
+            panic_with_error!(&env, ContractError::NotAuthorized);
+        }
+
+        if allowed {
+            env.storage().persistent().set(&StreamDataKey::StreamFunder(funder.clone()), &true);
+        } else {
+            env.storage().persistent().remove(&StreamDataKey::StreamFunder(funder.clone()));
+        }
+
+        log!(&env, "Stream funder {} allowed: {}", funder, allowed);
+    }
+
+    /// Check whether a partner may fund reward streams
+    pub fn is_stream_funder(env: Env, funder: Address) -> bool {
+        env.storage().persistent().get(&StreamDataKey::StreamFunder(funder)).unwrap_or(false)
+    }
+
+    /// Claim a staker's rewards from a single stream
+    pub fn claim_stream_rewards(env: Env, staker: Address, reward_token: Address) -> i128 {
+        staker.require_auth();
+        Self::require_not_paused(&env);
+
+        let stream = Self::load_reward_stream(&env, &reward_token)
+            .unwrap_or_else(|| panic_with_error!(&env, ContractError::InvalidAmount));
+        let weight = Self::staker_weight(&env, &staker);
+
+        Self::claim_from_stream(&env, &staker, weight, stream)
+    }
+
+    /// Claim KALE rewards and every stream's rewards, returning the amount paid
+    /// per token. Former stakers can still collect what their streams owe them.
+    pub fn claim_all_rewards(env: Env, staker: Address) -> Map<Address, i128> {
+        staker.require_auth();
+        Self::require_not_paused(&env);
+
+        let config: Config = env.storage().instance().get(&DataKey::Config).unwrap();
+        let weight = Self::staker_weight(&env, &staker);
+
+        let kale_paid = if env.storage().persistent().has(&DataKey::StakeInfo(staker.clone())) {
+            Self::claim_rewards_to_staker(&env, &staker)
+        } else {
+            0
+        };
+
+        let mut paid = Map::new(&env);
+        paid.set(config.kale_token, kale_paid);
+
+        for stream in Self::get_reward_streams(env.clone()).iter() {
+            let token = stream.token.clone();
+            paid.set(token, Self::claim_from_stream(&env, &staker, weight, stream));
+        }
+
+        paid
+    }
+
+    /// Get all reward streams
+    pub fn get_reward_streams(env: Env) -> Vec<RewardStream> {
+        let stream_count: u32 = env.storage().instance().get(&StreamDataKey::RewardStreamCount).unwrap_or(0);
+
+        let mut streams = Vec::new(&env);
+        for i in 0..stream_count {
+            let reward_token: Address = env.storage().instance().get(&StreamDataKey::RewardStreamToken(i)).unwrap();
+            streams.push_back(Self::load_reward_stream(&env, &reward_token).unwrap());
+        }
+        streams
+    }
+
+    /// Get a staker's unclaimed rewards from a stream
+    pub fn get_stream_pending_rewards(env: Env, staker: Address, reward_token: Address) -> i128 {
+        let stream = match Self::load_reward_stream(&env, &reward_token) {
+            Some(stream) => stream,
+            None => return 0,
+        };
+
+        let now = env.ledger().timestamp();
+        let (reward_per_token, _) = Self::accrue_reward_stream(&env, &stream, now);
+        let earned: i128 = env.storage().persistent()
+            .get(&StreamDataKey::StreamRewards(staker.clone(), reward_token.clone()))
+            .unwrap_or(0);
+        let paid: i128 = env.storage().persistent()
+            .get(&StreamDataKey::StreamRewardPerTokenPaid(staker.clone(), reward_token))
+            .unwrap_or(0);
+
+        earned + Self::staker_weight(&env, &staker) * (reward_per_token - paid) / REWARD_PRECISION
+    }
+
+    // Private helper functions
+
+    fn load_reward_stream(env: &Env, reward_token: &Address) -> Option<RewardStream> {
+        env.storage().persistent().get(&StreamDataKey::RewardStream(reward_token.clone()))
+    }
+
+    fn staker_weight(env: &Env, staker: &Address) -> i128 {
+        env.storage().persistent()
+            .get::<_, StakeInfo>(&DataKey::StakeInfo(staker.clone()))
+            .map(|stake_info| stake_info.weight)
+            .unwrap_or(0)
+    }
+
+    /// Reward per token and amount emitted by a stream up to `now`
+    fn accrue_reward_stream(env: &Env, stream: &RewardStream, now: u64) -> (i128, i128) {
+        let total_weight: i128 = env.storage().instance().get(&DataKey::TotalWeight).unwrap_or(0);
+        let until = if now < stream.end_time { now } else { stream.end_time };
+
+        if total_weight == 0 || until <= stream.last_update {
+            return (stream.reward_per_token, 0);
+        }
+
+        let emitted = stream.rate_per_second * (until - stream.last_update) as i128;
+        let emitted = if emitted > stream.unallocated { stream.unallocated } else { emitted };
+
+        (stream.reward_per_token + emitted * REWARD_PRECISION / total_weight, emitted)
+    }
+
+    /// Move a stream's emission up to now into its accumulator
+    fn update_reward_stream(env: &Env, mut stream: RewardStream) -> RewardStream {
+        let now = env.ledger().timestamp();
+        let (reward_per_token, emitted) = Self::accrue_reward_stream(env, &stream, now);
+
+        stream.reward_per_token = reward_per_token;
+        stream.unallocated -= emitted;
+        stream.owed += emitted;
+        stream.last_update = now;
+        stream
+    }
+
+    /// Credit a staker's earnings from a stream at their current weight
+    fn checkpoint_stream(env: &Env, staker: &Address, weight: i128, stream: &mut RewardStream) -> i128 {
+        let paid_key = StreamDataKey::StreamRewardPerTokenPaid(staker.clone(), stream.token.clone());
+        let rewards_key = StreamDataKey::StreamRewards(staker.clone(), stream.token.clone());
+
+        let paid: i128 = env.storage().persistent().get(&paid_key).unwrap_or(0);
+        let earned: i128 = env.storage().persistent().get(&rewards_key).unwrap_or(0);
+        let new_earnings = weight * (stream.reward_per_token - paid) / REWARD_PRECISION;
+        env.storage().persistent().set(&paid_key, &stream.reward_per_token);
+
+        // The vault's share can never be claimed, so it returns to the stream
+        if *staker == env.current_contract_address() {
+            stream.owed -= new_earnings;
+            stream.unallocated += new_earnings;
+            return 0;
+        }
+
+        let earned = earned + new_earnings;
+        if earned == 0 {
+            env.storage().persistent().remove(&rewards_key);
+        } else {
+            env.storage().persistent().set(&rewards_key, &earned);
+        }
+        earned
+    }
+
+    /// Checkpoint a staker in every stream. Must run before their weight changes.
+    pub(crate) fn checkpoint_reward_streams(env: &Env, staker: &Address, weight: i128) {
+        for stream in Self::get_reward_streams(env.clone()).iter() {
+            let mut stream = Self::update_reward_stream(env, stream);
+            Self::checkpoint_stream(env, staker, weight, &mut stream);
+            env.storage().persistent().set(&StreamDataKey::RewardStream(stream.token.clone()), &stream);
+        }
+    }
+
+    fn claim_from_stream(env: &Env, staker: &Address, weight: i128, stream: RewardStream) -> i128 {
+        let mut stream = Self::update_reward_stream(env, stream);
+        let earned = Self::checkpoint_stream(env, staker, weight, &mut stream);
+        let payout = if earned > stream.owed { stream.owed } else { earned };
+
+        if payout > 0 {
+            stream.owed -= payout;
+
+            let rewards_key = StreamDataKey::StreamRewards(staker.clone(), stream.token.clone());
+            if earned == payout {
+                env.storage().persistent().remove(&rewards_key);
+            } else {
+                env.storage().persistent().set(&rewards_key, &(earned - payout));
+            }
+
+            let token_client = token::Client::new(env, &stream.token);
+            token_client.transfer(&env.current_contract_address(), staker, &payout);
+        }
+
+        env.storage().persistent().set(&StreamDataKey::RewardStream(stream.token.clone()), &stream);
+
+        log!(env, "Claimed {} stream rewards in {} by {}", payout, stream.token, staker);
+
+        payout
+    }
+}
@@ -0,0 +1,1527 @@
+#![cfg(test)]
+
+use super::*;
+use soroban_sdk::testutils::{Address as _, Ledger};
+use soroban_sdk::token::StellarAssetClient;
+
+const REWARD_RATE: i128 = 1_000;
+const MIN_STAKE: i128 = 100;
+
+struct Setup {
+    env: Env,
+    client: KaleIntegrationContractClient<'static>,
+    token: token::Client<'static>,
+    admin: Address,
+}
+
+fn setup() -> Setup {
+    // Fund the reward pool generously so payouts are never capped
+    setup_with_rewards(1_000_000_000)
+}
+
+fn setup_with_rewards(rewards: i128) -> Setup {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let token_address = env.register_stellar_asset_contract(admin.clone());
+    let token = token::Client::new(&env, &token_address);
+
+    let contract_id = env.register_contract(None, KaleIntegrationContract);
+    let client = KaleIntegrationContractClient::new(&env, &contract_id);
+    client.initialize(&admin, &token_address, &REWARD_RATE, &MIN_STAKE, &admin, &100u32);
+
+    StellarAssetClient::new(&env, &token_address).mint(&admin, &rewards);
+    client.add_rewards(&admin, &rewards);
+
+    Setup { env, client, token, admin }
+}
+
+fn new_staker(setup: &Setup, amount: i128) -> Address {
+    let staker = Address::generate(&setup.env);
+    StellarAssetClient::new(&setup.env, &setup.token.address).mint(&staker, &amount);
+    staker
+}
+
+fn advance(env: &Env, seconds: u64) {
+    env.ledger().with_mut(|li| li.timestamp += seconds);
+}
+
+// Calls expected to panic go straight to the contract impl: a panic can't unwind
+// out of the SDK's `extern` entry points, so through the client it would abort
+fn call_direct<T>(setup: &Setup, f: impl FnOnce(Env) -> T) -> T {
+    setup.env.as_contract(&setup.client.address, || f(setup.env.clone()))
+}
+
+#[test]
+fn test_single_staker_earns_full_emission() {
+    let setup = setup();
+    let staker = new_staker(&setup, 1_000);
+
+    setup.client.stake(&staker, &1_000);
+    advance(&setup.env, 100);
+
+    assert_eq!(setup.client.claim_rewards(&staker), REWARD_RATE * 100);
+}
+
+#[test]
+fn test_payouts_sum_to_emission_when_total_stake_changes() {
+    let setup = setup();
+    let alice = new_staker(&setup, 3_000);
+    let bob = new_staker(&setup, 1_000);
+
+    // t=0..100: alice alone
+    setup.client.stake(&alice, &1_000);
+    advance(&setup.env, 100);
+
+    // t=100..200: alice 1000, bob 1000
+    setup.client.stake(&bob, &1_000);
+    advance(&setup.env, 100);
+
+    // t=200..300: alice 3000, bob 1000
+    setup.client.stake(&alice, &2_000);
+    advance(&setup.env, 100);
+
+    let alice_rewards = setup.client.claim_rewards(&alice);
+    let bob_rewards = setup.client.claim_rewards(&bob);
+
+    assert_eq!(alice_rewards, 100_000 + 50_000 + 75_000);
+    assert_eq!(bob_rewards, 50_000 + 25_000);
+    assert_eq!(alice_rewards + bob_rewards, REWARD_RATE * 300);
+}
+
+#[test]
+fn test_claim_timing_does_not_change_total_payout() {
+    let setup = setup();
+    let alice = new_staker(&setup, 1_000);
+    let bob = new_staker(&setup, 1_000);
+
+    setup.client.stake(&alice, &1_000);
+    setup.client.stake(&bob, &1_000);
+
+    let mut paid = 0;
+    for _ in 0..5 {
+        advance(&setup.env, 60);
+        paid += setup.client.claim_rewards(&alice);
+    }
+
+    // Bob unstakes half midway through the final period
+    setup.client.unstake(&bob, &500);
+    advance(&setup.env, 60);
+    paid += setup.client.claim_rewards(&alice);
+    paid += setup.client.claim_rewards(&bob);
+
+    assert_eq!(paid, REWARD_RATE * 360);
+}
+
+#[test]
+fn test_no_rewards_accrue_while_nothing_is_staked() {
+    let setup = setup();
+    let staker = new_staker(&setup, 1_000);
+
+    advance(&setup.env, 1_000);
+    setup.client.stake(&staker, &1_000);
+    advance(&setup.env, 10);
+
+    assert_eq!(setup.client.get_stake_info(&staker).accumulated_rewards, REWARD_RATE * 10);
+    assert_eq!(setup.client.claim_rewards(&staker), REWARD_RATE * 10);
+}
+
+fn accrue_platform_fees(setup: &Setup, amount: i128) {
+    let creator = new_staker(setup, amount);
+    setup.client.create_market(
+        &creator,
+        &String::from_str(&setup.env, "KALE above 1"),
+        &String::from_str(&setup.env, "KALE"),
+        &1,
+        &0,
+        &(setup.env.ledger().timestamp() + 1_000),
+        &amount,
+    );
+}
+
+/// Append epochs after the seeded one-year epoch, then stake `amount` as they
+/// begin so only the appended epochs pay out
+fn stake_into_schedule(setup: &Setup, amount: i128, epochs: &[(u64, i128, i128)]) -> Address {
+    let mut start = setup.env.ledger().timestamp() + INITIAL_EPOCH_DURATION;
+    for &(duration, start_rate, end_rate) in epochs {
+        let epoch = EmissionEpoch { start_time: start, end_time: start + duration, start_rate, end_rate };
+        setup.client.append_emission_epoch(&setup.admin, &epoch);
+        start += duration;
+    }
+
+    let staker = new_staker(setup, amount);
+    advance(&setup.env, INITIAL_EPOCH_DURATION);
+    setup.client.stake(&staker, &amount);
+    staker
+}
+
+#[test]
+fn test_halving_epochs_emit_at_each_rate() {
+    let setup = setup();
+    let staker = stake_into_schedule(&setup, 1_000, &[(100, 1_000, 1_000), (100, 500, 500)]);
+
+    advance(&setup.env, 150);
+    assert_eq!(setup.client.get_current_emission_rate(), 500);
+    assert_eq!(setup.client.claim_rewards(&staker), 100 * 1_000 + 50 * 500);
+
+    // Nothing is emitted once the schedule runs out
+    advance(&setup.env, 100);
+    assert_eq!(setup.client.claim_rewards(&staker), 50 * 500);
+    assert_eq!(setup.client.get_current_emission_rate(), 0);
+}
+
+#[test]
+fn test_decaying_epoch_emits_along_its_rate() {
+    let setup = setup();
+    let staker = stake_into_schedule(&setup, 1_000, &[(100, 1_000, 0)]);
+
+    advance(&setup.env, 50);
+    assert_eq!(setup.client.get_current_emission_rate(), 500);
+    assert_eq!(setup.client.claim_rewards(&staker), (1_000 + 500) * 50 / 2);
+
+    advance(&setup.env, 100);
+    assert_eq!(setup.client.claim_rewards(&staker), 500 * 50 / 2);
+}
+
+#[test]
+fn test_emission_is_capped_by_reward_pool() {
+    let setup = setup_with_rewards(30_000);
+    let staker = stake_into_schedule(&setup, 1_000, &[(100, 1_000, 1_000), (100, 500, 500)]);
+
+    advance(&setup.env, 200);
+    assert_eq!(setup.client.claim_rewards(&staker), 30_000);
+    assert_eq!(setup.client.get_reward_pool(), 0);
+    assert!(setup.client.check_solvency().solvent);
+}
+
+#[test]
+fn test_claims_cannot_drain_platform_fees() {
+    // Emission over the period far exceeds the funded pool
+    let setup = setup_with_rewards(5_000);
+    let staker = new_staker(&setup, 1_000);
+
+    accrue_platform_fees(&setup, 10_000);
+    setup.client.stake(&staker, &1_000);
+    advance(&setup.env, 100);
+
+    assert_eq!(setup.client.claim_rewards(&staker), 5_000);
+    advance(&setup.env, 100);
+    assert_eq!(setup.client.claim_rewards(&staker), 0);
+
+    let report = setup.client.check_solvency();
+    assert_eq!(report.platform_fees, 10_000);
+    assert_eq!(report.reward_pool, 0);
+    assert_eq!(report.rewards_owed, 0);
+    assert!(report.solvent);
+    assert_eq!(setup.token.balance(&setup.client.address), 1_000 + 10_000);
+}
+
+#[test]
+fn test_fee_collection_cannot_drain_rewards_or_principal() {
+    let setup = setup();
+    let staker = new_staker(&setup, 1_000);
+
+    setup.client.stake(&staker, &1_000);
+    advance(&setup.env, 100);
+
+    assert_eq!(setup.client.collect_fees(&setup.admin), 0);
+
+    accrue_platform_fees(&setup, 2_500);
+    assert_eq!(setup.client.collect_fees(&setup.admin), 2_500);
+    assert_eq!(setup.client.collect_fees(&setup.admin), 0);
+
+    assert_eq!(setup.client.claim_rewards(&staker), REWARD_RATE * 100);
+    assert_eq!(setup.client.get_stake_info(&staker).amount, 1_000);
+    assert!(setup.client.check_solvency().solvent);
+}
+
+#[test]
+fn test_unstaking_cannot_drain_rewards_or_fees() {
+    let setup = setup_with_rewards(50_000);
+    let staker = new_staker(&setup, 1_000);
+
+    accrue_platform_fees(&setup, 10_000);
+    setup.client.stake(&staker, &1_000);
+    advance(&setup.env, 10);
+
+    setup.client.unstake(&staker, &1_000);
+    advance(&setup.env, setup.client.get_unbonding_period());
+
+    // Principal is returned without the accrued rewards
+    assert_eq!(setup.client.withdraw_unbonded(&staker), 1_000);
+    assert_eq!(setup.token.balance(&staker), 1_000);
+
+    let report = setup.client.check_solvency();
+    assert_eq!(report.total_staked, 0);
+    assert_eq!(report.total_unbonding, 0);
+    assert_eq!(report.rewards_owed, REWARD_RATE * 10);
+    assert_eq!(report.reward_pool, 50_000 - REWARD_RATE * 10);
+    assert_eq!(report.platform_fees, 10_000);
+    assert!(report.solvent);
+}
+
+#[test]
+#[should_panic]
+fn test_unstaking_more_than_principal_fails() {
+    let setup = setup();
+    let staker = new_staker(&setup, 1_000);
+
+    accrue_platform_fees(&setup, 10_000);
+    setup.client.stake(&staker, &1_000);
+    call_direct(&setup, |env| KaleIntegrationContract::unstake(env, staker.clone(), 2_000));
+}
+
+#[test]
+fn test_vault_compounding_stays_solvent() {
+    let setup = setup();
+    let depositor = new_staker(&setup, 1_000);
+
+    setup.client.vault_deposit(&depositor, &1_000);
+    advance(&setup.env, 100);
+
+    assert_eq!(setup.client.harvest(), REWARD_RATE * 100);
+    // The vault's own stake is never listed as a staker
+    assert_eq!(setup.client.get_staker_count(), 0);
+
+    let report = setup.client.check_solvency();
+    assert_eq!(report.total_staked, 1_000 + REWARD_RATE * 100);
+    assert_eq!(report.rewards_owed, 0);
+    assert_eq!(report.surplus, 0);
+    assert!(report.solvent);
+}
+
+const WEEK: u64 = 7 * 24 * 60 * 60;
+const MAX_VE_LOCK: u64 = 4 * 365 * 24 * 60 * 60;
+
+fn expected_voting_power(amount: i128, end: u64, timestamp: u64) -> i128 {
+    if end > timestamp {
+        amount * (end - timestamp) as i128 / MAX_VE_LOCK as i128
+    } else {
+        0
+    }
+}
+
+#[test]
+fn test_ve_voting_power_decays_linearly() {
+    let setup = setup();
+    setup.env.ledger().with_mut(|li| li.timestamp = 10 * WEEK);
+    let alice = new_staker(&setup, 1_000_000_000);
+
+    let start = 10 * WEEK;
+    let end = start + 200 * WEEK;
+    setup.client.create_ve_lock(&alice, &1_000_000_000, &end);
+    assert_eq!(setup.client.voting_power(&alice), expected_voting_power(1_000_000_000, end, start));
+
+    advance(&setup.env, 100 * WEEK);
+    assert_eq!(setup.client.voting_power(&alice), expected_voting_power(1_000_000_000, end, start + 100 * WEEK));
+    assert_eq!(setup.client.voting_power_at(&alice, &start), expected_voting_power(1_000_000_000, end, start));
+    assert_eq!(setup.client.voting_power_at(&alice, &(start - 1)), 0);
+
+    advance(&setup.env, 100 * WEEK);
+    assert_eq!(setup.client.voting_power(&alice), 0);
+    assert_eq!(setup.client.total_voting_power(), 0);
+
+    assert_eq!(setup.client.withdraw_ve(&alice), 1_000_000_000);
+    assert!(setup.client.check_solvency().solvent);
+}
+
+#[test]
+fn test_ve_checkpoint_catches_up_after_a_long_idle_period() {
+    let setup = setup();
+    setup.env.ledger().with_mut(|li| li.timestamp = 10 * WEEK);
+    let alice = new_staker(&setup, 1_000_000_000);
+    let bob = new_staker(&setup, 1_000_000_000);
+
+    setup.client.create_ve_lock(&alice, &1_000_000_000, &(10 * WEEK + MAX_VE_LOCK));
+
+    // Longer than the 255 weeks walked per call
+    advance(&setup.env, 300 * WEEK);
+    assert_eq!(setup.client.total_voting_power(), 0);
+
+    let now = setup.env.ledger().timestamp();
+    let end = now + 100 * WEEK;
+    setup.client.create_ve_lock(&bob, &1_000_000_000, &end);
+    assert_eq!(setup.client.total_voting_power(), setup.client.voting_power(&bob));
+    assert_eq!(setup.client.voting_power(&bob), expected_voting_power(1_000_000_000, end / WEEK * WEEK, now));
+}
+
+#[test]
+fn test_ve_total_supply_matches_sum_of_users() {
+    let setup = setup();
+    let start = 10 * WEEK;
+    setup.env.ledger().with_mut(|li| li.timestamp = start);
+    let alice = new_staker(&setup, 2_000_000_000);
+    let bob = new_staker(&setup, 500_000_000);
+
+    setup.client.create_ve_lock(&alice, &1_000_000_000, &(start + 52 * WEEK));
+    advance(&setup.env, 3 * WEEK + 86_400);
+    setup.client.create_ve_lock(&bob, &500_000_000, &(start + 104 * WEEK));
+    advance(&setup.env, 10 * WEEK);
+    setup.client.increase_ve_amount(&alice, &1_000_000_000);
+    advance(&setup.env, 60 * WEEK);
+    setup.client.increase_ve_unlock_time(&bob, &(start + 200 * WEEK));
+    advance(&setup.env, 5 * WEEK);
+
+    let mut timestamp = start;
+    while timestamp < start + 210 * WEEK {
+        let total = setup.client.total_voting_power_at(&timestamp);
+        let sum = setup.client.voting_power_at(&alice, &timestamp)
+            + setup.client.voting_power_at(&bob, &timestamp);
+
+        // Per-user powers are floored individually
+        assert!(total - sum >= 0 && total - sum <= 1);
+        timestamp += 5 * WEEK + 12_345;
+    }
+
+    assert_eq!(setup.client.total_voting_power(), setup.client.voting_power(&bob));
+}
+
+#[test]
+fn test_stake_checkpoints_record_history() {
+    let setup = setup();
+    let alice = new_staker(&setup, 3_000);
+    let bob = new_staker(&setup, 1_000);
+    let start = setup.env.ledger().timestamp();
+
+    setup.client.stake(&alice, &1_000);
+    advance(&setup.env, 100);
+    setup.client.stake(&bob, &1_000);
+    setup.client.stake(&alice, &2_000);
+    advance(&setup.env, 100);
+    setup.client.unstake(&alice, &500);
+    // A second change in the same ledger overwrites the checkpoint
+    setup.client.unstake(&alice, &500);
+
+    assert_eq!(setup.client.stake_at(&alice, &(start + 50)), 1_000);
+    assert_eq!(setup.client.stake_at(&alice, &(start + 100)), 3_000);
+    assert_eq!(setup.client.stake_at(&alice, &(start + 200)), 2_000);
+    assert_eq!(setup.client.stake_at(&bob, &(start + 99)), 0);
+    assert_eq!(setup.client.stake_at(&bob, &(start + 500)), 1_000);
+    assert_eq!(setup.client.get_stake_checkpoint_count(&alice), 3);
+
+    assert_eq!(setup.client.total_staked_at(&(start + 50)), 1_000);
+    assert_eq!(setup.client.total_staked_at(&(start + 150)), 4_000);
+    assert_eq!(setup.client.total_staked_at(&(start + 200)), 3_000);
+}
+
+#[test]
+fn test_delegated_voting_weight_follows_stake_checkpoints() {
+    let setup = setup();
+    let alice = new_staker(&setup, 3_000);
+    let bob = new_staker(&setup, 1_000);
+    let operator = Address::generate(&setup.env);
+    let start = setup.env.ledger().timestamp();
+
+    setup.client.stake(&alice, &2_000);
+    setup.client.stake(&bob, &1_000);
+    advance(&setup.env, 100);
+    setup.client.set_delegate(&alice, &operator);
+    advance(&setup.env, 100);
+    setup.client.stake(&alice, &1_000);
+    setup.client.set_delegate(&bob, &operator);
+    advance(&setup.env, 100);
+    setup.client.remove_delegate(&alice);
+    setup.client.unstake(&alice, &500);
+
+    assert_eq!(setup.client.voting_weight_at(&alice, &(start + 50)), 2_000);
+    assert_eq!(setup.client.voting_weight_at(&operator, &(start + 50)), 0);
+
+    // Delegated stake votes through the operator, including later top-ups
+    assert_eq!(setup.client.voting_weight_at(&alice, &(start + 150)), 0);
+    assert_eq!(setup.client.voting_weight_at(&operator, &(start + 150)), 2_000);
+    assert_eq!(setup.client.voting_weight_at(&operator, &(start + 250)), 4_000);
+    assert_eq!(setup.client.voting_weight_at(&bob, &(start + 250)), 0);
+
+    // Revoking returns the stake's weight to the staker
+    assert_eq!(setup.client.voting_weight_at(&alice, &(start + 300)), 2_500);
+    assert_eq!(setup.client.voting_weight_at(&operator, &(start + 300)), 1_000);
+    assert_eq!(setup.client.voting_weight_at(&bob, &(start + 300)), 0);
+}
+
+#[test]
+fn test_voting_checkpoints_use_the_locked_weight() {
+    let setup = setup();
+    let alice = new_staker(&setup, 2_000);
+    let operator = Address::generate(&setup.env);
+    let start = setup.env.ledger().timestamp();
+
+    setup.client.stake_locked(&alice, &1_000, &MONTH_LOCK);
+    setup.client.set_delegate(&alice, &operator);
+    advance(&setup.env, 100);
+    setup.client.stake(&alice, &1_000);
+    advance(&setup.env, 100);
+
+    // Checkpoints count the boosted weight, matching the live voting weight
+    assert_eq!(setup.client.voting_weight_at(&operator, &(start + 50)), 1_250);
+    assert_eq!(setup.client.get_voting_weight(&operator), 2_250);
+    assert_eq!(setup.client.voting_weight_at(&operator, &setup.env.ledger().timestamp()), 2_250);
+
+    setup.client.remove_delegate(&alice);
+    assert_eq!(setup.client.voting_weight_at(&operator, &setup.env.ledger().timestamp()), 0);
+    assert_eq!(setup.client.voting_weight_at(&alice, &setup.env.ledger().timestamp()), 2_250);
+    assert_eq!(setup.client.get_voting_weight(&alice), 2_250);
+}
+
+#[test]
+fn test_staker_list_migration_skips_entries_without_a_stake() {
+    let setup = setup();
+    let alice = new_staker(&setup, 1_000);
+    let departed = Address::generate(&setup.env);
+    setup.client.stake(&alice, &1_000);
+
+    // Rebuild the pre-index layout: everyone in the instance list, no index
+    call_direct(&setup, |env| {
+        env.storage().persistent().remove(&DataKey::StakerIndex(alice.clone()));
+        env.storage().persistent().remove(&DataKey::StakerAt(0));
+        env.storage().instance().set(&DataKey::StakerCount, &0u32);
+        env.storage().instance().set(&DataKey::StakerList, &Vec::from_array(&env, [alice.clone(), departed.clone()]));
+    });
+
+    assert_eq!(setup.client.migrate_staker_list(&setup.admin, &10), 0);
+    assert_eq!(setup.client.get_staker_count(), 1);
+    assert_eq!(setup.client.get_stakers(&0, &10), Vec::from_array(&setup.env, [alice]));
+}
+
+fn new_reward_token(setup: &Setup, amount: i128) -> Address {
+    let reward_token = setup.env.register_stellar_asset_contract(setup.admin.clone());
+    StellarAssetClient::new(&setup.env, &reward_token).mint(&setup.admin, &amount);
+    reward_token
+}
+
+#[test]
+fn test_reward_streams_pay_each_token_until_end_time() {
+    let setup = setup();
+    let alice = new_staker(&setup, 1_000);
+    let bob = new_staker(&setup, 3_000);
+    let usdc = new_reward_token(&setup, 40_000);
+    let xlm = new_reward_token(&setup, 10_000);
+
+    setup.client.stake(&alice, &1_000);
+    setup.client.stake(&bob, &3_000);
+
+    let now = setup.env.ledger().timestamp();
+    setup.client.fund_reward_stream(&setup.admin, &usdc, &40_000, &(now + 100));
+    setup.client.fund_reward_stream(&setup.admin, &xlm, &10_000, &(now + 400));
+
+    // Well past the USDC stream's end
+    advance(&setup.env, 200);
+    assert_eq!(setup.client.get_stream_pending_rewards(&alice, &usdc), 10_000);
+    assert_eq!(setup.client.get_stream_pending_rewards(&alice, &xlm), 1_250);
+
+    let paid = setup.client.claim_all_rewards(&alice);
+    assert_eq!(paid.get(setup.token.address.clone()), Some(REWARD_RATE * 200 / 4));
+    assert_eq!(paid.get(usdc.clone()), Some(10_000));
+    assert_eq!(paid.get(xlm.clone()), Some(1_250));
+    assert_eq!(token::Client::new(&setup.env, &usdc).balance(&alice), 10_000);
+
+    advance(&setup.env, 200);
+    assert_eq!(setup.client.claim_stream_rewards(&bob, &usdc), 30_000);
+    assert_eq!(setup.client.claim_stream_rewards(&bob, &xlm), 7_500);
+    assert_eq!(setup.client.claim_stream_rewards(&alice, &xlm), 1_250);
+    assert_eq!(setup.client.claim_stream_rewards(&alice, &usdc), 0);
+}
+
+#[test]
+fn test_reward_stream_tracks_stake_changes() {
+    let setup = setup();
+    let alice = new_staker(&setup, 1_000);
+    let bob = new_staker(&setup, 1_000);
+    let usdc = new_reward_token(&setup, 20_000);
+
+    setup.client.stake(&alice, &1_000);
+    let now = setup.env.ledger().timestamp();
+    setup.client.fund_reward_stream(&setup.admin, &usdc, &20_000, &(now + 200));
+
+    advance(&setup.env, 100);
+    setup.client.stake(&bob, &1_000);
+    advance(&setup.env, 100);
+
+    assert_eq!(setup.client.claim_stream_rewards(&alice, &usdc), 10_000 + 5_000);
+    assert_eq!(setup.client.claim_stream_rewards(&bob, &usdc), 5_000);
+
+    let stream = setup.client.get_reward_streams().get(0).unwrap();
+    assert_eq!(stream.owed, 0);
+    assert_eq!(stream.unallocated, 0);
+}
+
+#[test]
+fn test_allowed_partners_fund_reward_streams() {
+    let setup = setup();
+    let alice = new_staker(&setup, 1_000);
+    let partner = Address::generate(&setup.env);
+    let usdc = new_reward_token(&setup, 0);
+    StellarAssetClient::new(&setup.env, &usdc).mint(&partner, &10_000);
+
+    setup.client.set_stream_funder(&setup.admin, &partner, &true);
+    assert!(setup.client.is_stream_funder(&partner));
+
+    setup.client.stake(&alice, &1_000);
+    let now = setup.env.ledger().timestamp();
+    setup.client.fund_reward_stream(&partner, &usdc, &10_000, &(now + 100));
+
+    // Alice leaves entirely once the stream has ended, then collects from it
+    advance(&setup.env, 100);
+    setup.client.claim_rewards(&alice);
+    setup.client.unstake(&alice, &1_000);
+    assert_eq!(setup.client.get_staker_count(), 0);
+
+    let paid = setup.client.claim_all_rewards(&alice);
+    assert_eq!(paid.get(setup.token.address.clone()), Some(0));
+    assert_eq!(paid.get(usdc.clone()), Some(10_000));
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1)")]
+fn test_unlisted_partners_cannot_fund_reward_streams() {
+    let setup = setup();
+    let partner = Address::generate(&setup.env);
+    let usdc = new_reward_token(&setup, 0);
+    let end_time = setup.env.ledger().timestamp() + 100;
+
+    call_direct(&setup, |env| KaleIntegrationContract::fund_reward_stream(env, partner, usdc, 0, end_time));
+}
+
+#[test]
+fn test_referrer_earns_share_of_platform_fees() {
+    use referral_registry::{ReferralRegistryContract, ReferralRegistryContractClient};
+
+    let setup = setup();
+    let registry_id = setup.env.register_contract(None, ReferralRegistryContract);
+    let registry = ReferralRegistryContractClient::new(&setup.env, &registry_id);
+    registry.initialize(&setup.admin, &2_000u32);
+    registry.set_reporter(&setup.admin, &setup.client.address, &true);
+    setup.client.set_referral_registry(&setup.admin, &registry_id);
+
+    let referrer = Address::generate(&setup.env);
+    let creator = new_staker(&setup, 10_000);
+    registry.register_referrer(&creator, &referrer);
+
+    setup.client.create_market(
+        &creator,
+        &String::from_str(&setup.env, "KALE above 1"),
+        &String::from_str(&setup.env, "KALE"),
+        &1,
+        &0,
+        &(setup.env.ledger().timestamp() + 1_000),
+        &10_000,
+    );
+
+    assert_eq!(setup.token.balance(&referrer), 2_000);
+    assert_eq!(setup.client.get_fee_info().platform_fees, 8_000);
+    assert_eq!(registry.get_referral_earnings(&referrer), 2_000);
+    assert_eq!(registry.get_referee_fees(&creator), 10_000);
+    assert_eq!(registry.get_referees(&referrer, &0, &10).get(0), Some(creator));
+    assert!(setup.client.check_solvency().solvent);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1)")]
+fn test_referrer_cannot_be_registered_after_paying_fees() {
+    use referral_registry::{ReferralRegistryContract, ReferralRegistryContractClient};
+
+    let setup = setup();
+    let registry_id = setup.env.register_contract(None, ReferralRegistryContract);
+    let registry = ReferralRegistryContractClient::new(&setup.env, &registry_id);
+    registry.initialize(&setup.admin, &2_000u32);
+    registry.set_reporter(&setup.admin, &setup.client.address, &true);
+    setup.client.set_referral_registry(&setup.admin, &registry_id);
+
+    let creator = new_staker(&setup, 10_000);
+    setup.client.create_market(
+        &creator,
+        &String::from_str(&setup.env, "KALE above 1"),
+        &String::from_str(&setup.env, "KALE"),
+        &1,
+        &0,
+        &(setup.env.ledger().timestamp() + 1_000),
+        &10_000,
+    );
+    assert_eq!(registry.get_referee_fees(&creator), 10_000);
+
+    let referrer = Address::generate(&setup.env);
+    setup.env.as_contract(&registry_id, || {
+        ReferralRegistryContract::register_referrer(setup.env.clone(), creator, referrer)
+    });
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1)")]
+fn test_referrer_cannot_be_registered_after_staking() {
+    use referral_registry::{ReferralRegistryContract, ReferralRegistryContractClient};
+
+    let setup = setup();
+    let registry_id = setup.env.register_contract(None, ReferralRegistryContract);
+    let registry = ReferralRegistryContractClient::new(&setup.env, &registry_id);
+    registry.initialize(&setup.admin, &2_000u32);
+    registry.set_reporter(&setup.admin, &setup.client.address, &true);
+    setup.client.set_referral_registry(&setup.admin, &registry_id);
+
+    let alice = new_staker(&setup, 1_000);
+    setup.client.stake(&alice, &1_000);
+    assert!(registry.is_active(&alice));
+    assert_eq!(registry.get_referee_fees(&alice), 0);
+
+    let referrer = Address::generate(&setup.env);
+    setup.env.as_contract(&registry_id, || {
+        ReferralRegistryContract::register_referrer(setup.env.clone(), alice, referrer)
+    });
+}
+
+#[test]
+fn test_market_ids_are_unique_and_indexed_by_counter() {
+    let setup = setup();
+    let creator = new_staker(&setup, 0);
+    let resolve_time = setup.env.ledger().timestamp() + 1_000;
+
+    let mut ids = Vec::new(&setup.env);
+    for _ in 0..3 {
+        ids.push_back(setup.client.create_market(
+            &creator,
+            &String::from_str(&setup.env, "KALE above 1"),
+            &String::from_str(&setup.env, "KALE"),
+            &1,
+            &0,
+            &resolve_time,
+            &0,
+        ));
+    }
+
+    assert_eq!(setup.client.get_market_count(), 3);
+    assert_ne!(ids.get(0), ids.get(1));
+    assert_ne!(ids.get(1), ids.get(2));
+    assert_ne!(ids.get(0), ids.get(2));
+    for i in 0..3 {
+        let id = ids.get(i).unwrap();
+        assert_eq!(setup.client.get_market_id_by_counter(&(i + 1)), id.clone());
+        assert_eq!(setup.client.get_market_info(&id).creator, creator);
+    }
+}
+
+/// Register a Reflector oracle with one node and point markets at it
+fn setup_market_oracle(setup: &Setup) -> (reflector_oracle::ReflectorOracleContractClient<'static>, Address) {
+    use reflector_oracle::{ReflectorOracleContract, ReflectorOracleContractClient};
+
+    let oracle_id = setup.env.register_contract(None, ReflectorOracleContract);
+    let oracle = ReflectorOracleContractClient::new(&setup.env, &oracle_id);
+    let node = Address::generate(&setup.env);
+    oracle.initialize(&setup.admin, &80u32, &3_600u64);
+    oracle.add_oracle_node(&setup.admin, &node);
+    setup.client.set_market_oracle(&setup.admin, &oracle_id);
+    (oracle, node)
+}
+
+#[test]
+fn test_market_bets_resolve_from_oracle_and_pay_winners() {
+    let setup = setup();
+    let (oracle, node) = setup_market_oracle(&setup);
+
+    let creator = new_staker(&setup, 0);
+    let alice = new_staker(&setup, 3_000);
+    let bob = new_staker(&setup, 1_000);
+    let carol = new_staker(&setup, 2_000);
+    let asset = String::from_str(&setup.env, "KALE");
+    let resolve_time = setup.env.ledger().timestamp() + 1_000;
+
+    let market_id = setup.client.create_market(
+        &creator,
+        &String::from_str(&setup.env, "KALE above 100"),
+        &asset,
+        &100,
+        &0,
+        &resolve_time,
+        &0,
+    );
+
+    setup.client.place_bet(&alice, &market_id, &true, &3_000);
+    setup.client.place_bet(&bob, &market_id, &true, &1_000);
+    setup.client.place_bet(&carol, &market_id, &false, &2_000);
+    // The 1% platform fee is taken from each bet and held until settlement
+    assert_eq!(setup.client.get_market_escrow(), 6_000);
+    assert_eq!(setup.client.get_fee_info().platform_fees, 0);
+
+    advance(&setup.env, 1_000);
+    oracle.update_price(&node, &asset, &150, &90u32, &String::from_str(&setup.env, "test"));
+    assert!(setup.client.resolve_market(&creator, &market_id));
+
+    assert_eq!(setup.client.claim(&alice, &market_id), 2_970 + 1_485);
+    assert_eq!(setup.client.claim(&bob, &market_id), 990 + 495);
+    assert_eq!(setup.client.claim(&carol, &market_id), 0);
+    assert_eq!(setup.client.get_market_escrow(), 0);
+    assert_eq!(setup.client.get_fee_info().platform_fees, 60);
+    assert!(setup.client.get_market_position(&market_id, &alice).claimed);
+    assert!(setup.client.check_solvency().solvent);
+}
+
+#[test]
+fn test_fee_router_splits_fees_between_treasury_rewards_and_burn() {
+    let setup = setup();
+    setup.client.set_fee_split(&setup.admin, &FeeSplit { treasury_bps: 5_000, reward_pool_bps: 3_000, burn_bps: 2_000 });
+
+    let reward_pool = setup.client.get_reward_pool();
+    let supply_before = setup.token.balance(&setup.client.address);
+    accrue_platform_fees(&setup, 10_000);
+
+    assert_eq!(setup.client.get_fee_info().platform_fees, 5_000);
+    assert_eq!(setup.client.get_reward_pool(), reward_pool + 3_000);
+    assert_eq!(setup.client.get_routed_fees(), (3_000, 2_000));
+    assert_eq!(setup.token.balance(&setup.client.address), supply_before + 8_000);
+
+    let report = setup.client.check_solvency();
+    assert_eq!(report.surplus, 0);
+    assert!(report.solvent);
+}
+
+#[test]
+fn test_fee_reward_share_is_paid_to_stakers_immediately() {
+    let setup = setup();
+    setup.client.set_fee_split(&setup.admin, &FeeSplit { treasury_bps: 0, reward_pool_bps: BASIS_POINTS, burn_bps: 0 });
+    let alice = new_staker(&setup, 3_000);
+    let bob = new_staker(&setup, 1_000);
+    setup.client.stake(&alice, &3_000);
+    setup.client.stake(&bob, &1_000);
+
+    let reward_pool = setup.client.get_reward_pool();
+    accrue_platform_fees(&setup, 8_000);
+
+    // Split by weight without waiting for emission, leaving the pool untouched
+    assert_eq!(setup.client.get_reward_pool(), reward_pool);
+    assert_eq!(setup.client.claim_rewards(&alice), 6_000);
+    assert_eq!(setup.client.claim_rewards(&bob), 2_000);
+    assert!(setup.client.check_solvency().solvent);
+}
+
+#[test]
+#[should_panic]
+fn test_fee_split_must_sum_to_whole() {
+    let setup = setup();
+    let split = FeeSplit { treasury_bps: 5_000, reward_pool_bps: 3_000, burn_bps: 1_000 };
+    call_direct(&setup, |env| KaleIntegrationContract::set_fee_split(env, setup.admin.clone(), split));
+}
+
+#[test]
+fn test_emergency_withdraw_returns_principal_while_paused() {
+    let setup = setup();
+    let guardian = Address::generate(&setup.env);
+    let staker = new_staker(&setup, 4_000);
+    setup.client.set_guardian(&setup.admin, &guardian);
+
+    setup.client.stake(&staker, &1_000);
+    setup.client.unstake(&staker, &400);
+    setup.client.vault_deposit(&staker, &2_000);
+    advance(&setup.env, 100);
+
+    setup.client.pause(&guardian);
+    assert!(setup.client.is_paused());
+
+    // Principal only: 600 staked, 400 unbonding and 2000 in the vault
+    assert_eq!(setup.client.emergency_withdraw(&staker), 3_000);
+    assert_eq!(setup.token.balance(&staker), 4_000);
+    assert_eq!(setup.client.get_total_staked(), 0);
+    assert_eq!(setup.client.get_vault_shares(&staker), 0);
+    assert!(setup.client.check_solvency().solvent);
+
+    setup.client.unpause(&setup.admin);
+    assert!(!setup.client.is_paused());
+}
+
+#[test]
+fn test_emergency_withdraw_releases_ve_locks() {
+    let setup = setup();
+    let alice = new_staker(&setup, 1_000);
+    setup.client.create_ve_lock(&alice, &1_000, &(setup.env.ledger().timestamp() + 10 * WEEK));
+
+    setup.client.pause(&setup.admin);
+    assert_eq!(setup.client.emergency_withdraw(&alice), 1_000);
+    assert_eq!(setup.token.balance(&alice), 1_000);
+    assert_eq!(setup.client.get_ve_lock(&alice).amount, 0);
+    assert_eq!(setup.client.get_ve_total_locked(), 0);
+    assert_eq!(setup.client.voting_power(&alice), 0);
+    assert_eq!(setup.client.total_voting_power(), 0);
+    assert!(setup.client.check_solvency().solvent);
+}
+
+#[test]
+fn test_emergency_vault_exit_leaves_earned_rewards_with_the_vault() {
+    let setup = setup();
+    let alice = new_staker(&setup, 1_000);
+    let bob = new_staker(&setup, 1_000);
+    let carol = new_staker(&setup, 2_000);
+    setup.client.vault_deposit(&alice, &1_000);
+    setup.client.vault_deposit(&bob, &1_000);
+    setup.client.stake(&carol, &2_000);
+    advance(&setup.env, 100);
+
+    setup.client.pause(&setup.admin);
+    assert_eq!(setup.client.emergency_withdraw(&alice), 1_000);
+    setup.client.unpause(&setup.admin);
+
+    // The vault's half of everything emitted before the exit is still
+    // harvested for Bob, and Carol keeps her half
+    assert_eq!(setup.client.harvest(), REWARD_RATE * 100 / 2);
+    assert_eq!(setup.client.claim_rewards(&carol), REWARD_RATE * 100 / 2);
+    assert!(setup.client.check_solvency().solvent);
+}
+
+#[test]
+#[should_panic]
+fn test_staking_is_blocked_while_paused() {
+    let setup = setup();
+    let staker = new_staker(&setup, 1_000);
+
+    setup.client.pause(&setup.admin);
+    call_direct(&setup, |env| KaleIntegrationContract::stake(env, staker.clone(), 1_000));
+}
+
+#[test]
+#[should_panic]
+fn test_emergency_withdraw_requires_pause() {
+    let setup = setup();
+    let staker = new_staker(&setup, 1_000);
+
+    setup.client.stake(&staker, &1_000);
+    call_direct(&setup, |env| KaleIntegrationContract::emergency_withdraw(env, staker.clone()));
+}
+
+#[test]
+fn test_unstaked_kale_waits_out_the_unbonding_period() {
+    let setup = setup();
+    let alice = new_staker(&setup, 1_000);
+    let unbonding_period = setup.client.get_unbonding_period();
+
+    setup.client.stake(&alice, &1_000);
+    setup.client.unstake(&alice, &400);
+    assert_eq!(setup.client.withdraw_unbonded(&alice), 0);
+
+    advance(&setup.env, unbonding_period - 1);
+    assert_eq!(setup.client.withdraw_unbonded(&alice), 0);
+
+    advance(&setup.env, 1);
+    assert_eq!(setup.client.withdraw_unbonded(&alice), 400);
+    assert_eq!(setup.token.balance(&alice), 400);
+    assert_eq!(setup.client.get_unbonding_requests(&alice).len(), 0);
+    assert!(setup.client.check_solvency().solvent);
+}
+
+#[test]
+fn test_unbonding_requests_keep_their_release_time_at_the_limit() {
+    let setup = setup();
+    let alice = new_staker(&setup, 10_000);
+    let unbonding_period = setup.client.get_unbonding_period();
+    setup.client.stake(&alice, &10_000);
+
+    // Requests released at the same time share one entry
+    let first = setup.client.unstake(&alice, &100);
+    assert_eq!(setup.client.unstake(&alice, &100), first);
+    assert_eq!(setup.client.get_unbonding_requests(&alice).get(0).unwrap().amount, 200);
+
+    advance(&setup.env, 1);
+    for _ in 1..20 {
+        setup.client.unstake(&alice, &100);
+        advance(&setup.env, 1);
+    }
+    let queued = setup.client.get_unbonding_requests(&alice);
+    assert_eq!(queued.len(), 20);
+
+    // Once the first requests mature they are folded together to make room,
+    // and everything already queued keeps its original release time
+    advance(&setup.env, unbonding_period - 19);
+    let last = setup.client.unstake(&alice, &100);
+    let requests = setup.client.get_unbonding_requests(&alice);
+    assert_eq!(requests.len(), 20);
+    let folded = requests.get(0).unwrap();
+    assert_eq!(folded.amount, 300);
+    assert_eq!(folded.release_time, queued.get(1).unwrap().release_time);
+    for i in 2..20 {
+        assert_eq!(requests.get(i - 1).unwrap(), queued.get(i).unwrap());
+    }
+    let latest = requests.get(19).unwrap();
+    assert_eq!(latest.id, last);
+    assert_eq!(latest.release_time, setup.env.ledger().timestamp() + unbonding_period);
+
+    assert_eq!(setup.client.withdraw_unbonded(&alice), 300);
+    advance(&setup.env, unbonding_period);
+    assert_eq!(setup.client.withdraw_unbonded(&alice), 1_900);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #6)")]
+fn test_unbonding_is_rejected_when_the_queue_is_full() {
+    let setup = setup();
+    let alice = new_staker(&setup, 10_000);
+    setup.client.stake(&alice, &10_000);
+    for _ in 0..20 {
+        setup.client.unstake(&alice, &100);
+        advance(&setup.env, 1);
+    }
+
+    call_direct(&setup, |env| KaleIntegrationContract::unstake(env, alice.clone(), 100));
+}
+
+const MONTH_LOCK: u64 = 30 * 24 * 60 * 60;
+
+#[test]
+fn test_lock_multiplier_boosts_reward_weight() {
+    let setup = setup();
+    let alice = new_staker(&setup, 1_000);
+    let bob = new_staker(&setup, 1_000);
+
+    setup.client.stake(&alice, &1_000);
+    setup.client.stake_locked(&bob, &1_000, &MONTH_LOCK);
+    assert_eq!(setup.client.get_stake_info(&bob).weight, 1_250);
+
+    // Emission is split 1_000 : 1_250
+    advance(&setup.env, 100);
+    assert_eq!(setup.client.claim_rewards(&alice), 44_444);
+    assert_eq!(setup.client.claim_rewards(&bob), 55_555);
+}
+
+#[test]
+fn test_extend_lock_moves_to_a_longer_tier() {
+    let setup = setup();
+    let alice = new_staker(&setup, 1_000);
+    let position_id = setup.client.stake_locked(&alice, &1_000, &MONTH_LOCK);
+
+    advance(&setup.env, 10 * 24 * 60 * 60);
+    setup.client.extend_lock(&alice, &position_id, &(3 * MONTH_LOCK));
+
+    let position = setup.client.get_lock_positions(&alice).get(0).unwrap();
+    assert_eq!(position.multiplier_bps, 15_000);
+    assert_eq!(position.unlock_time, setup.env.ledger().timestamp() + 3 * MONTH_LOCK);
+    assert_eq!(setup.client.get_stake_info(&alice).weight, 1_500);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #11)")]
+fn test_extend_lock_rejects_a_shorter_tier() {
+    let setup = setup();
+    let alice = new_staker(&setup, 1_000);
+    let position_id = setup.client.stake_locked(&alice, &1_000, &(3 * MONTH_LOCK));
+
+    call_direct(&setup, |env| KaleIntegrationContract::extend_lock(env, alice.clone(), position_id, MONTH_LOCK));
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #11)")]
+fn test_locked_stake_cannot_be_unstaked_early() {
+    let setup = setup();
+    let alice = new_staker(&setup, 1_000);
+    let position_id = setup.client.stake_locked(&alice, &1_000, &MONTH_LOCK);
+
+    advance(&setup.env, MONTH_LOCK - 1);
+    call_direct(&setup, |env| KaleIntegrationContract::unstake_locked(env, alice.clone(), position_id));
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #13)")]
+fn test_locked_stake_is_not_free_to_unstake() {
+    let setup = setup();
+    let alice = new_staker(&setup, 1_000);
+    setup.client.stake_locked(&alice, &1_000, &MONTH_LOCK);
+
+    call_direct(&setup, |env| KaleIntegrationContract::unstake(env, alice.clone(), 1_000));
+}
+
+#[test]
+fn test_expired_locks_release_to_unboosted_free_stake() {
+    let setup = setup();
+    let alice = new_staker(&setup, 1_000);
+    setup.client.stake_locked(&alice, &1_000, &MONTH_LOCK);
+
+    // Nothing has expired yet
+    assert_eq!(setup.client.release_expired_locks(&alice), 0);
+
+    advance(&setup.env, MONTH_LOCK);
+    assert_eq!(setup.client.release_expired_locks(&alice), 1);
+    assert_eq!(setup.client.get_stake_info(&alice).weight, 1_000);
+    assert_eq!(setup.client.get_lock_positions(&alice).len(), 0);
+    assert_eq!(setup.client.get_stake_breakdown(&alice), StakeBreakdown { staked: 1_000, locked: 0, collateral: 0, free: 1_000 });
+
+    setup.client.unstake(&alice, &1_000);
+    assert_eq!(setup.client.get_total_staked(), 0);
+}
+
+#[test]
+fn test_staker_index_pages_and_swap_removes() {
+    let setup = setup();
+    let alice = new_staker(&setup, 1_000 + MIN_STAKE);
+    let bob = new_staker(&setup, 1_000);
+    let carol = new_staker(&setup, 1_000);
+
+    setup.client.stake(&alice, &1_000);
+    setup.client.stake(&bob, &1_000);
+    setup.client.stake(&carol, &1_000);
+    // Topping up doesn't register the staker twice
+    setup.client.stake(&alice, &MIN_STAKE);
+    assert_eq!(setup.client.get_staker_count(), 3);
+
+    let page = setup.client.get_stakers(&1, &10);
+    assert_eq!(page.len(), 2);
+    assert_eq!(page.get(0), Some(bob.clone()));
+
+    // Fully exiting with no rewards drops the staker; the last one takes its slot
+    setup.client.unstake(&alice, &(1_000 + MIN_STAKE));
+    assert_eq!(setup.client.get_staker_count(), 2);
+    assert_eq!(setup.client.get_stakers(&0, &10).get(0), Some(carol));
+    assert_eq!(setup.client.get_stakers(&5, &10).len(), 0);
+}
+
+#[test]
+fn test_bets_backed_by_stake_settle_against_the_stake() {
+    let setup = setup();
+    setup.client.update_fee_rate(&setup.admin, &0u32);
+    let (oracle, node) = setup_market_oracle(&setup);
+
+    let alice = new_staker(&setup, 2_000);
+    let bob = new_staker(&setup, 1_000);
+    let asset = String::from_str(&setup.env, "KALE");
+    let market_id = setup.client.create_market(
+        &alice,
+        &String::from_str(&setup.env, "KALE above 100"),
+        &asset,
+        &100,
+        &0,
+        &(setup.env.ledger().timestamp() + 1_000),
+        &0,
+    );
+
+    setup.client.stake(&alice, &2_000);
+    setup.client.stake(&bob, &1_000);
+    setup.client.place_bet_with_stake(&alice, &market_id, &true, &1_500);
+    setup.client.place_bet_with_stake(&bob, &market_id, &false, &1_000);
+
+    // Collateral stays staked and earning
+    let breakdown = setup.client.get_stake_breakdown(&alice);
+    assert_eq!(breakdown, StakeBreakdown { staked: 2_000, locked: 0, collateral: 1_500, free: 500 });
+    assert_eq!(setup.client.get_stake_info(&alice).weight, 2_000);
+    assert_eq!(setup.client.get_total_staked(), 3_000);
+    assert_eq!(setup.client.get_market_escrow(), 0);
+
+    advance(&setup.env, 1_000);
+    oracle.update_price(&node, &asset, &150, &90u32, &String::from_str(&setup.env, "test"));
+    setup.client.resolve_market(&alice, &market_id);
+
+    // Anyone can take Bob's lost collateral out of his stake, which leaves
+    // him nothing staked and drops him from the staker index
+    setup.client.claim_rewards(&bob);
+    assert_eq!(setup.client.settle_collateral(&market_id, &bob), 1_000);
+    assert_eq!(setup.client.get_stake_breakdown(&bob), StakeBreakdown { staked: 0, locked: 0, collateral: 0, free: 0 });
+    assert_eq!(setup.client.get_staker_count(), 1);
+    assert_eq!(setup.client.get_market_escrow(), 1_000);
+
+    // Alice's collateral is released in her stake; only the profit is paid out
+    assert_eq!(setup.client.claim(&alice, &market_id), 2_500);
+    assert_eq!(setup.client.get_stake_breakdown(&alice), StakeBreakdown { staked: 2_000, locked: 0, collateral: 0, free: 2_000 });
+    assert_eq!(setup.token.balance(&alice), 1_000);
+
+    assert_eq!(setup.client.claim(&bob, &market_id), 0);
+    assert_eq!(setup.client.get_market_escrow(), 0);
+    assert!(setup.client.check_solvency().solvent);
+}
+
+#[test]
+fn test_winners_can_claim_before_losing_collateral_is_settled() {
+    let setup = setup();
+    let (oracle, node) = setup_market_oracle(&setup);
+
+    let alice = new_staker(&setup, 1_000);
+    let bob = new_staker(&setup, 1_000);
+    let asset = String::from_str(&setup.env, "KALE");
+    let market_id = setup.client.create_market(
+        &alice,
+        &String::from_str(&setup.env, "KALE above 100"),
+        &asset,
+        &100,
+        &0,
+        &(setup.env.ledger().timestamp() + 1_000),
+        &0,
+    );
+
+    // With the default 1% fee, the collateral bet's fee comes out of the stake
+    setup.client.stake(&bob, &1_000);
+    setup.client.place_bet_with_stake(&bob, &market_id, &false, &1_000);
+    assert_eq!(setup.client.get_stake_breakdown(&bob), StakeBreakdown { staked: 990, locked: 0, collateral: 990, free: 0 });
+    setup.client.place_bet(&alice, &market_id, &true, &1_000);
+
+    advance(&setup.env, 1_000);
+    oracle.update_price(&node, &asset, &150, &90u32, &String::from_str(&setup.env, "test"));
+    setup.client.resolve_market(&alice, &market_id);
+
+    assert_eq!(setup.client.claim(&alice, &market_id), 1_980);
+    assert!(setup.client.check_solvency().solvent);
+
+    setup.client.settle_collateral(&market_id, &bob);
+    assert_eq!(setup.client.get_market_escrow(), 0);
+    assert!(setup.client.check_solvency().solvent);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #10)")]
+fn test_markets_reject_prices_published_after_the_resolution_window() {
+    let setup = setup();
+    let (oracle, node) = setup_market_oracle(&setup);
+
+    let alice = new_staker(&setup, 0);
+    let asset = String::from_str(&setup.env, "KALE");
+    let market_id = setup.client.create_market(
+        &alice,
+        &String::from_str(&setup.env, "KALE above 100"),
+        &asset,
+        &100,
+        &0,
+        &(setup.env.ledger().timestamp() + 1_000),
+        &0,
+    );
+
+    advance(&setup.env, 1_000 + 60 * 60 + 1);
+    oracle.update_price(&node, &asset, &150, &90u32, &String::from_str(&setup.env, "test"));
+    call_direct(&setup, |env| KaleIntegrationContract::resolve_market(env, alice.clone(), market_id));
+}
+
+#[test]
+fn test_admin_can_widen_the_resolution_window() {
+    let setup = setup();
+    let (oracle, node) = setup_market_oracle(&setup);
+    setup.client.set_resolution_window(&setup.admin, &(2 * 60 * 60));
+    assert_eq!(setup.client.get_resolution_window(), 2 * 60 * 60);
+
+    let alice = new_staker(&setup, 0);
+    let asset = String::from_str(&setup.env, "KALE");
+    let market_id = setup.client.create_market(
+        &alice,
+        &String::from_str(&setup.env, "KALE above 100"),
+        &asset,
+        &100,
+        &0,
+        &(setup.env.ledger().timestamp() + 1_000),
+        &0,
+    );
+
+    advance(&setup.env, 1_000 + 60 * 60 + 1);
+    oracle.update_price(&node, &asset, &150, &90u32, &String::from_str(&setup.env, "test"));
+    assert!(setup.client.resolve_market(&alice, &market_id));
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #11)")]
+fn test_resolution_window_must_end_before_refunds() {
+    let setup = setup();
+    call_direct(&setup, |env| KaleIntegrationContract::set_resolution_window(env, setup.admin.clone(), 7 * 24 * 60 * 60));
+}
+
+#[test]
+fn test_unresolved_markets_refund_every_bet_after_the_grace_period() {
+    let setup = setup();
+    let alice = new_staker(&setup, 1_000);
+    let bob = new_staker(&setup, 1_000);
+    let market_id = setup.client.create_market(
+        &alice,
+        &String::from_str(&setup.env, "KALE above 100"),
+        &String::from_str(&setup.env, "KALE"),
+        &100,
+        &0,
+        &(setup.env.ledger().timestamp() + 1_000),
+        &0,
+    );
+
+    setup.client.place_bet(&alice, &market_id, &true, &1_000);
+    setup.client.stake(&bob, &1_000);
+    setup.client.place_bet_with_stake(&bob, &market_id, &false, &1_000);
+
+    advance(&setup.env, 1_000 + 7 * 24 * 60 * 60);
+    setup.client.refund_unresolved_market(&market_id);
+
+    // The 1% platform fee is refunded with each bet
+    assert_eq!(setup.client.claim(&alice, &market_id), 1_000);
+    assert_eq!(setup.token.balance(&alice), 1_000);
+
+    // Bob's collateral is released back into his stake and his fee, taken
+    // out of it, is paid back
+    assert_eq!(setup.client.claim(&bob, &market_id), 1_000);
+    assert_eq!(setup.client.get_stake_breakdown(&bob), StakeBreakdown { staked: 990, locked: 0, collateral: 0, free: 990 });
+    assert_eq!(setup.token.balance(&bob), 10);
+    assert_eq!(setup.client.get_market_escrow(), 0);
+    assert_eq!(setup.client.get_fee_info().platform_fees, 0);
+    assert!(setup.client.check_solvency().solvent);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #11)")]
+fn test_markets_cannot_be_refunded_before_the_grace_period() {
+    let setup = setup();
+    let alice = new_staker(&setup, 0);
+    let market_id = setup.client.create_market(
+        &alice,
+        &String::from_str(&setup.env, "KALE above 100"),
+        &String::from_str(&setup.env, "KALE"),
+        &100,
+        &0,
+        &(setup.env.ledger().timestamp() + 1_000),
+        &0,
+    );
+
+    advance(&setup.env, 1_000 + 7 * 24 * 60 * 60 - 1);
+    call_direct(&setup, |env| KaleIntegrationContract::refund_unresolved_market(env, market_id));
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #13)")]
+fn test_stake_backing_bets_cannot_be_unstaked() {
+    let setup = setup();
+    let alice = new_staker(&setup, 2_000);
+    let market_id = setup.client.create_market(
+        &alice,
+        &String::from_str(&setup.env, "KALE above 100"),
+        &String::from_str(&setup.env, "KALE"),
+        &100,
+        &0,
+        &(setup.env.ledger().timestamp() + 1_000),
+        &0,
+    );
+
+    setup.client.stake(&alice, &2_000);
+    setup.client.place_bet_with_stake(&alice, &market_id, &true, &1_500);
+    call_direct(&setup, |env| KaleIntegrationContract::unstake(env, alice.clone(), 600));
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #6)")]
+fn test_stake_bets_respect_minimum_stake() {
+    let setup = setup();
+    let alice = new_staker(&setup, 1_000);
+    let market_id = setup.client.create_market(
+        &alice,
+        &String::from_str(&setup.env, "KALE above 100"),
+        &String::from_str(&setup.env, "KALE"),
+        &100,
+        &0,
+        &(setup.env.ledger().timestamp() + 1_000),
+        &0,
+    );
+
+    setup.client.stake(&alice, &1_000);
+    call_direct(&setup, |env| KaleIntegrationContract::place_bet_with_stake(env, alice.clone(), market_id, true, MIN_STAKE - 1));
+}
+
+#[test]
+fn test_emergency_withdraw_keeps_stake_backing_bets() {
+    let setup = setup();
+    setup.client.update_fee_rate(&setup.admin, &0u32);
+    let alice = new_staker(&setup, 2_000);
+    let market_id = setup.client.create_market(
+        &alice,
+        &String::from_str(&setup.env, "KALE above 100"),
+        &String::from_str(&setup.env, "KALE"),
+        &100,
+        &0,
+        &(setup.env.ledger().timestamp() + 1_000),
+        &0,
+    );
+
+    setup.client.stake(&alice, &2_000);
+    setup.client.place_bet_with_stake(&alice, &market_id, &true, &1_500);
+
+    setup.client.pause(&setup.admin);
+    assert_eq!(setup.client.emergency_withdraw(&alice), 500);
+    assert_eq!(setup.client.get_stake_breakdown(&alice), StakeBreakdown { staked: 1_500, locked: 0, collateral: 1_500, free: 0 });
+    assert_eq!(setup.client.get_total_staked(), 1_500);
+    assert!(setup.client.check_solvency().solvent);
+}
+
+#[test]
+#[should_panic]
+fn test_locked_stake_cannot_back_bets() {
+    let setup = setup();
+    let alice = new_staker(&setup, 1_000);
+    let market_id = setup.client.create_market(
+        &alice,
+        &String::from_str(&setup.env, "KALE above 100"),
+        &String::from_str(&setup.env, "KALE"),
+        &100,
+        &0,
+        &(setup.env.ledger().timestamp() + 1_000),
+        &0,
+    );
+
+    setup.client.stake_locked(&alice, &1_000, &(30 * 24 * 60 * 60));
+    call_direct(&setup, |env| KaleIntegrationContract::place_bet_with_stake(env, alice.clone(), market_id, true, 100));
+}
+
+#[test]
+fn test_no_loss_pool_returns_principal_and_pays_yield_to_winners() {
+    let setup = setup();
+    let (oracle, node) = setup_market_oracle(&setup);
+
+    let creator = new_staker(&setup, 0);
+    let alice = new_staker(&setup, 3_000);
+    let bob = new_staker(&setup, 1_000);
+    let asset = String::from_str(&setup.env, "KALE");
+    let market_id = setup.client.create_no_loss_market(
+        &creator,
+        &String::from_str(&setup.env, "KALE above 100"),
+        &asset,
+        &100,
+        &0,
+        &(setup.env.ledger().timestamp() + 1_000),
+        &0,
+    );
+
+    setup.client.deposit_no_loss(&alice, &market_id, &true, &3_000);
+    setup.client.deposit_no_loss(&bob, &market_id, &false, &1_000);
+    assert_eq!(setup.client.get_total_staked(), 4_000);
+
+    advance(&setup.env, 1_000);
+    oracle.update_price(&node, &asset, &150, &90u32, &String::from_str(&setup.env, "test"));
+    assert!(setup.client.resolve_market(&creator, &market_id));
+
+    // The pool is the only staker, so it earned the full emission
+    let prize = setup.client.settle_no_loss_pool(&market_id);
+    assert_eq!(prize, REWARD_RATE * 1_000);
+    assert_eq!(setup.client.get_total_staked(), 0);
+
+    assert_eq!(setup.client.claim_no_loss(&alice, &market_id), 3_000 + prize);
+    assert_eq!(setup.client.claim_no_loss(&bob, &market_id), 1_000);
+    assert_eq!(setup.client.get_market_escrow(), 0);
+    assert!(setup.client.check_solvency().solvent);
+
+    // Payouts wait out the unbonding period like any unstake
+    assert_eq!(setup.client.withdraw_unbonded(&alice), 0);
+    advance(&setup.env, setup.client.get_unbonding_period());
+    assert_eq!(setup.client.withdraw_unbonded(&alice), 3_000 + prize);
+    assert_eq!(setup.client.withdraw_unbonded(&bob), 1_000);
+    assert_eq!(setup.token.balance(&alice), 3_000 + prize);
+}
+
+#[test]
+fn test_no_loss_prize_dust_goes_to_last_claimant() {
+    let setup = setup();
+    let (oracle, node) = setup_market_oracle(&setup);
+
+    let creator = new_staker(&setup, 0);
+    let asset = String::from_str(&setup.env, "KALE");
+    let market_id = setup.client.create_no_loss_market(
+        &creator,
+        &String::from_str(&setup.env, "KALE above 100"),
+        &asset,
+        &100,
+        &0,
+        &(setup.env.ledger().timestamp() + 1_000),
+        &0,
+    );
+
+    let depositors = [new_staker(&setup, 1_000), new_staker(&setup, 1_000), new_staker(&setup, 1_000)];
+    for depositor in depositors.iter() {
+        setup.client.deposit_no_loss(depositor, &market_id, &true, &1_000);
+    }
+
+    advance(&setup.env, 1_001);
+    oracle.update_price(&node, &asset, &150, &90u32, &String::from_str(&setup.env, "test"));
+    setup.client.resolve_market(&creator, &market_id);
+    let prize = setup.client.settle_no_loss_pool(&market_id);
+    assert!(prize % 3 != 0);
+
+    assert_eq!(setup.client.claim_no_loss(&depositors[0], &market_id), 1_000 + prize / 3);
+    assert_eq!(setup.client.claim_no_loss(&depositors[1], &market_id), 1_000 + prize / 3);
+    assert_eq!(setup.client.claim_no_loss(&depositors[2], &market_id), 1_000 + prize - 2 * (prize / 3));
+    assert_eq!(setup.client.get_market_escrow(), 0);
+    assert!(setup.client.check_solvency().solvent);
+}
+
+#[test]
+fn test_no_loss_emergency_withdraw_returns_deposit_while_paused() {
+    let setup = setup();
+    let alice = new_staker(&setup, 1_000);
+    let market_id = setup.client.create_no_loss_market(
+        &alice,
+        &String::from_str(&setup.env, "KALE above 100"),
+        &String::from_str(&setup.env, "KALE"),
+        &100,
+        &0,
+        &(setup.env.ledger().timestamp() + 1_000),
+        &0,
+    );
+
+    setup.client.deposit_no_loss(&alice, &market_id, &true, &1_000);
+    setup.client.pause(&setup.admin);
+
+    assert_eq!(setup.client.emergency_withdraw_no_loss(&alice, &market_id), 1_000);
+    assert_eq!(setup.token.balance(&alice), 1_000);
+    assert_eq!(setup.client.get_no_loss_pool(&market_id).unwrap().principal, 0);
+    assert!(setup.client.check_solvency().solvent);
+}
+
+#[test]
+#[should_panic]
+fn test_no_loss_markets_reject_bets() {
+    let setup = setup();
+    let alice = new_staker(&setup, 1_000);
+    let market_id = setup.client.create_no_loss_market(
+        &alice,
+        &String::from_str(&setup.env, "KALE above 100"),
+        &String::from_str(&setup.env, "KALE"),
+        &100,
+        &0,
+        &(setup.env.ledger().timestamp() + 1_000),
+        &0,
+    );
+
+    call_direct(&setup, |env| KaleIntegrationContract::place_bet(env, alice.clone(), market_id, true, 100));
+}
+
+/// Factory that only recognises the markets it was told about
+#[contract]
+pub struct MockFactory;
+
+#[contractimpl]
+impl MockFactory {
+    pub fn add_market(env: Env, market: Address) {
+        env.storage().instance().set(&market, &true);
+    }
+
+    pub fn get_market_id(env: Env, contract_id: Address) -> Result<u32, ContractError> {
+        if env.storage().instance().has(&contract_id) {
+            Ok(1)
+        } else {
+            Err(ContractError::MarketNotFound)
+        }
+    }
+}
+
+fn setup_market_factory(setup: &Setup) -> (MockFactoryClient<'static>, Address) {
+    let factory = MockFactoryClient::new(&setup.env, &setup.env.register_contract(None, MockFactory));
+    let market = Address::generate(&setup.env);
+    factory.add_market(&market);
+    setup.client.set_market_factory(&setup.admin, &factory.address);
+    (factory, market)
+}
+
+#[test]
+fn test_factory_markets_settle_bets_against_the_stake() {
+    let setup = setup();
+    let (_, market) = setup_market_factory(&setup);
+    let alice = new_staker(&setup, 2_000);
+    setup.client.stake(&alice, &2_000);
+
+    setup.client.lock_market_collateral(&market, &alice, &1_500);
+    assert_eq!(setup.client.get_market_collateral(&market, &alice), 1_500);
+    assert_eq!(setup.client.get_stake_breakdown(&alice), StakeBreakdown { staked: 2_000, locked: 0, collateral: 1_500, free: 500 });
+
+    // The lost part of the bet leaves the stake for the market
+    setup.client.settle_market_collateral(&market, &alice, &1_000);
+    assert_eq!(setup.token.balance(&market), 1_000);
+    assert_eq!(setup.client.get_market_collateral(&market, &alice), 0);
+    assert_eq!(setup.client.get_stake_breakdown(&alice), StakeBreakdown { staked: 1_000, locked: 0, collateral: 0, free: 1_000 });
+    assert!(setup.client.check_solvency().solvent);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1)")]
+fn test_unknown_markets_cannot_lock_collateral() {
+    let setup = setup();
+    setup_market_factory(&setup);
+    let alice = new_staker(&setup, 2_000);
+    setup.client.stake(&alice, &2_000);
+
+    let market = Address::generate(&setup.env);
+    call_direct(&setup, |env| KaleIntegrationContract::lock_market_collateral(env, market, alice, 1_000));
+}
@@ -0,0 +1,187 @@
+//! Auto-compounding staking vault.
+//!
+//! The vault holds a single flexible stake owned by this contract. Depositors
+//! receive shares, and `harvest` restakes the vault's rewards so the value of
+//! each share grows instead of individual balances.
+//! No-loss pools (see `no_loss`) also hold vault shares for their depositors.
+
+use soroban_sdk::{contractimpl, contracttype, token, Address, Env, panic_with_error, log};
+use shared_types::{StakeInfo, ContractError, Config};
+
+use crate::{DataKey, KaleIntegrationContract, KaleIntegrationContractClient};
+#[cfg(any(test, feature = "testutils"))]
+use crate::__KaleIntegrationContract_fn_set_registry;
+
+/// Vault totals for UI and share pricing
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct VaultInfo {
+    pub total_shares: i128,
+    pub total_assets: i128,
+    pub pending_rewards: i128,
+}
+
+#[contractimpl]
+impl KaleIntegrationContract {
+    /// Deposit KALE into the compounding vault, returning the shares minted
+    pub fn vault_deposit(env: Env, depositor: Address, amount: i128) -> i128 {
+        depositor.require_auth();
+        Self::require_not_paused(&env);
+
+        let config: Config = env.storage().instance().get(&DataKey::Config).unwrap();
+        if amount < config.min_stake_amount {
+            panic_with_error!(&env, ContractError::InvalidAmount);
+        }
+
+        let token_client = token::Client::new(&env, &config.kale_token);
+        token_client.transfer(&depositor, &env.current_contract_address(), &amount);
+
+        let shares = Self::mint_vault_shares(&env, amount);
+
+        let depositor_shares = Self::get_vault_shares(env.clone(), depositor.clone());
+        env.storage().persistent().set(&DataKey::VaultShares(depositor.clone()), &(depositor_shares + shares));
+
+        log!(&env, "Vault deposit: {} KALE for {} shares by {}", amount, shares, depositor);
+
+        shares
+    }
+
+    /// Redeem vault shares. The KALE they are worth enters the unbonding queue.
+    pub fn vault_withdraw(env: Env, depositor: Address, shares: i128) -> i128 {
+        depositor.require_auth();
+        Self::require_not_paused(&env);
+
+        let depositor_shares = Self::get_vault_shares(env.clone(), depositor.clone());
+        if shares <= 0 || shares > depositor_shares {
+            panic_with_error!(&env, ContractError::InsufficientStake);
+        }
+
+        let assets = Self::burn_vault_shares(&env, shares);
+
+        if depositor_shares == shares {
+            env.storage().persistent().remove(&DataKey::VaultShares(depositor.clone()));
+        } else {
+            env.storage().persistent().set(&DataKey::VaultShares(depositor.clone()), &(depositor_shares - shares));
+        }
+
+        if assets > 0 {
+            Self::queue_unbonding(&env, &depositor, assets);
+        }
+
+        log!(&env, "Vault withdraw: {} shares for {} KALE by {}", shares, assets, depositor);
+
+        assets
+    }
+
+    /// Claim the vault's staking rewards and restake them for all depositors.
+    /// Callable by anyone, typically a keeper.
+    pub fn harvest(env: Env) -> i128 {
+        Self::require_not_paused(&env);
+
+        let before: i128 = Self::vault_stake(&env).amount;
+        let vault_stake = Self::harvest_vault(&env);
+        let compounded = vault_stake.amount - before;
+
+        log!(&env, "Vault harvested {} KALE", compounded);
+
+        compounded
+    }
+
+    /// Get a depositor's vault shares
+    pub fn get_vault_shares(env: Env, depositor: Address) -> i128 {
+        env.storage().persistent().get(&DataKey::VaultShares(depositor)).unwrap_or(0)
+    }
+
+    /// Get the KALE value of a depositor's shares at the last harvest
+    pub fn get_vault_balance(env: Env, depositor: Address) -> i128 {
+        let total_shares = Self::vault_total_shares(&env);
+        if total_shares == 0 {
+            return 0;
+        }
+        Self::get_vault_shares(env.clone(), depositor) * Self::vault_stake(&env).amount / total_shares
+    }
+
+    /// Get vault totals
+    pub fn get_vault_info(env: Env) -> VaultInfo {
+        let vault_stake = Self::vault_stake(&env);
+        VaultInfo {
+            total_shares: Self::vault_total_shares(&env),
+            total_assets: vault_stake.amount,
+            pending_rewards: vault_stake.accumulated_rewards + Self::calculate_pending_rewards(&env, &vault_stake),
+        }
+    }
+
+    // Private helper functions
+
+    fn vault_total_shares(env: &Env) -> i128 {
+        env.storage().instance().get(&DataKey::VaultTotalShares).unwrap_or(0)
+    }
+
+    /// The vault's stake, without registering it as a staker
+    fn vault_stake(env: &Env) -> StakeInfo {
+        let vault = env.current_contract_address();
+        let current_time = env.ledger().timestamp();
+
+        env.storage().persistent()
+            .get(&DataKey::StakeInfo(vault.clone()))
+            .unwrap_or(StakeInfo {
+                staker: vault,
+                amount: 0,
+                weight: 0,
+                stake_time: current_time,
+                last_reward_time: current_time,
+                accumulated_rewards: 0,
+                reward_per_token_paid: Self::reward_per_token(env),
+            })
+    }
+
+    /// Stake `amount` KALE already held by this contract in the vault and
+    /// return the shares minted for it
+    pub(crate) fn mint_vault_shares(env: &Env, amount: i128) -> i128 {
+        // Compound first so new depositors don't capture rewards earned before them
+        let mut vault_stake = Self::harvest_vault(env);
+
+        let total_shares = Self::vault_total_shares(env);
+        let shares = if total_shares == 0 || vault_stake.amount == 0 {
+            amount
+        } else {
+            amount * total_shares / vault_stake.amount
+        };
+
+        if shares <= 0 {
+            panic_with_error!(env, ContractError::InvalidAmount);
+        }
+
+        Self::adjust_stake(env, &mut vault_stake, amount, amount);
+        env.storage().persistent().set(&DataKey::StakeInfo(vault_stake.staker.clone()), &vault_stake);
+        env.storage().instance().set(&DataKey::VaultTotalShares, &(total_shares + shares));
+
+        shares
+    }
+
+    /// Burn vault shares, unstaking and returning the KALE they are worth
+    pub(crate) fn burn_vault_shares(env: &Env, shares: i128) -> i128 {
+        let mut vault_stake = Self::harvest_vault(env);
+        let total_shares = Self::vault_total_shares(env);
+        let assets = shares * vault_stake.amount / total_shares;
+
+        Self::adjust_stake(env, &mut vault_stake, -assets, -assets);
+        env.storage().persistent().set(&DataKey::StakeInfo(vault_stake.staker.clone()), &vault_stake);
+        env.storage().instance().set(&DataKey::VaultTotalShares, &(total_shares - shares));
+
+        assets
+    }
+
+    /// Restake the vault's claimable rewards and return its updated stake
+    fn harvest_vault(env: &Env) -> StakeInfo {
+        let mut vault_stake = Self::vault_stake(env);
+        let rewards = Self::take_claimable_rewards(env, &mut vault_stake);
+
+        if rewards > 0 {
+            Self::adjust_stake(env, &mut vault_stake, rewards, rewards);
+        }
+
+        env.storage().persistent().set(&DataKey::StakeInfo(vault_stake.staker.clone()), &vault_stake);
+        vault_stake
+    }
+}
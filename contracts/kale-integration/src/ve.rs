@@ -0,0 +1,376 @@
+//! Vote-escrowed KALE.
+//!
+//! KALE locked for up to four years grants voting power that decays linearly
+//! to zero at unlock. Points are stored in "KALE-seconds" (amount times time
+//! remaining) so sums stay exact; they are divided by `MAX_VE_LOCK` on read,
+//! giving one vote per KALE locked for the full four years.
+//!
+//! Global supply is tracked with a point history plus scheduled slope changes
+//! at each unlock week, so both per-user and total power can be queried at
+//! any past timestamp by binary search.
+
+use soroban_sdk::{contractimpl, contracttype, token, Address, Env, panic_with_error, log};
+use shared_types::{ContractError, Config};
+
+use crate::{DataKey, KaleIntegrationContract, KaleIntegrationContractClient};
+#[cfg(any(test, feature = "testutils"))]
+use crate::__KaleIntegrationContract_fn_set_registry;
+
+/// Vote-escrow storage keys
+#[contracttype]
+#[derive(Clone)]
+pub enum VeDataKey {
+    Lock(Address),
+    TotalLocked,
+    UserPointCount(Address),
+    UserPoint(Address, u32),
+    GlobalPointCount,
+    GlobalPoint(u32),
+    SlopeChange(u64),
+}
+
+const WEEK: u64 = 7 * 24 * 60 * 60;
+const MAX_VE_LOCK: u64 = 4 * 365 * 24 * 60 * 60;
+/// Most weeks walked per call. Longer than `MAX_VE_LOCK`, so every lock has
+/// expired by the time the cap is reached.
+const MAX_VE_WEEKS: u32 = 255;
+
+/// A user's escrowed KALE and its unlock time (rounded down to a week)
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct VeLock {
+    pub amount: i128,
+    pub end: u64,
+}
+
+/// Voting power snapshot: `bias` decreases by `slope` every second after `ts`
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct VePoint {
+    pub bias: i128,
+    pub slope: i128,
+    pub ts: u64,
+}
+
+impl VePoint {
+    fn bias_at(&self, timestamp: u64) -> i128 {
+        let bias = self.bias - self.slope * (timestamp - self.ts) as i128;
+        if bias < 0 { 0 } else { bias }
+    }
+}
+
+#[contractimpl]
+impl KaleIntegrationContract {
+    /// Lock KALE until `unlock_time` (rounded down to a week, at most four years out)
+    pub fn create_ve_lock(env: Env, owner: Address, amount: i128, unlock_time: u64) {
+        owner.require_auth();
+        Self::require_not_paused(&env);
+
+        let old_lock = Self::get_ve_lock(env.clone(), owner.clone());
+        if old_lock.amount > 0 {
+            panic_with_error!(&env, ContractError::InvalidAmount);
+        }
+        if amount <= 0 {
+            panic_with_error!(&env, ContractError::InvalidAmount);
+        }
+
+        let end = Self::ve_unlock_week(&env, unlock_time);
+        Self::deposit_ve(&env, &owner, amount);
+        Self::write_ve_lock(&env, &owner, &old_lock, &VeLock { amount, end });
+
+        log!(&env, "ve lock created: {} KALE until {} by {}", amount, end, owner);
+    }
+
+    /// Add KALE to an existing, unexpired lock without changing its unlock time
+    pub fn increase_ve_amount(env: Env, owner: Address, amount: i128) {
+        owner.require_auth();
+        Self::require_not_paused(&env);
+
+        let old_lock = Self::active_ve_lock(&env, &owner);
+        if amount <= 0 {
+            panic_with_error!(&env, ContractError::InvalidAmount);
+        }
+
+        Self::deposit_ve(&env, &owner, amount);
+        let new_lock = VeLock { amount: old_lock.amount + amount, end: old_lock.end };
+        Self::write_ve_lock(&env, &owner, &old_lock, &new_lock);
+
+        log!(&env, "ve lock increased by {} KALE by {}", amount, owner);
+    }
+
+    /// Push an unexpired lock's unlock time further out
+    pub fn increase_ve_unlock_time(env: Env, owner: Address, unlock_time: u64) {
+        owner.require_auth();
+        Self::require_not_paused(&env);
+
+        let old_lock = Self::active_ve_lock(&env, &owner);
+        let end = Self::ve_unlock_week(&env, unlock_time);
+        if end <= old_lock.end {
+            panic_with_error!(&env, ContractError::InvalidTimestamp);
+        }
+
+        let new_lock = VeLock { amount: old_lock.amount, end };
+        Self::write_ve_lock(&env, &owner, &old_lock, &new_lock);
+
+        log!(&env, "ve lock extended to {} by {}", end, owner);
+    }
+
+    /// Withdraw KALE from an expired lock
+    pub fn withdraw_ve(env: Env, owner: Address) -> i128 {
+        owner.require_auth();
+
+        let old_lock = Self::get_ve_lock(env.clone(), owner.clone());
+        if old_lock.amount == 0 {
+            panic_with_error!(&env, ContractError::StakeNotFound);
+        }
+        if env.ledger().timestamp() < old_lock.end {
+            panic_with_error!(&env, ContractError::InvalidTimestamp);
+        }
+
+        Self::release_ve_lock(&env, &owner);
+
+        let config: Config = env.storage().instance().get(&DataKey::Config).unwrap();
+        let token_client = token::Client::new(&env, &config.kale_token);
+        token_client.transfer(&env.current_contract_address(), &owner, &old_lock.amount);
+
+        log!(&env, "ve lock withdrawn: {} KALE by {}", old_lock.amount, owner);
+
+        old_lock.amount
+    }
+
+    /// Get a user's lock (zero amount if none)
+    pub fn get_ve_lock(env: Env, owner: Address) -> VeLock {
+        env.storage().persistent()
+            .get(&VeDataKey::Lock(owner))
+            .unwrap_or(VeLock { amount: 0, end: 0 })
+    }
+
+    /// Get total KALE held in ve locks
+    pub fn get_ve_total_locked(env: Env) -> i128 {
+        env.storage().instance().get(&VeDataKey::TotalLocked).unwrap_or(0)
+    }
+
+    /// Get a user's current voting power
+    pub fn voting_power(env: Env, owner: Address) -> i128 {
+        let now = env.ledger().timestamp();
+        Self::voting_power_at(env, owner, now)
+    }
+
+    /// Get a user's voting power at `timestamp`
+    pub fn voting_power_at(env: Env, owner: Address, timestamp: u64) -> i128 {
+        let count: u32 = env.storage().persistent()
+            .get(&VeDataKey::UserPointCount(owner.clone()))
+            .unwrap_or(0);
+
+        match Self::find_ve_point(count, timestamp, |i| {
+            env.storage().persistent().get(&VeDataKey::UserPoint(owner.clone(), i)).unwrap()
+        }) {
+            Some(point) => point.bias_at(timestamp) / MAX_VE_LOCK as i128,
+            None => 0,
+        }
+    }
+
+    /// Get total voting power now
+    pub fn total_voting_power(env: Env) -> i128 {
+        let now = env.ledger().timestamp();
+        Self::total_voting_power_at(env, now)
+    }
+
+    /// Get total voting power at `timestamp`
+    pub fn total_voting_power_at(env: Env, timestamp: u64) -> i128 {
+        let count: u32 = env.storage().instance().get(&VeDataKey::GlobalPointCount).unwrap_or(0);
+
+        match Self::find_ve_point(count, timestamp, |i| {
+            env.storage().persistent().get(&VeDataKey::GlobalPoint(i)).unwrap()
+        }) {
+            Some(point) => Self::advance_ve_point(&env, point, timestamp, false).bias / MAX_VE_LOCK as i128,
+            None => 0,
+        }
+    }
+
+    // Private helper functions
+
+    /// Clear a user's lock, expired or not, and return the KALE it held
+    pub(crate) fn release_ve_lock(env: &Env, owner: &Address) -> i128 {
+        let old_lock = Self::get_ve_lock(env.clone(), owner.clone());
+        if old_lock.amount == 0 {
+            return 0;
+        }
+
+        Self::write_ve_lock(env, owner, &old_lock, &VeLock { amount: 0, end: 0 });
+
+        let total_locked = Self::get_ve_total_locked(env.clone());
+        env.storage().instance().set(&VeDataKey::TotalLocked, &(total_locked - old_lock.amount));
+
+        old_lock.amount
+    }
+
+    fn ve_unlock_week(env: &Env, unlock_time: u64) -> u64 {
+        let now = env.ledger().timestamp();
+        let end = unlock_time / WEEK * WEEK;
+        if end <= now || end > now + MAX_VE_LOCK {
+            panic_with_error!(env, ContractError::InvalidTimestamp);
+        }
+        end
+    }
+
+    fn active_ve_lock(env: &Env, owner: &Address) -> VeLock {
+        let lock = Self::get_ve_lock(env.clone(), owner.clone());
+        if lock.amount == 0 {
+            panic_with_error!(env, ContractError::StakeNotFound);
+        }
+        if lock.end <= env.ledger().timestamp() {
+            panic_with_error!(env, ContractError::InvalidTimestamp);
+        }
+        lock
+    }
+
+    fn deposit_ve(env: &Env, owner: &Address, amount: i128) {
+        let config: Config = env.storage().instance().get(&DataKey::Config).unwrap();
+        let token_client = token::Client::new(env, &config.kale_token);
+        token_client.transfer(owner, &env.current_contract_address(), &amount);
+
+        let total_locked = Self::get_ve_total_locked(env.clone());
+        env.storage().instance().set(&VeDataKey::TotalLocked, &(total_locked + amount));
+    }
+
+    /// The point a lock contributes at `now`: zero once expired
+    fn ve_lock_point(lock: &VeLock, now: u64) -> VePoint {
+        if lock.end > now {
+            VePoint { bias: lock.amount * (lock.end - now) as i128, slope: lock.amount, ts: now }
+        } else {
+            VePoint { bias: 0, slope: 0, ts: now }
+        }
+    }
+
+    /// Store a user's new lock and checkpoint user and global history
+    fn write_ve_lock(env: &Env, owner: &Address, old_lock: &VeLock, new_lock: &VeLock) {
+        let now = env.ledger().timestamp();
+        let old_point = Self::ve_lock_point(old_lock, now);
+        let new_point = Self::ve_lock_point(new_lock, now);
+
+        // Bring the global point up to now, recording each week crossed
+        let mut global = Self::checkpoint_ve_global(env);
+        global.bias += new_point.bias - old_point.bias;
+        global.slope += new_point.slope - old_point.slope;
+        Self::record_ve_global_point(env, &global);
+
+        // Reschedule where the user's slope drops out of the global supply
+        if old_point.slope > 0 {
+            Self::add_ve_slope_change(env, old_lock.end, -old_point.slope);
+        }
+        if new_point.slope > 0 {
+            Self::add_ve_slope_change(env, new_lock.end, new_point.slope);
+        }
+
+        let count: u32 = env.storage().persistent()
+            .get(&VeDataKey::UserPointCount(owner.clone()))
+            .unwrap_or(0);
+        let index = if count > 0 {
+            let last: VePoint = env.storage().persistent()
+                .get(&VeDataKey::UserPoint(owner.clone(), count - 1))
+                .unwrap();
+            if last.ts == now { count - 1 } else { count }
+        } else {
+            0
+        };
+        env.storage().persistent().set(&VeDataKey::UserPoint(owner.clone(), index), &new_point);
+        env.storage().persistent().set(&VeDataKey::UserPointCount(owner.clone()), &(index + 1));
+
+        if new_lock.amount == 0 {
+            env.storage().persistent().remove(&VeDataKey::Lock(owner.clone()));
+        } else {
+            env.storage().persistent().set(&VeDataKey::Lock(owner.clone()), new_lock);
+        }
+    }
+
+    /// Advance the latest global point to now, writing a point per week crossed
+    fn checkpoint_ve_global(env: &Env) -> VePoint {
+        let now = env.ledger().timestamp();
+        let count: u32 = env.storage().instance().get(&VeDataKey::GlobalPointCount).unwrap_or(0);
+        if count == 0 {
+            return VePoint { bias: 0, slope: 0, ts: now };
+        }
+
+        let last: VePoint = env.storage().persistent().get(&VeDataKey::GlobalPoint(count - 1)).unwrap();
+        Self::advance_ve_point(env, last, now, true)
+    }
+
+    /// Walk a global point forward to `timestamp`, applying the slope change at
+    /// each week boundary crossed. Optionally records the intermediate points.
+    fn advance_ve_point(env: &Env, mut point: VePoint, timestamp: u64, record: bool) -> VePoint {
+        let mut week = point.ts / WEEK * WEEK;
+        for _ in 0..MAX_VE_WEEKS {
+            if point.ts >= timestamp {
+                break;
+            }
+            week += WEEK;
+            let step = if week > timestamp { timestamp } else { week };
+
+            point.bias -= point.slope * (step - point.ts) as i128;
+            if point.bias < 0 {
+                point.bias = 0;
+            }
+            point.ts = step;
+
+            if step == week {
+                let slope_change: i128 = env.storage().persistent().get(&VeDataKey::SlopeChange(week)).unwrap_or(0);
+                point.slope -= slope_change;
+                if point.slope < 0 {
+                    point.slope = 0;
+                }
+
+                if record && step < timestamp {
+                    Self::record_ve_global_point(env, &point);
+                }
+            }
+        }
+
+        // Past the cap every lock has expired, so nothing is left to decay
+        if point.ts < timestamp {
+            point = VePoint { bias: 0, slope: 0, ts: timestamp };
+        }
+
+        point
+    }
+
+    fn record_ve_global_point(env: &Env, point: &VePoint) {
+        let count: u32 = env.storage().instance().get(&VeDataKey::GlobalPointCount).unwrap_or(0);
+        let index = if count > 0 {
+            let last: VePoint = env.storage().persistent().get(&VeDataKey::GlobalPoint(count - 1)).unwrap();
+            if last.ts == point.ts { count - 1 } else { count }
+        } else {
+            0
+        };
+        env.storage().persistent().set(&VeDataKey::GlobalPoint(index), point);
+        env.storage().instance().set(&VeDataKey::GlobalPointCount, &(index + 1));
+    }
+
+    fn add_ve_slope_change(env: &Env, week: u64, delta: i128) {
+        let slope_change: i128 = env.storage().persistent().get(&VeDataKey::SlopeChange(week)).unwrap_or(0);
+        if slope_change + delta == 0 {
+            env.storage().persistent().remove(&VeDataKey::SlopeChange(week));
+        } else {
+            env.storage().persistent().set(&VeDataKey::SlopeChange(week), &(slope_change + delta));
+        }
+    }
+
+    /// Binary search for the last point at or before `timestamp`
+    fn find_ve_point<F: Fn(u32) -> VePoint>(count: u32, timestamp: u64, point_at: F) -> Option<VePoint> {
+        if count == 0 {
+            return None;
+        }
+
+        let (mut low, mut high) = (0u32, count);
+        while low < high {
+            let mid = (low + high) / 2;
+            if point_at(mid).ts <= timestamp {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+
+        if low == 0 { None } else { Some(point_at(low - 1)) }
+    }
+}
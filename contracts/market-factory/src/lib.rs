@@ -1,36 +1,191 @@
 #![no_std]
+#![allow(clippy::too_many_arguments)]
 
 use soroban_sdk::{
-    contract, contractimpl, contracttype, token, Address, Env, String, Vec, Map,
+    contract, contractimpl, contracttype, token, Address, BytesN, Env, IntoVal, String, Val, Vec,
     panic_with_error, log, symbol_short,
 };
-use shared_types::{Market, ContractError, Config};
+use shared_types::{MarketStatus, MarketParams, ContractError};
+use shared_types::clients::{PredictionMarketClient, KaleIntegrationClient, ReferralRegistryClient};
 
 const DAY_IN_LEDGERS: u32 = 17280;
 const INSTANCE_BUMP_AMOUNT: u32 = 7 * DAY_IN_LEDGERS;
 const INSTANCE_LIFETIME_THRESHOLD: u32 = INSTANCE_BUMP_AMOUNT - DAY_IN_LEDGERS;
+const PERSISTENT_BUMP_AMOUNT: u32 = 30 * DAY_IN_LEDGERS;
+const PERSISTENT_LIFETIME_THRESHOLD: u32 = PERSISTENT_BUMP_AMOUNT - DAY_IN_LEDGERS;
+
+const MAX_PAGE_SIZE: u32 = 100;
+const MAX_FEE_RATE: u32 = 1_000; // 10% in basis points
+
+// Reputation score in basis points; new creators start at the neutral score
+const NEUTRAL_REPUTATION: u32 = 5000;
+const MAX_REPUTATION: u32 = 10000;
+const CLEAN_MARKET_CREDIT: u32 = 500;
+const CANCELLED_MARKET_PENALTY: u32 = 1000;
+const DISPUTED_MARKET_PENALTY: u32 = 2000;
 
 #[contracttype]
 pub enum DataKey {
     Config,
     MarketCount,
-    Markets(Vec<Address>),
-    CreatorFee,
-    MinMarketFee,
+    // Market registry, keyed by sequential id (1-based)
+    Market(u32),
+    MarketIdByAddress(Address),
+    // Secondary indexes: (index key, position) -> market id, plus a length per index
+    CreatorMarketCount(Address),
+    CreatorMarket(Address, u32),
+    AssetMarketCount(String),
+    AssetMarket(String, u32),
+    StatusMarketCount(MarketStatus),
+    StatusMarket(MarketStatus, u32),
+    StatusMarketPosition(u32),
+    // Creator bonds
+    BondsHeld,
+    CreatorBond(u32),
+    CreatorReputation(Address),
+    SlashedBondClaimed(u32, Address),
+    // Moderation queue
+    ModerationEnabled,
+    Approver(Address),
+    TrustedCreator(Address),
+    MarketRequestCount,
+    MarketRequest(u32),
+    PendingQueueLength,
+    PendingQueue(u32),
+    PendingQueuePosition(u32),
+    PendingEscrow,
+    // Keeper bounties
+    KeeperBountyPaid(u32),
+}
+
+/// Tunable factory parameters, updatable by the admin
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FactoryParams {
+    pub oracle_address: Address,
+    pub platform_fee_rate: u32,    // basis points
+    pub max_creator_fee_rate: u32, // basis points
+    pub min_market_fee: i128,
+    pub min_market_duration: u64,
+    pub max_market_duration: u64,
+    pub min_bet_amount: i128, // lower bound for a market's min bet
+    pub max_bet_amount: i128, // upper bound for a market's max bet
+    pub base_creator_bond: i128,
+    pub dispute_window: u64,
+    pub expiry_grace_period: u64, // unresolved markets expire this long after resolve_time
+    pub keeper_bounty: i128,
+    pub max_keeper_bounty_per_market: i128, // total paid across all of a market's steps
+}
+
+/// Factory configuration; the single source of truth for all factory parameters
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FactoryConfig {
+    pub admin: Address,
+    pub kale_token: Address,
+    pub kale_integration: Address,
+    pub market_wasm_hash: BytesN<32>, // prediction market code deployed for each market
+    pub params: FactoryParams,
+}
+
+/// Registry entry for a market deployed by the factory
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MarketRecord {
+    pub id: u32,
+    pub contract_id: Address,
+    pub creator: Address,
+    pub oracle_asset: String,
+    pub resolve_time: u64,
+    pub created_at: u64,
+    pub status: MarketStatus,
+}
+
+/// Market creation awaiting moderation; fee and bond are held in escrow
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MarketRequest {
+    pub id: u32,
+    pub creator: Address,
+    pub event_description: String,
+    pub oracle_asset: String,
+    pub target_price: i128,
+    pub condition: u32,
+    pub resolve_time: u64,
+    pub min_bet_amount: i128,
+    pub max_bet_amount: i128,
+    pub creator_fee_rate: u32,
+    pub platform_fee_rate: u32,  // factory params when submitted
+    pub oracle_address: Address,
+    pub fee_paid: i128,
+    pub bond_paid: i128,
+    pub submitted_at: u64,
+}
+
+/// Creator track record used to size creation bonds
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CreatorReputation {
+    pub clean_markets: u32,
+    pub cancelled_markets: u32,
+    pub disputed_markets: u32,
+    pub score: u32, // basis points, 5000 = neutral
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum BondStatus {
+    Held,
+    Released,
+    Slashed,
+}
+
+/// Refundable bond posted by a market creator
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CreatorBond {
+    pub market_id: u32,
+    pub creator: Address,
+    pub amount: i128,
+    pub status: BondStatus,
+    pub resolved_at: Option<u64>,
+    pub slashed_pool: i128,    // total bets at slash time, used for pro-rata payouts
+    pub slashed_claimed: i128, // bets whose share has been claimed
+    pub slashed_paid: i128,    // bond paid out to claimants so far
+}
+
+/// Lifecycle step a keeper can trigger for a market
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum KeeperAction {
+    Close,
+    Resolve,
+    Finalize,
+    Expire,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct KeeperTask {
+    pub market_id: u32,
+    pub action: KeeperAction,
+}
+
+/// Filter for paginated registry queries
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum MarketFilter {
+    All,
+    Creator(Address),
+    Asset(String),
+    Status(MarketStatus),
 }
 
 #[contracttype]
 pub enum EventType {
-    MarketCreated {
-        market_id: u32,
-        creator: Address,
-        contract_id: Address,
-        event_description: String,
-        oracle_asset: String,
-        target_price: i128,
-        condition: u32,
-        resolve_time: u64,
-    },
+    // market_id, creator, contract_id, event_description, oracle_asset, target_price, condition, resolve_time
+    MarketCreated(u32, Address, Address, String, String, i128, u32, u64),
+    ConfigurationUpdated(FactoryParams, FactoryParams), // old_params, new_params
 }
 
 #[contract]
@@ -43,30 +198,27 @@ impl MarketFactoryContract {
         env: Env,
         admin: Address,
         kale_token: Address,
-        reflector_oracle: Address,
-        creator_fee_rate: u32,
-        min_market_fee: i128,
+        kale_integration: Address,
+        market_wasm_hash: BytesN<32>,
+        params: FactoryParams,
     ) {
         if env.storage().instance().has(&DataKey::Config) {
             panic_with_error!(&env, ContractError::NotAuthorized);
         }
 
-        let config = Config {
+        Self::validate_params(&env, &params);
+
+        let config = FactoryConfig {
             admin: admin.clone(),
             kale_token,
-            oracle_address: reflector_oracle,
-            platform_fee_rate: creator_fee_rate,
-            min_stake_amount: min_market_fee,
-            reward_rate_per_second: 0, // Not used in factory
-            max_market_duration: 30 * 24 * 60 * 60, // 30 days
-            min_market_duration: 60 * 60, // 1 hour
+            kale_integration,
+            market_wasm_hash,
+            params,
         };
 
         env.storage().instance().set(&DataKey::Config, &config);
         env.storage().instance().set(&DataKey::MarketCount, &0u32);
-        env.storage().instance().set(&DataKey::Markets(Vec::new(&env)), &Vec::<Address>::new(&env));
-        env.storage().instance().set(&DataKey::CreatorFee, &creator_fee_rate);
-        env.storage().instance().set(&DataKey::MinMarketFee, &min_market_fee);
+        env.storage().instance().set(&DataKey::BondsHeld, &0i128);
 
         env.storage()
             .instance()
@@ -75,7 +227,8 @@ impl MarketFactoryContract {
         log!(&env, "MarketFactory initialized by admin: {}", admin);
     }
 
-    /// Create a new prediction market
+    /// Create a new prediction market. When moderation is enabled, only trusted
+    /// creators may deploy directly; everyone else must use `submit_market`.
     pub fn create_market(
         env: Env,
         creator: Address,
@@ -90,91 +243,458 @@ impl MarketFactoryContract {
     ) -> Address {
         creator.require_auth();
 
-        let config: Config = env.storage().instance().get(&DataKey::Config).unwrap();
-        let current_time = env.ledger().timestamp();
-        let min_market_fee: i128 = env.storage().instance().get(&DataKey::MinMarketFee).unwrap();
-
-        // Validate market parameters
-        if resolve_time <= current_time {
-            panic_with_error!(&env, ContractError::InvalidTimestamp);
+        if Self::requires_moderation(env.clone(), creator.clone()) {
+            panic_with_error!(&env, ContractError::NotAuthorized);
         }
 
-        let duration = resolve_time - current_time;
-        if duration < config.min_market_duration || duration > config.max_market_duration {
-            panic_with_error!(&env, ContractError::InvalidTimestamp);
-        }
+        let request = Self::collect_market_request(
+            &env,
+            creator,
+            event_description,
+            oracle_asset,
+            target_price,
+            condition,
+            resolve_time,
+            min_bet_amount,
+            max_bet_amount,
+            creator_fee_rate,
+        );
 
-        if condition > 1 {
-            panic_with_error!(&env, ContractError::InvalidOutcome);
-        }
+        Self::deploy_market(&env, &request)
+    }
 
-        if creator_fee_rate > 1000 { // Max 10%
-            panic_with_error!(&env, ContractError::InvalidAmount);
-        }
+    /// Submit a market to the moderation queue, returning the request id. Only
+    /// open while moderation is enabled. The creation fee and bond are escrowed
+    /// until the request is approved or rejected.
+    pub fn submit_market(
+        env: Env,
+        creator: Address,
+        event_description: String,
+        oracle_asset: String,
+        target_price: i128,
+        condition: u32,
+        resolve_time: u64,
+        min_bet_amount: i128,
+        max_bet_amount: i128,
+        creator_fee_rate: u32,
+    ) -> u32 {
+        creator.require_auth();
 
-        if min_bet_amount <= 0 || max_bet_amount <= min_bet_amount {
-            panic_with_error!(&env, ContractError::InvalidAmount);
+        if !env.storage().instance().get(&DataKey::ModerationEnabled).unwrap_or(false) {
+            panic_with_error!(&env, ContractError::NotAuthorized);
         }
 
-        // Transfer KALE fee from creator to prevent spam
-        let token_client = token::Client::new(&env, &config.kale_token);
-        token_client.transfer(&creator, &env.current_contract_address(), &min_market_fee);
-
-        // Deploy new prediction market contract
-        let market_contract_id = Self::deploy_market_contract(&env);
-
-        // Initialize the new market contract
-        Self::initialize_market_contract(
+        let mut request = Self::collect_market_request(
             &env,
-            &market_contract_id,
-            &creator,
-            &event_description,
-            &oracle_asset,
+            creator,
+            event_description,
+            oracle_asset,
             target_price,
             condition,
             resolve_time,
             min_bet_amount,
             max_bet_amount,
             creator_fee_rate,
-            &config,
         );
 
-        // Update factory state
-        let mut market_count: u32 = env.storage().instance().get(&DataKey::MarketCount).unwrap_or(0);
-        market_count += 1;
+        let mut request_count: u32 = env.storage().instance().get(&DataKey::MarketRequestCount).unwrap_or(0);
+        request_count += 1;
+        request.id = request_count;
 
-        let mut markets: Vec<Address> = env.storage().instance()
-            .get(&DataKey::Markets(Vec::new(&env)))
-            .unwrap_or_else(|| Vec::new(&env));
-        markets.push_back(market_contract_id.clone());
+        env.storage().instance().set(&DataKey::MarketRequestCount, &request_count);
+        Self::set_registry_entry(&env, &DataKey::MarketRequest(request.id), &request);
+        Self::adjust_pending_escrow(&env, request.fee_paid + request.bond_paid);
 
-        env.storage().instance().set(&DataKey::MarketCount, &market_count);
-        env.storage().instance().set(&DataKey::Markets(Vec::new(&env)), &markets);
+        // Append to the pending queue
+        let queue_length: u32 = env.storage().persistent().get(&DataKey::PendingQueueLength).unwrap_or(0);
+        Self::set_registry_entry(&env, &DataKey::PendingQueue(queue_length), &request.id);
+        Self::set_registry_entry(&env, &DataKey::PendingQueuePosition(request.id), &queue_length);
+        Self::set_registry_entry(&env, &DataKey::PendingQueueLength, &(queue_length + 1));
 
-        // Emit market created event
-        let event = EventType::MarketCreated {
-            market_id: market_count,
-            creator: creator.clone(),
-            contract_id: market_contract_id.clone(),
-            event_description: event_description.clone(),
-            oracle_asset: oracle_asset.clone(),
-            target_price,
-            condition,
-            resolve_time,
-        };
+        log!(&env, "Market request {} submitted by {}", request.id, request.creator);
 
-        env.events().publish((symbol_short!("market_created"), event));
+        request.id
+    }
+
+    /// Approve a pending market request and deploy it (approvers only)
+    pub fn approve_market(env: Env, approver: Address, request_id: u32) -> Address {
+        approver.require_auth();
+        Self::require_approver(&env, &approver);
+
+        let request = Self::take_pending_request(&env, request_id);
+        let config: FactoryConfig = env.storage().instance().get(&DataKey::Config).unwrap();
+
+        // The market must still leave room for the minimum duration
+        if request.resolve_time < env.ledger().timestamp() + config.params.min_market_duration {
+            panic_with_error!(&env, ContractError::InvalidTimestamp);
+        }
 
-        log!(&env, "Market created: {} by {}", event_description, creator);
+        Self::adjust_pending_escrow(&env, -(request.fee_paid + request.bond_paid));
+        let market_contract_id = Self::deploy_market(&env, &request);
+
+        log!(&env, "Market request {} approved by {}", request_id, approver);
 
         market_contract_id
     }
 
-    /// Get all markets created by this factory
+    /// Reject a pending market request, refunding the fee and bond (approvers only)
+    pub fn reject_market(env: Env, approver: Address, request_id: u32) {
+        approver.require_auth();
+        Self::require_approver(&env, &approver);
+
+        let request = Self::take_pending_request(&env, request_id);
+        let refund = request.fee_paid + request.bond_paid;
+
+        Self::adjust_pending_escrow(&env, -refund);
+
+        if refund > 0 {
+            let config: FactoryConfig = env.storage().instance().get(&DataKey::Config).unwrap();
+            let token_client = token::Client::new(&env, &config.kale_token);
+            token_client.transfer(&env.current_contract_address(), &request.creator, &refund);
+        }
+
+        log!(&env, "Market request {} rejected by {}", request_id, approver);
+    }
+
+    /// Get a pending market request
+    pub fn get_market_request(env: Env, request_id: u32) -> MarketRequest {
+        env.storage().persistent()
+            .get(&DataKey::MarketRequest(request_id))
+            .unwrap_or_else(|| panic_with_error!(&env, ContractError::MarketNotFound))
+    }
+
+    /// List pending market requests, `limit` entries starting at `offset`
+    pub fn list_pending_markets(env: Env, offset: u32, limit: u32) -> Vec<MarketRequest> {
+        let mut requests = Vec::new(&env);
+        let total: u32 = env.storage().persistent().get(&DataKey::PendingQueueLength).unwrap_or(0);
+        let limit = if limit > MAX_PAGE_SIZE { MAX_PAGE_SIZE } else { limit };
+
+        if offset >= total {
+            return requests;
+        }
+
+        let end = if total - offset > limit { offset + limit } else { total };
+        for position in offset..end {
+            let request_id: u32 = env.storage().persistent().get(&DataKey::PendingQueue(position)).unwrap();
+            requests.push_back(env.storage().persistent().get(&DataKey::MarketRequest(request_id)).unwrap());
+        }
+
+        requests
+    }
+
+    /// Enable or disable the moderation queue (admin only)
+    pub fn set_moderation_enabled(env: Env, admin: Address, enabled: bool) {
+        Self::require_admin(&env, &admin);
+        env.storage().instance().set(&DataKey::ModerationEnabled, &enabled);
+        log!(&env, "Market moderation enabled: {}", enabled);
+    }
+
+    /// Grant or revoke approver rights (admin only)
+    pub fn set_approver(env: Env, admin: Address, approver: Address, is_approver: bool) {
+        Self::require_admin(&env, &admin);
+
+        if is_approver {
+            env.storage().persistent().set(&DataKey::Approver(approver.clone()), &true);
+        } else {
+            env.storage().persistent().remove(&DataKey::Approver(approver.clone()));
+        }
+
+        log!(&env, "Approver {} set to {}", approver, is_approver);
+    }
+
+    /// Whitelist a creator to skip the moderation queue (admin only)
+    pub fn set_trusted_creator(env: Env, admin: Address, creator: Address, trusted: bool) {
+        Self::require_admin(&env, &admin);
+
+        if trusted {
+            env.storage().persistent().set(&DataKey::TrustedCreator(creator.clone()), &true);
+        } else {
+            env.storage().persistent().remove(&DataKey::TrustedCreator(creator.clone()));
+        }
+
+        log!(&env, "Trusted creator {} set to {}", creator, trusted);
+    }
+
+    /// Check whether a creator's markets must go through the moderation queue
+    pub fn requires_moderation(env: Env, creator: Address) -> bool {
+        let enabled: bool = env.storage().instance().get(&DataKey::ModerationEnabled).unwrap_or(false);
+        enabled && !env.storage().persistent().has(&DataKey::TrustedCreator(creator))
+    }
+
+    /// Get a market registry entry by id
+    pub fn get_market(env: Env, market_id: u32) -> MarketRecord {
+        env.storage().persistent()
+            .get(&DataKey::Market(market_id))
+            .unwrap_or_else(|| panic_with_error!(&env, ContractError::MarketNotFound))
+    }
+
+    /// Look up a market id by its deployed contract address
+    pub fn get_market_id(env: Env, contract_id: Address) -> u32 {
+        env.storage().persistent()
+            .get(&DataKey::MarketIdByAddress(contract_id))
+            .unwrap_or_else(|| panic_with_error!(&env, ContractError::MarketNotFound))
+    }
+
+    /// Get the contract addresses of the first page of markets.
+    /// Deprecated: use `list_markets`, which pages through every market.
     pub fn get_markets(env: Env) -> Vec<Address> {
-        env.storage().instance()
-            .get(&DataKey::Markets(Vec::new(&env)))
-            .unwrap_or_else(|| Vec::new(&env))
+        let mut addresses = Vec::new(&env);
+        for record in Self::list_markets(env.clone(), 0, MAX_PAGE_SIZE, MarketFilter::All).iter() {
+            addresses.push_back(record.contract_id);
+        }
+        addresses
+    }
+
+    /// List markets matching a filter, `limit` entries starting at `offset`
+    pub fn list_markets(env: Env, offset: u32, limit: u32, filter: MarketFilter) -> Vec<MarketRecord> {
+        let mut markets = Vec::new(&env);
+        let total = Self::count_markets(env.clone(), filter.clone());
+        let limit = if limit > MAX_PAGE_SIZE { MAX_PAGE_SIZE } else { limit };
+
+        if offset >= total {
+            return markets;
+        }
+
+        let end = if total - offset > limit { offset + limit } else { total };
+        for position in offset..end {
+            let market_id = match &filter {
+                MarketFilter::All => position + 1,
+                MarketFilter::Creator(creator) => env.storage().persistent()
+                    .get(&DataKey::CreatorMarket(creator.clone(), position))
+                    .unwrap(),
+                MarketFilter::Asset(asset) => env.storage().persistent()
+                    .get(&DataKey::AssetMarket(asset.clone(), position))
+                    .unwrap(),
+                MarketFilter::Status(status) => env.storage().persistent()
+                    .get(&DataKey::StatusMarket(status.clone(), position))
+                    .unwrap(),
+            };
+            markets.push_back(env.storage().persistent().get(&DataKey::Market(market_id)).unwrap());
+        }
+
+        markets
+    }
+
+    /// Count markets matching a filter
+    pub fn count_markets(env: Env, filter: MarketFilter) -> u32 {
+        match filter {
+            MarketFilter::All => env.storage().instance().get(&DataKey::MarketCount).unwrap_or(0),
+            MarketFilter::Creator(creator) => env.storage().persistent()
+                .get(&DataKey::CreatorMarketCount(creator))
+                .unwrap_or(0),
+            MarketFilter::Asset(asset) => env.storage().persistent()
+                .get(&DataKey::AssetMarketCount(asset))
+                .unwrap_or(0),
+            MarketFilter::Status(status) => env.storage().persistent()
+                .get(&DataKey::StatusMarketCount(status))
+                .unwrap_or(0),
+        }
+    }
+
+    /// Move a market forward to closed or resolved (admin or the market
+    /// contract itself). Cancellation only happens through expiry or an upheld
+    /// dispute.
+    pub fn set_market_status(env: Env, caller: Address, market_id: u32, status: MarketStatus) {
+        caller.require_auth();
+
+        let config: FactoryConfig = env.storage().instance().get(&DataKey::Config).unwrap();
+        let mut record = Self::get_market(env.clone(), market_id);
+
+        if caller != config.admin && caller != record.contract_id {
+            panic_with_error!(&env, ContractError::NotAuthorized);
+        }
+
+        if record.status == status {
+            return;
+        }
+
+        match (&record.status, &status) {
+            (MarketStatus::Active, MarketStatus::Closed)
+            | (MarketStatus::Active, MarketStatus::Resolved)
+            | (MarketStatus::Closed, MarketStatus::Resolved) => {}
+            (MarketStatus::Resolved, _) | (MarketStatus::Cancelled, _) => {
+                panic_with_error!(&env, ContractError::MarketAlreadyResolved)
+            }
+            _ => panic_with_error!(&env, ContractError::NotAuthorized),
+        }
+
+        Self::apply_status(&env, &mut record, status);
+
+        log!(&env, "Market {} status updated by {}", market_id, caller);
+    }
+
+    /// Uphold a dispute against a resolved market before its dispute window
+    /// closes (admin only)
+    pub fn uphold_dispute(env: Env, admin: Address, market_id: u32) {
+        admin.require_auth();
+
+        let config: FactoryConfig = env.storage().instance().get(&DataKey::Config).unwrap();
+        if admin != config.admin {
+            panic_with_error!(&env, ContractError::NotAuthorized);
+        }
+
+        let mut record = Self::get_market(env.clone(), market_id);
+        let bond = Self::get_creator_bond(env.clone(), market_id);
+        if bond.status != BondStatus::Held {
+            panic_with_error!(&env, ContractError::AlreadyClaimed);
+        }
+
+        let resolved_at = bond.resolved_at
+            .unwrap_or_else(|| panic_with_error!(&env, ContractError::MarketClosed));
+        if env.ledger().timestamp() >= resolved_at + config.params.dispute_window {
+            panic_with_error!(&env, ContractError::InvalidTimestamp);
+        }
+
+        Self::transition_status(&env, &mut record, MarketStatus::Cancelled);
+        Self::slash_bond(&env, &record);
+        Self::update_reputation(&env, &record.creator, 0, 0, 1);
+
+        log!(&env, "Dispute upheld for market {}", market_id);
+    }
+
+    /// Return the creator bond once a resolved market has cleared the dispute window
+    pub fn release_creator_bond(env: Env, market_id: u32) -> i128 {
+        Self::release_bond(&env, market_id)
+    }
+
+    /// List the next keeper step for markets in one status index slice.
+    /// Scans at most `limit` entries of the `status` index starting at `offset`.
+    pub fn get_keeper_tasks(env: Env, status: MarketStatus, offset: u32, limit: u32) -> Vec<KeeperTask> {
+        let mut tasks = Vec::new(&env);
+        let total = Self::count_markets(env.clone(), MarketFilter::Status(status.clone()));
+        let limit = if limit > MAX_PAGE_SIZE { MAX_PAGE_SIZE } else { limit };
+
+        if offset >= total {
+            return tasks;
+        }
+
+        let end = if total - offset > limit { offset + limit } else { total };
+        for position in offset..end {
+            let market_id: u32 = env.storage().persistent()
+                .get(&DataKey::StatusMarket(status.clone(), position))
+                .unwrap();
+            let record: MarketRecord = env.storage().persistent().get(&DataKey::Market(market_id)).unwrap();
+
+            if let Some(action) = Self::next_keeper_action(&env, &record) {
+                tasks.push_back(KeeperTask { market_id, action });
+            }
+        }
+
+        tasks
+    }
+
+    /// Advance a market to its next lifecycle step, paying the keeper the
+    /// KALE bounty for it until the market's bounty cap is reached.
+    pub fn run_keeper_task(env: Env, keeper: Address, market_id: u32) -> KeeperAction {
+        keeper.require_auth();
+
+        let mut record = Self::get_market(env.clone(), market_id);
+        let action = Self::next_keeper_action(&env, &record)
+            .unwrap_or_else(|| panic_with_error!(&env, ContractError::InvalidTimestamp));
+
+        match action {
+            KeeperAction::Close => {
+                PredictionMarketClient::new(&env, &record.contract_id).close(&env.current_contract_address());
+                Self::apply_status(&env, &mut record, MarketStatus::Closed);
+            }
+            KeeperAction::Resolve => {
+                let market_client = PredictionMarketClient::new(&env, &record.contract_id);
+                if !market_client.is_resolved() {
+                    market_client.resolve(&env.current_contract_address());
+                }
+                Self::apply_status(&env, &mut record, MarketStatus::Resolved);
+            }
+            KeeperAction::Finalize => {
+                Self::release_bond(&env, market_id);
+            }
+            KeeperAction::Expire => {
+                Self::expire_market(&env, &mut record);
+            }
+        }
+
+        let bounty = Self::pay_keeper_bounty(&env, &keeper, market_id);
+
+        log!(&env, "Keeper {} advanced market {} for {} KALE", keeper, market_id, bounty);
+
+        action
+    }
+
+    /// Total keeper bounty paid out for a market
+    pub fn get_keeper_bounty_paid(env: Env, market_id: u32) -> i128 {
+        env.storage().persistent().get(&DataKey::KeeperBountyPaid(market_id)).unwrap_or(0)
+    }
+
+    /// Claim a bettor's pro-rata share of a slashed creator bond. The last
+    /// claimant also gets the rounding dust.
+    pub fn claim_slashed_bond(env: Env, bettor: Address, market_id: u32) -> i128 {
+        bettor.require_auth();
+
+        let mut bond = Self::get_creator_bond(env.clone(), market_id);
+        if bond.status != BondStatus::Slashed || bond.slashed_pool == 0 {
+            panic_with_error!(&env, ContractError::BetNotFound);
+        }
+
+        let claim_key = DataKey::SlashedBondClaimed(market_id, bettor.clone());
+        if env.storage().persistent().has(&claim_key) {
+            panic_with_error!(&env, ContractError::AlreadyClaimed);
+        }
+
+        let record = Self::get_market(env.clone(), market_id);
+        let market_client = PredictionMarketClient::new(&env, &record.contract_id);
+        let (bet_for, bet_against) = market_client.get_user_bets(&bettor);
+        let bets = bet_for + bet_against;
+
+        bond.slashed_claimed += bets;
+        let share = if bond.slashed_claimed >= bond.slashed_pool {
+            bond.amount - bond.slashed_paid
+        } else {
+            bond.amount * bets / bond.slashed_pool
+        };
+
+        if share == 0 {
+            panic_with_error!(&env, ContractError::BetNotFound);
+        }
+
+        bond.slashed_paid += share;
+        env.storage().persistent().set(&DataKey::CreatorBond(market_id), &bond);
+        env.storage().persistent().set(&claim_key, &true);
+        Self::adjust_bonds_held(&env, -share);
+
+        let config: FactoryConfig = env.storage().instance().get(&DataKey::Config).unwrap();
+        let token_client = token::Client::new(&env, &config.kale_token);
+        token_client.transfer(&env.current_contract_address(), &bettor, &share);
+
+        log!(&env, "Slashed bond share claimed: {} KALE by {}", share, bettor);
+
+        share
+    }
+
+    /// Get the bond posted for a market
+    pub fn get_creator_bond(env: Env, market_id: u32) -> CreatorBond {
+        env.storage().persistent()
+            .get(&DataKey::CreatorBond(market_id))
+            .unwrap_or_else(|| panic_with_error!(&env, ContractError::MarketNotFound))
+    }
+
+    /// Get a creator's on-chain reputation
+    pub fn get_creator_reputation(env: Env, creator: Address) -> CreatorReputation {
+        env.storage().persistent()
+            .get(&DataKey::CreatorReputation(creator))
+            .unwrap_or(CreatorReputation {
+                clean_markets: 0,
+                cancelled_markets: 0,
+                disputed_markets: 0,
+                score: NEUTRAL_REPUTATION,
+            })
+    }
+
+    /// Bond a creator must post for their next market, scaled by reputation:
+    /// 1.5x the base bond at score 0, 1x at neutral, 0.5x at the maximum score
+    pub fn get_required_bond(env: Env, creator: Address) -> i128 {
+        let config: FactoryConfig = env.storage().instance().get(&DataKey::Config).unwrap();
+        let base_bond = config.params.base_creator_bond;
+        let reputation = Self::get_creator_reputation(env, creator);
+        base_bond * (15000 - reputation.score as i128) / 10000
     }
 
     /// Get total number of markets created
@@ -183,47 +703,41 @@ impl MarketFactoryContract {
     }
 
     /// Get factory configuration
-    pub fn get_config(env: Env) -> Config {
+    pub fn get_config(env: Env) -> FactoryConfig {
         env.storage().instance().get(&DataKey::Config).unwrap()
     }
 
-    /// Update factory configuration (admin only)
-    pub fn update_config(
-        env: Env,
-        admin: Address,
-        creator_fee_rate: u32,
-        min_market_fee: i128,
-    ) {
-        admin.require_auth();
+    /// Replace the factory parameters (admin only). Takes effect for all subsequent calls.
+    pub fn update_config(env: Env, admin: Address, params: FactoryParams) {
+        Self::require_admin(&env, &admin);
+        Self::validate_params(&env, &params);
 
-        let config: Config = env.storage().instance().get(&DataKey::Config).unwrap();
+        let mut config: FactoryConfig = env.storage().instance().get(&DataKey::Config).unwrap();
+        let old_params = config.params.clone();
 
-        if admin != config.admin {
-            panic_with_error!(&env, ContractError::NotAuthorized);
-        }
+        config.params = params.clone();
+        env.storage().instance().set(&DataKey::Config, &config);
 
-        if creator_fee_rate > 1000 {
-            panic_with_error!(&env, ContractError::InvalidAmount);
-        }
+        let event = EventType::ConfigurationUpdated(old_params, params);
 
-        env.storage().instance().set(&DataKey::CreatorFee, &creator_fee_rate);
-        env.storage().instance().set(&DataKey::MinMarketFee, &min_market_fee);
+        env.events().publish((symbol_short!("cfg_upd"),), event);
 
-        log!(&env, "Factory config updated: creator_fee={}, min_fee={}", creator_fee_rate, min_market_fee);
+        log!(&env, "Factory config updated by admin: {}", admin);
     }
 
     /// Withdraw accumulated fees (admin only)
     pub fn withdraw_fees(env: Env, admin: Address, recipient: Address) {
         admin.require_auth();
 
-        let config: Config = env.storage().instance().get(&DataKey::Config).unwrap();
+        let config: FactoryConfig = env.storage().instance().get(&DataKey::Config).unwrap();
 
         if admin != config.admin {
             panic_with_error!(&env, ContractError::NotAuthorized);
         }
 
+        // Creator bonds and pending-request escrow are held on behalf of users, not fee revenue
         let token_client = token::Client::new(&env, &config.kale_token);
-        let balance = token_client.balance(&env.current_contract_address());
+        let balance = Self::available_fees(&env, &config);
 
         if balance > 0 {
             token_client.transfer(&env.current_contract_address(), &recipient, &balance);
@@ -233,28 +747,470 @@ impl MarketFactoryContract {
     }
 
     // Private helper functions
-    fn deploy_market_contract(env: &Env) -> Address {
-        // In a real implementation, this would deploy the prediction market contract
-        // For now, we'll use a mock contract ID
-        Address::from_string(&env.string().from_str("CDBG4XY2T5RRPH7HKGZIWMR2MFPLC6RJ453ITXQGNQXG6LNVL4375MRJ"))
+    fn require_admin(env: &Env, admin: &Address) {
+        admin.require_auth();
+
+        let config: FactoryConfig = env.storage().instance().get(&DataKey::Config).unwrap();
+        if *admin != config.admin {
+            panic_with_error!(env, ContractError::NotAuthorized);
+        }
+    }
+
+    fn validate_params(env: &Env, params: &FactoryParams) {
+        if params.platform_fee_rate > MAX_FEE_RATE
+            || params.max_creator_fee_rate > MAX_FEE_RATE
+            || params.platform_fee_rate + params.max_creator_fee_rate > MAX_FEE_RATE
+        {
+            panic_with_error!(env, ContractError::InvalidAmount);
+        }
+
+        if params.min_market_fee < 0
+            || params.base_creator_bond < 0
+            || params.keeper_bounty < 0
+            || params.max_keeper_bounty_per_market < params.keeper_bounty
+        {
+            panic_with_error!(env, ContractError::InvalidAmount);
+        }
+
+        if params.min_bet_amount <= 0 || params.max_bet_amount <= params.min_bet_amount {
+            panic_with_error!(env, ContractError::InvalidAmount);
+        }
+
+        if params.min_market_duration == 0 || params.max_market_duration < params.min_market_duration {
+            panic_with_error!(env, ContractError::InvalidTimestamp);
+        }
     }
 
-    fn initialize_market_contract(
+    fn require_approver(env: &Env, approver: &Address) {
+        let config: FactoryConfig = env.storage().instance().get(&DataKey::Config).unwrap();
+        if *approver != config.admin && !env.storage().persistent().has(&DataKey::Approver(approver.clone())) {
+            panic_with_error!(env, ContractError::NotAuthorized);
+        }
+    }
+
+    /// Validate market parameters and collect the creation fee and bond
+    fn collect_market_request(
         env: &Env,
-        contract_id: &Address,
-        creator: &Address,
-        event_description: &String,
-        oracle_asset: &String,
+        creator: Address,
+        event_description: String,
+        oracle_asset: String,
         target_price: i128,
         condition: u32,
         resolve_time: u64,
         min_bet_amount: i128,
         max_bet_amount: i128,
         creator_fee_rate: u32,
-        config: &Config,
-    ) {
-        // In a real implementation, this would call the initialize function on the deployed contract
-        // For now, we'll just log the initialization
-        log!(env, "Initializing market contract: {} with oracle asset: {}", contract_id, oracle_asset);
+    ) -> MarketRequest {
+        let config: FactoryConfig = env.storage().instance().get(&DataKey::Config).unwrap();
+        let params = &config.params;
+        let current_time = env.ledger().timestamp();
+        let min_market_fee = params.min_market_fee;
+
+        // Validate market parameters
+        if resolve_time <= current_time {
+            panic_with_error!(env, ContractError::InvalidTimestamp);
+        }
+
+        let duration = resolve_time - current_time;
+        if duration < params.min_market_duration || duration > params.max_market_duration {
+            panic_with_error!(env, ContractError::InvalidTimestamp);
+        }
+
+        if condition > 1 {
+            panic_with_error!(env, ContractError::InvalidOutcome);
+        }
+
+        if creator_fee_rate > params.max_creator_fee_rate {
+            panic_with_error!(env, ContractError::InvalidAmount);
+        }
+
+        if min_bet_amount < params.min_bet_amount
+            || max_bet_amount > params.max_bet_amount
+            || max_bet_amount <= min_bet_amount
+        {
+            panic_with_error!(env, ContractError::InvalidAmount);
+        }
+
+        // Transfer KALE fee plus the refundable creator bond to prevent spam
+        let bond_amount = Self::get_required_bond(env.clone(), creator.clone());
+        let token_client = token::Client::new(env, &config.kale_token);
+        token_client.transfer(&creator, &env.current_contract_address(), &(min_market_fee + bond_amount));
+
+        MarketRequest {
+            id: 0,
+            creator,
+            event_description,
+            oracle_asset,
+            target_price,
+            condition,
+            resolve_time,
+            min_bet_amount,
+            max_bet_amount,
+            creator_fee_rate,
+            platform_fee_rate: params.platform_fee_rate,
+            oracle_address: params.oracle_address.clone(),
+            fee_paid: min_market_fee,
+            bond_paid: bond_amount,
+            submitted_at: current_time,
+        }
+    }
+
+    /// Deploy, initialize and register a market whose fee and bond are already paid
+    fn deploy_market(env: &Env, request: &MarketRequest) -> Address {
+        let mut market_count: u32 = env.storage().instance().get(&DataKey::MarketCount).unwrap_or(0);
+        market_count += 1;
+
+        // Deploy new prediction market contract
+        let market_contract_id = Self::deploy_market_contract(env, market_count);
+
+        // Initialize the new market contract
+        Self::initialize_market_contract(env, &market_contract_id, request);
+
+        // Update factory state
+
+        let record = MarketRecord {
+            id: market_count,
+            contract_id: market_contract_id.clone(),
+            creator: request.creator.clone(),
+            oracle_asset: request.oracle_asset.clone(),
+            resolve_time: request.resolve_time,
+            created_at: env.ledger().timestamp(),
+            status: MarketStatus::Active,
+        };
+        Self::register_market(env, &record);
+
+        let bond = CreatorBond {
+            market_id: market_count,
+            creator: request.creator.clone(),
+            amount: request.bond_paid,
+            status: BondStatus::Held,
+            resolved_at: None,
+            slashed_pool: 0,
+            slashed_claimed: 0,
+            slashed_paid: 0,
+        };
+        env.storage().persistent().set(&DataKey::CreatorBond(market_count), &bond);
+        Self::adjust_bonds_held(env, request.bond_paid);
+
+        env.storage().instance().set(&DataKey::MarketCount, &market_count);
+
+        // Emit market created event
+        let event = EventType::MarketCreated(
+            market_count,
+            request.creator.clone(),
+            market_contract_id.clone(),
+            request.event_description.clone(),
+            request.oracle_asset.clone(),
+            request.target_price,
+            request.condition,
+            request.resolve_time,
+        );
+
+        env.events().publish((symbol_short!("mkt_new"),), event);
+
+        log!(env, "Market created: {} by {}", request.event_description, request.creator);
+
+        market_contract_id
+    }
+
+    /// Remove a request from the pending queue and delete it
+    fn take_pending_request(env: &Env, request_id: u32) -> MarketRequest {
+        let storage = env.storage().persistent();
+        let request: MarketRequest = storage
+            .get(&DataKey::MarketRequest(request_id))
+            .unwrap_or_else(|| panic_with_error!(env, ContractError::MarketNotFound));
+
+        let length: u32 = storage.get(&DataKey::PendingQueueLength).unwrap_or(0);
+        let position: u32 = storage.get(&DataKey::PendingQueuePosition(request_id)).unwrap();
+        let last = length - 1;
+
+        // Swap-remove: move the last entry into the vacated slot
+        if position != last {
+            let moved_id: u32 = storage.get(&DataKey::PendingQueue(last)).unwrap();
+            Self::set_registry_entry(env, &DataKey::PendingQueue(position), &moved_id);
+            Self::set_registry_entry(env, &DataKey::PendingQueuePosition(moved_id), &position);
+        }
+
+        storage.remove(&DataKey::PendingQueue(last));
+        storage.remove(&DataKey::PendingQueuePosition(request_id));
+        storage.remove(&DataKey::MarketRequest(request_id));
+        Self::set_registry_entry(env, &DataKey::PendingQueueLength, &last);
+
+        request
+    }
+
+    fn adjust_pending_escrow(env: &Env, delta: i128) {
+        let escrow: i128 = env.storage().instance().get(&DataKey::PendingEscrow).unwrap_or(0);
+        env.storage().instance().set(&DataKey::PendingEscrow, &(escrow + delta));
+    }
+
+    fn register_market(env: &Env, record: &MarketRecord) {
+        let storage = env.storage().persistent();
+
+        Self::set_registry_entry(env, &DataKey::Market(record.id), record);
+        Self::set_registry_entry(env, &DataKey::MarketIdByAddress(record.contract_id.clone()), &record.id);
+
+        let creator_count: u32 = storage.get(&DataKey::CreatorMarketCount(record.creator.clone())).unwrap_or(0);
+        Self::set_registry_entry(env, &DataKey::CreatorMarket(record.creator.clone(), creator_count), &record.id);
+        Self::set_registry_entry(env, &DataKey::CreatorMarketCount(record.creator.clone()), &(creator_count + 1));
+
+        let asset_count: u32 = storage.get(&DataKey::AssetMarketCount(record.oracle_asset.clone())).unwrap_or(0);
+        Self::set_registry_entry(env, &DataKey::AssetMarket(record.oracle_asset.clone(), asset_count), &record.id);
+        Self::set_registry_entry(env, &DataKey::AssetMarketCount(record.oracle_asset.clone()), &(asset_count + 1));
+
+        Self::append_to_status_index(env, &record.status, record.id);
+    }
+
+    /// Write a registry or index entry and extend its TTL, so indexes live as
+    /// long as the records they point to
+    fn set_registry_entry<V: IntoVal<Env, Val>>(env: &Env, key: &DataKey, value: &V) {
+        let storage = env.storage().persistent();
+        storage.set(key, value);
+        storage.extend_ttl(key, PERSISTENT_LIFETIME_THRESHOLD, PERSISTENT_BUMP_AMOUNT);
+    }
+
+    fn release_bond(env: &Env, market_id: u32) -> i128 {
+        let mut bond = Self::get_creator_bond(env.clone(), market_id);
+        if bond.status != BondStatus::Held {
+            panic_with_error!(env, ContractError::AlreadyClaimed);
+        }
+
+        let config: FactoryConfig = env.storage().instance().get(&DataKey::Config).unwrap();
+        let dispute_window = config.params.dispute_window;
+        let resolved_at = bond.resolved_at
+            .unwrap_or_else(|| panic_with_error!(env, ContractError::MarketClosed));
+        if env.ledger().timestamp() < resolved_at + dispute_window {
+            panic_with_error!(env, ContractError::InvalidTimestamp);
+        }
+
+        Self::return_bond(env, &mut bond);
+        Self::update_reputation(env, &bond.creator, 1, 0, 0);
+
+        bond.amount
+    }
+
+    /// Pay a held bond back to its creator
+    fn return_bond(env: &Env, bond: &mut CreatorBond) {
+        bond.status = BondStatus::Released;
+        env.storage().persistent().set(&DataKey::CreatorBond(bond.market_id), bond);
+        Self::adjust_bonds_held(env, -bond.amount);
+
+        if bond.amount > 0 {
+            let config: FactoryConfig = env.storage().instance().get(&DataKey::Config).unwrap();
+            let token_client = token::Client::new(env, &config.kale_token);
+            token_client.transfer(&env.current_contract_address(), &bond.creator, &bond.amount);
+        }
+
+        log!(env, "Creator bond released: {} KALE to {}", bond.amount, bond.creator);
+    }
+
+    /// Cancel a market the oracle never resolved so its bets are refunded. The
+    /// creator isn't at fault, so their bond comes back with no penalty.
+    fn expire_market(env: &Env, record: &mut MarketRecord) {
+        let market_client = PredictionMarketClient::new(env, &record.contract_id);
+        market_client.cancel(&env.current_contract_address());
+        Self::transition_status(env, record, MarketStatus::Cancelled);
+
+        let mut bond = Self::get_creator_bond(env.clone(), record.id);
+        if bond.status == BondStatus::Held {
+            Self::return_bond(env, &mut bond);
+        }
+    }
+
+    /// Move a market to a new status, starting the dispute window on resolution
+    fn apply_status(env: &Env, record: &mut MarketRecord, status: MarketStatus) {
+        Self::transition_status(env, record, status.clone());
+
+        if status == MarketStatus::Resolved {
+            let mut bond = Self::get_creator_bond(env.clone(), record.id);
+            bond.resolved_at = Some(env.ledger().timestamp());
+            env.storage().persistent().set(&DataKey::CreatorBond(record.id), &bond);
+        }
+    }
+
+    fn next_keeper_action(env: &Env, record: &MarketRecord) -> Option<KeeperAction> {
+        let config: FactoryConfig = env.storage().instance().get(&DataKey::Config).unwrap();
+        let current_time = env.ledger().timestamp();
+
+        match record.status {
+            MarketStatus::Active if current_time >= record.resolve_time => Some(KeeperAction::Close),
+            MarketStatus::Closed if current_time > record.resolve_time + config.params.expiry_grace_period => {
+                Some(KeeperAction::Expire)
+            }
+            MarketStatus::Closed => Some(KeeperAction::Resolve),
+            MarketStatus::Resolved => {
+                let bond: CreatorBond = env.storage().persistent().get(&DataKey::CreatorBond(record.id))?;
+                let resolved_at = bond.resolved_at?;
+                if bond.status == BondStatus::Held && current_time >= resolved_at + config.params.dispute_window {
+                    Some(KeeperAction::Finalize)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Pay the keeper bounty out of fee revenue, up to what's left of the
+    /// market's total bounty cap
+    fn pay_keeper_bounty(env: &Env, keeper: &Address, market_id: u32) -> i128 {
+        let config: FactoryConfig = env.storage().instance().get(&DataKey::Config).unwrap();
+        let paid: i128 = env.storage().persistent().get(&DataKey::KeeperBountyPaid(market_id)).unwrap_or(0);
+
+        let mut bounty = config.params.keeper_bounty;
+        if bounty > config.params.max_keeper_bounty_per_market - paid {
+            bounty = config.params.max_keeper_bounty_per_market - paid;
+        }
+
+        let available = Self::available_fees(env, &config);
+        if bounty > available {
+            bounty = available;
+        }
+
+        if bounty <= 0 {
+            return 0;
+        }
+
+        env.storage().persistent().set(&DataKey::KeeperBountyPaid(market_id), &(paid + bounty));
+
+        let token_client = token::Client::new(env, &config.kale_token);
+        token_client.transfer(&env.current_contract_address(), keeper, &bounty);
+
+        bounty
+    }
+
+    /// Fee revenue held by the factory, excluding bonds and escrow held for users
+    fn available_fees(env: &Env, config: &FactoryConfig) -> i128 {
+        let token_client = token::Client::new(env, &config.kale_token);
+        let bonds_held: i128 = env.storage().instance().get(&DataKey::BondsHeld).unwrap_or(0);
+        let pending_escrow: i128 = env.storage().instance().get(&DataKey::PendingEscrow).unwrap_or(0);
+        token_client.balance(&env.current_contract_address()) - bonds_held - pending_escrow
+    }
+
+    fn transition_status(env: &Env, record: &mut MarketRecord, status: MarketStatus) {
+        Self::remove_from_status_index(env, &record.status, record.id);
+        Self::append_to_status_index(env, &status, record.id);
+
+        record.status = status;
+        Self::set_registry_entry(env, &DataKey::Market(record.id), record);
+    }
+
+    fn slash_bond(env: &Env, record: &MarketRecord) {
+        let mut bond: CreatorBond = env.storage().persistent().get(&DataKey::CreatorBond(record.id)).unwrap();
+        if bond.status != BondStatus::Held {
+            return;
+        }
+
+        let market_client = PredictionMarketClient::new(env, &record.contract_id);
+        let (total_for, total_against) = market_client.get_totals();
+
+        bond.status = BondStatus::Slashed;
+        bond.slashed_pool = total_for + total_against;
+        env.storage().persistent().set(&DataKey::CreatorBond(record.id), &bond);
+
+        // With no bettors to compensate, the slashed bond becomes fee revenue
+        if bond.slashed_pool == 0 {
+            Self::adjust_bonds_held(env, -bond.amount);
+        }
+
+        log!(env, "Creator bond slashed: {} KALE on market {}", bond.amount, record.id);
+    }
+
+    fn adjust_bonds_held(env: &Env, delta: i128) {
+        let bonds_held: i128 = env.storage().instance().get(&DataKey::BondsHeld).unwrap_or(0);
+        env.storage().instance().set(&DataKey::BondsHeld, &(bonds_held + delta));
+    }
+
+    fn update_reputation(env: &Env, creator: &Address, clean: u32, cancelled: u32, disputed: u32) {
+        let mut reputation = Self::get_creator_reputation(env.clone(), creator.clone());
+        reputation.clean_markets += clean;
+        reputation.cancelled_markets += cancelled;
+        reputation.disputed_markets += disputed;
+
+        let credit = NEUTRAL_REPUTATION + reputation.clean_markets * CLEAN_MARKET_CREDIT;
+        let penalty = reputation.cancelled_markets * CANCELLED_MARKET_PENALTY
+            + reputation.disputed_markets * DISPUTED_MARKET_PENALTY;
+        reputation.score = if penalty >= credit {
+            0
+        } else if credit - penalty > MAX_REPUTATION {
+            MAX_REPUTATION
+        } else {
+            credit - penalty
+        };
+
+        env.storage().persistent().set(&DataKey::CreatorReputation(creator.clone()), &reputation);
+    }
+
+    fn append_to_status_index(env: &Env, status: &MarketStatus, market_id: u32) {
+        let storage = env.storage().persistent();
+        let count: u32 = storage.get(&DataKey::StatusMarketCount(status.clone())).unwrap_or(0);
+
+        Self::set_registry_entry(env, &DataKey::StatusMarket(status.clone(), count), &market_id);
+        Self::set_registry_entry(env, &DataKey::StatusMarketPosition(market_id), &count);
+        Self::set_registry_entry(env, &DataKey::StatusMarketCount(status.clone()), &(count + 1));
+    }
+
+    fn remove_from_status_index(env: &Env, status: &MarketStatus, market_id: u32) {
+        let storage = env.storage().persistent();
+        let count: u32 = storage.get(&DataKey::StatusMarketCount(status.clone())).unwrap_or(0);
+        let position: u32 = storage.get(&DataKey::StatusMarketPosition(market_id)).unwrap();
+        let last = count - 1;
+
+        // Swap-remove: move the last entry into the vacated slot
+        if position != last {
+            let moved_id: u32 = storage.get(&DataKey::StatusMarket(status.clone(), last)).unwrap();
+            Self::set_registry_entry(env, &DataKey::StatusMarket(status.clone(), position), &moved_id);
+            Self::set_registry_entry(env, &DataKey::StatusMarketPosition(moved_id), &position);
+        }
+
+        storage.remove(&DataKey::StatusMarket(status.clone(), last));
+        Self::set_registry_entry(env, &DataKey::StatusMarketCount(status.clone()), &last);
+    }
+
+    /// Deploy the prediction market code at an address salted by market id
+    fn deploy_market_contract(env: &Env, market_id: u32) -> Address {
+        let config: FactoryConfig = env.storage().instance().get(&DataKey::Config).unwrap();
+        let mut salt = [0u8; 32];
+        salt[28..].copy_from_slice(&market_id.to_be_bytes());
+        env.deployer()
+            .with_current_contract(BytesN::from_array(env, &salt))
+            .deploy(config.market_wasm_hash)
+    }
+
+    /// Initialize a deployed market with the request's terms, oracle and platform fee
+    fn initialize_market_contract(env: &Env, contract_id: &Address, request: &MarketRequest) {
+        let config: FactoryConfig = env.storage().instance().get(&DataKey::Config).unwrap();
+        let params = MarketParams {
+            event_description: request.event_description.clone(),
+            oracle_asset: request.oracle_asset.clone(),
+            target_price: request.target_price,
+            condition: request.condition,
+            resolve_time: request.resolve_time,
+            min_bet_amount: request.min_bet_amount,
+            max_bet_amount: request.max_bet_amount,
+            creator_fee_rate: request.creator_fee_rate,
+        };
+
+        let market_client = PredictionMarketClient::new(env, contract_id);
+        market_client.initialize(
+            &env.current_contract_address(),
+            &request.creator,
+            &params,
+            &config.kale_token,
+            &request.oracle_address,
+            &config.kale_integration,
+            &request.platform_fee_rate,
+        );
+
+        // Markets pay referral fees out of their platform fees, so the registry
+        // KALE integration uses must accept their reports. When one is set, it
+        // must allow this factory to add reporters.
+        let registry = KaleIntegrationClient::new(env, &config.kale_integration).get_referral_registry();
+        if let Some(registry) = registry {
+            ReferralRegistryClient::new(env, &registry).add_reporter(&env.current_contract_address(), contract_id);
+        }
     }
 }
+
+#[cfg(test)]
+mod test;
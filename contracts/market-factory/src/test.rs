@@ -0,0 +1,678 @@
+#![cfg(test)]
+
+use super::*;
+use soroban_sdk::testutils::{Address as _, Events, Ledger};
+use soroban_sdk::token::StellarAssetClient;
+use soroban_sdk::xdr::{ContractDataDurability, LedgerKey, LedgerKeyContractData, ScVal};
+use soroban_sdk::{contract, contractimpl, Bytes};
+extern crate std;
+use std::rc::Rc;
+
+const MARKET_FEE: i128 = 100;
+const BASE_BOND: i128 = 1_000;
+const DAY: u64 = 24 * 60 * 60;
+
+/// Stand-in for a deployed prediction market, registered at the address the
+/// factory deploys it to
+#[contract]
+struct MockMarket;
+
+#[contracttype]
+enum MockKey {
+    Oracle,
+    PlatformFeeRate,
+    Bets(Address),
+    TotalFor,
+    TotalAgainst,
+    Closed,
+    Resolved,
+    Cancelled,
+}
+
+#[contractimpl]
+impl MockMarket {
+    pub fn initialize(
+        env: Env,
+        _factory: Address,
+        _creator: Address,
+        _params: MarketParams,
+        _kale_token: Address,
+        reflector_oracle: Address,
+        _kale_integration: Address,
+        platform_fee_rate: u32,
+    ) {
+        env.storage().instance().set(&MockKey::Oracle, &reflector_oracle);
+        env.storage().instance().set(&MockKey::PlatformFeeRate, &platform_fee_rate);
+    }
+
+    pub fn oracle(env: Env) -> Address {
+        env.storage().instance().get(&MockKey::Oracle).unwrap()
+    }
+
+    pub fn platform_fee_rate(env: Env) -> u32 {
+        env.storage().instance().get(&MockKey::PlatformFeeRate).unwrap()
+    }
+
+    pub fn bet(env: Env, user: Address, side: bool, amount: i128) {
+        let (bet_for, bet_against) = Self::get_user_bets(env.clone(), user.clone());
+        let (total_for, total_against) = Self::get_totals(env.clone());
+        if side {
+            env.storage().instance().set(&MockKey::Bets(user), &(bet_for + amount, bet_against));
+            env.storage().instance().set(&MockKey::TotalFor, &(total_for + amount));
+        } else {
+            env.storage().instance().set(&MockKey::Bets(user), &(bet_for, bet_against + amount));
+            env.storage().instance().set(&MockKey::TotalAgainst, &(total_against + amount));
+        }
+    }
+
+    pub fn get_user_bets(env: Env, user: Address) -> (i128, i128) {
+        env.storage().instance().get(&MockKey::Bets(user)).unwrap_or((0, 0))
+    }
+
+    pub fn get_totals(env: Env) -> (i128, i128) {
+        (
+            env.storage().instance().get(&MockKey::TotalFor).unwrap_or(0),
+            env.storage().instance().get(&MockKey::TotalAgainst).unwrap_or(0),
+        )
+    }
+
+    pub fn is_resolved(env: Env) -> bool {
+        env.storage().instance().get(&MockKey::Resolved).unwrap_or(false)
+    }
+
+    pub fn close(env: Env, _factory: Address) {
+        env.storage().instance().set(&MockKey::Closed, &true);
+    }
+
+    pub fn is_closed(env: Env) -> bool {
+        env.storage().instance().get(&MockKey::Closed).unwrap_or(false)
+    }
+
+    pub fn resolve(env: Env, _resolver: Address) {
+        env.storage().instance().set(&MockKey::Resolved, &true);
+    }
+
+    pub fn cancel(env: Env, _factory: Address) {
+        env.storage().instance().set(&MockKey::Resolved, &true);
+        env.storage().instance().set(&MockKey::Cancelled, &true);
+    }
+
+    pub fn is_cancelled(env: Env) -> bool {
+        env.storage().instance().get(&MockKey::Cancelled).unwrap_or(false)
+    }
+}
+
+/// Stand-in for KALE integration, which only tells the factory which referral
+/// registry markets report to
+#[contract]
+struct MockKaleIntegration;
+
+#[contracttype]
+enum MockKaleKey {
+    Registry,
+}
+
+#[contractimpl]
+impl MockKaleIntegration {
+    pub fn set_referral_registry(env: Env, registry: Address) {
+        env.storage().instance().set(&MockKaleKey::Registry, &registry);
+    }
+
+    pub fn get_referral_registry(env: Env) -> Option<Address> {
+        env.storage().instance().get(&MockKaleKey::Registry)
+    }
+}
+
+struct Setup {
+    env: Env,
+    client: MarketFactoryContractClient<'static>,
+    kale: MockKaleIntegrationClient<'static>,
+    token: token::Client<'static>,
+    admin: Address,
+}
+
+fn default_params(env: &Env) -> FactoryParams {
+    FactoryParams {
+        oracle_address: Address::generate(env),
+        platform_fee_rate: 200,
+        max_creator_fee_rate: 500,
+        min_market_fee: MARKET_FEE,
+        min_market_duration: 60 * 60,
+        max_market_duration: 30 * DAY,
+        min_bet_amount: 1,
+        max_bet_amount: 1_000_000,
+        base_creator_bond: BASE_BOND,
+        dispute_window: DAY,
+        expiry_grace_period: 7 * DAY,
+        keeper_bounty: 10,
+        max_keeper_bounty_per_market: 30,
+    }
+}
+
+fn setup() -> Setup {
+    let env = Env::default();
+    env.mock_all_auths();
+    env.ledger().with_mut(|li| li.timestamp = 1_000_000);
+
+    let admin = Address::generate(&env);
+    let token_address = env.register_stellar_asset_contract(admin.clone());
+    let token = token::Client::new(&env, &token_address);
+
+    // Test contracts run as the empty wasm; `expect_market` swaps in the mock
+    let market_wasm_hash = env.deployer().upload_contract_wasm(Bytes::new(&env));
+
+    let kale_id = env.register_contract(None, MockKaleIntegration);
+    let kale = MockKaleIntegrationClient::new(&env, &kale_id);
+
+    let contract_id = env.register_contract(None, MarketFactoryContract);
+    let client = MarketFactoryContractClient::new(&env, &contract_id);
+    client.initialize(&admin, &token_address, &kale_id, &market_wasm_hash, &default_params(&env));
+
+    Setup { env, client, kale, token, admin }
+}
+
+fn new_creator(setup: &Setup) -> Address {
+    let creator = Address::generate(&setup.env);
+    StellarAssetClient::new(&setup.env, &setup.token.address).mint(&creator, &100_000);
+    creator
+}
+
+// Calls expected to panic go straight to the contract impl: a panic can't unwind
+// out of the SDK's `extern` entry points, so through the client it would abort
+fn call_direct<T>(setup: &Setup, f: impl FnOnce(Env) -> T) -> T {
+    setup.env.as_contract(&setup.client.address, || f(setup.env.clone()))
+}
+
+fn advance(env: &Env, seconds: u64) {
+    env.ledger().with_mut(|li| li.timestamp += seconds);
+}
+
+/// Route calls for the next market the factory deploys to `MockMarket`. The
+/// mock is registered at the market's address, then its instance entry is
+/// dropped so the factory's own deploy can create it.
+fn expect_market(setup: &Setup) -> Address {
+    let mut salt = [0u8; 32];
+    salt[28..].copy_from_slice(&(setup.client.get_market_count() + 1).to_be_bytes());
+    let contract_id = setup.env.deployer()
+        .with_address(setup.client.address.clone(), BytesN::from_array(&setup.env, &salt))
+        .deployed_address();
+
+    setup.env.register_contract(Some(&contract_id), MockMarket);
+    let instance_key = Rc::new(LedgerKey::ContractData(LedgerKeyContractData {
+        contract: contract_id.clone().try_into().unwrap(),
+        key: ScVal::LedgerKeyContractInstance,
+        durability: ContractDataDurability::Persistent,
+    }));
+    let host = setup.env.host();
+    host.with_mut_storage(|storage| storage.del(&instance_key, &host.budget_cloned())).unwrap();
+
+    contract_id
+}
+
+fn create_market(setup: &Setup, creator: &Address, asset: &str) -> Address {
+    expect_market(setup);
+    setup.client.create_market(
+        creator,
+        &String::from_str(&setup.env, "Price above target"),
+        &String::from_str(&setup.env, asset),
+        &100,
+        &0,
+        &(setup.env.ledger().timestamp() + DAY),
+        &10,
+        &1_000,
+        &100,
+    )
+}
+
+fn submit_market(setup: &Setup, creator: &Address) -> u32 {
+    setup.client.submit_market(
+        creator,
+        &String::from_str(&setup.env, "Price above target"),
+        &String::from_str(&setup.env, "KALE"),
+        &100,
+        &0,
+        &(setup.env.ledger().timestamp() + DAY),
+        &10,
+        &1_000,
+        &100,
+    )
+}
+
+#[test]
+fn test_create_market_registers_record_and_collects_fee_and_bond() {
+    let setup = setup();
+    let creator = new_creator(&setup);
+
+    let contract_id = create_market(&setup, &creator, "KALE");
+
+    assert_eq!(setup.client.get_market_count(), 1);
+    let record = setup.client.get_market(&1);
+    assert_eq!(record.id, 1);
+    assert_eq!(record.contract_id, contract_id);
+    assert_eq!(record.creator, creator);
+    assert_eq!(record.oracle_asset, String::from_str(&setup.env, "KALE"));
+    assert_eq!(record.status, MarketStatus::Active);
+
+    let bond = setup.client.get_creator_bond(&1);
+    assert_eq!(bond.amount, BASE_BOND);
+    assert_eq!(bond.status, BondStatus::Held);
+    assert_eq!(setup.token.balance(&creator), 100_000 - MARKET_FEE - BASE_BOND);
+}
+
+#[test]
+fn test_list_markets_pages_through_all_markets() {
+    let setup = setup();
+    let creator = new_creator(&setup);
+    for _ in 0..5 {
+        create_market(&setup, &creator, "KALE");
+    }
+
+    assert_eq!(setup.client.count_markets(&MarketFilter::All), 5);
+
+    let first_page = setup.client.list_markets(&0, &2, &MarketFilter::All);
+    assert_eq!(first_page.len(), 2);
+    assert_eq!(first_page.get(0).unwrap().id, 1);
+    assert_eq!(first_page.get(1).unwrap().id, 2);
+
+    let last_page = setup.client.list_markets(&4, &2, &MarketFilter::All);
+    assert_eq!(last_page.len(), 1);
+    assert_eq!(last_page.get(0).unwrap().id, 5);
+
+    assert_eq!(setup.client.list_markets(&5, &2, &MarketFilter::All).len(), 0);
+}
+
+#[test]
+fn test_list_markets_filters_by_creator_and_asset() {
+    let setup = setup();
+    let alice = new_creator(&setup);
+    let bob = new_creator(&setup);
+
+    create_market(&setup, &alice, "KALE");
+    create_market(&setup, &bob, "BTC");
+    create_market(&setup, &alice, "BTC");
+
+    let alice_markets = setup.client.list_markets(&0, &10, &MarketFilter::Creator(alice.clone()));
+    assert_eq!(alice_markets.len(), 2);
+    assert_eq!(alice_markets.get(0).unwrap().id, 1);
+    assert_eq!(alice_markets.get(1).unwrap().id, 3);
+    assert_eq!(setup.client.count_markets(&MarketFilter::Creator(bob)), 1);
+
+    let btc = MarketFilter::Asset(String::from_str(&setup.env, "BTC"));
+    let btc_markets = setup.client.list_markets(&0, &10, &btc);
+    assert_eq!(btc_markets.len(), 2);
+    assert_eq!(btc_markets.get(0).unwrap().id, 2);
+    assert_eq!(btc_markets.get(1).unwrap().id, 3);
+
+    let xlm = MarketFilter::Asset(String::from_str(&setup.env, "XLM"));
+    assert_eq!(setup.client.count_markets(&xlm), 0);
+    assert_eq!(setup.client.list_markets(&0, &10, &xlm).len(), 0);
+}
+
+#[test]
+fn test_status_filter_follows_status_changes() {
+    let setup = setup();
+    let creator = new_creator(&setup);
+    for _ in 0..3 {
+        create_market(&setup, &creator, "KALE");
+    }
+
+    setup.client.set_market_status(&setup.admin, &1, &MarketStatus::Closed);
+
+    assert_eq!(setup.client.count_markets(&MarketFilter::Status(MarketStatus::Active)), 2);
+    let closed = setup.client.list_markets(&0, &10, &MarketFilter::Status(MarketStatus::Closed));
+    assert_eq!(closed.len(), 1);
+    assert_eq!(closed.get(0).unwrap().id, 1);
+    assert_eq!(closed.get(0).unwrap().status, MarketStatus::Closed);
+
+    // Swap-remove keeps the remaining active markets listed
+    let active = setup.client.list_markets(&0, &10, &MarketFilter::Status(MarketStatus::Active));
+    assert_eq!(active.len(), 2);
+    assert!(active.iter().any(|record| record.id == 2));
+    assert!(active.iter().any(|record| record.id == 3));
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1)")]
+fn test_admin_cannot_cancel_a_market_outside_a_dispute() {
+    let setup = setup();
+    let creator = new_creator(&setup);
+    create_market(&setup, &creator, "KALE");
+
+    let admin = setup.admin.clone();
+    call_direct(&setup, |env| MarketFactoryContract::set_market_status(env, admin, 1, MarketStatus::Cancelled));
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #4)")]
+fn test_resolved_markets_cannot_be_reopened() {
+    let setup = setup();
+    let creator = new_creator(&setup);
+    create_market(&setup, &creator, "KALE");
+    setup.client.set_market_status(&setup.admin, &1, &MarketStatus::Resolved);
+
+    let admin = setup.admin.clone();
+    call_direct(&setup, |env| MarketFactoryContract::set_market_status(env, admin, 1, MarketStatus::Active));
+}
+
+#[test]
+fn test_each_market_gets_its_own_address() {
+    let setup = setup();
+    let creator = new_creator(&setup);
+
+    let first = create_market(&setup, &creator, "KALE");
+    let second = create_market(&setup, &creator, "KALE");
+
+    assert_ne!(first, second);
+    assert_eq!(setup.client.get_market_id(&first), 1);
+    assert_eq!(setup.client.get_market_id(&second), 2);
+
+    // The deprecated listing still returns addresses, oldest first
+    let addresses = setup.client.get_markets();
+    assert_eq!(addresses.len(), 2);
+    assert_eq!(addresses.get(0).unwrap(), first);
+    assert_eq!(addresses.get(1).unwrap(), second);
+}
+
+#[test]
+fn test_market_requests_carry_platform_fee_and_oracle() {
+    let setup = setup();
+    setup.client.set_moderation_enabled(&setup.admin, &true);
+    let creator = new_creator(&setup);
+    let request_id = submit_market(&setup, &creator);
+
+    let params = setup.client.get_config().params;
+    let request = setup.client.get_market_request(&request_id);
+    assert_eq!(request.platform_fee_rate, params.platform_fee_rate);
+    assert_eq!(request.oracle_address, params.oracle_address);
+}
+
+#[test]
+fn test_markets_are_initialized_with_the_current_config() {
+    let setup = setup();
+    let creator = new_creator(&setup);
+    let first = MockMarketClient::new(&setup.env, &create_market(&setup, &creator, "KALE"));
+
+    let old_params = setup.client.get_config().params;
+    assert_eq!(first.platform_fee_rate(), old_params.platform_fee_rate);
+    assert_eq!(first.oracle(), old_params.oracle_address);
+
+    let new_params = FactoryParams {
+        oracle_address: Address::generate(&setup.env),
+        platform_fee_rate: 300,
+        ..old_params.clone()
+    };
+    setup.client.update_config(&setup.admin, &new_params);
+
+    let (contract_id, topics, data) = setup.env.events().all().last().unwrap();
+    assert_eq!(contract_id, setup.client.address);
+    assert_eq!(topics, (symbol_short!("cfg_upd"),).into_val(&setup.env));
+    let event: EventType = data.into_val(&setup.env);
+    assert!(matches!(event, EventType::ConfigurationUpdated(old, new) if old == old_params && new == new_params));
+
+    // Later markets pick up the new fee and oracle; earlier ones keep theirs
+    let second = MockMarketClient::new(&setup.env, &create_market(&setup, &creator, "KALE"));
+    assert_eq!(second.platform_fee_rate(), 300);
+    assert_eq!(second.oracle(), new_params.oracle_address);
+    assert_eq!(first.platform_fee_rate(), old_params.platform_fee_rate);
+}
+
+#[test]
+fn test_approved_requests_deploy_with_the_escrowed_fee_and_bond() {
+    let setup = setup();
+    setup.client.set_moderation_enabled(&setup.admin, &true);
+    let approver = Address::generate(&setup.env);
+    setup.client.set_approver(&setup.admin, &approver, &true);
+    let creator = new_creator(&setup);
+
+    let request_id = submit_market(&setup, &creator);
+    assert_eq!(setup.client.get_market_count(), 0);
+    assert_eq!(setup.token.balance(&creator), 100_000 - MARKET_FEE - BASE_BOND);
+
+    let contract_id = expect_market(&setup);
+    assert_eq!(setup.client.approve_market(&approver, &request_id), contract_id);
+    assert_eq!(setup.client.get_market(&1).creator, creator);
+    assert_eq!(setup.client.get_creator_bond(&1).amount, BASE_BOND);
+    assert_eq!(setup.client.list_pending_markets(&0, &10).len(), 0);
+
+    // The fee is now revenue; the bond stays held for the creator
+    let recipient = Address::generate(&setup.env);
+    setup.client.withdraw_fees(&setup.admin, &recipient);
+    assert_eq!(setup.token.balance(&recipient), MARKET_FEE);
+}
+
+#[test]
+fn test_rejected_requests_refund_the_fee_and_bond() {
+    let setup = setup();
+    setup.client.set_moderation_enabled(&setup.admin, &true);
+    let creator = new_creator(&setup);
+
+    let request_id = submit_market(&setup, &creator);
+    setup.client.reject_market(&setup.admin, &request_id);
+
+    assert_eq!(setup.token.balance(&creator), 100_000);
+    assert_eq!(setup.token.balance(&setup.client.address), 0);
+    assert_eq!(setup.client.get_market_count(), 0);
+    assert_eq!(setup.client.list_pending_markets(&0, &10).len(), 0);
+}
+
+#[test]
+fn test_pending_queue_pages_and_stays_packed() {
+    let setup = setup();
+    setup.client.set_moderation_enabled(&setup.admin, &true);
+    let creator = new_creator(&setup);
+    for _ in 0..4 {
+        submit_market(&setup, &creator);
+    }
+
+    let first_page = setup.client.list_pending_markets(&0, &3);
+    assert_eq!(first_page.len(), 3);
+    assert_eq!(first_page.get(0).unwrap().id, 1);
+    assert_eq!(setup.client.list_pending_markets(&3, &3).len(), 1);
+    assert_eq!(setup.client.list_pending_markets(&4, &3).len(), 0);
+
+    // Swap-remove moves the last request into the vacated slot
+    setup.client.reject_market(&setup.admin, &2);
+    let pending = setup.client.list_pending_markets(&0, &10);
+    assert_eq!(pending.len(), 3);
+    assert_eq!(pending.get(1).unwrap().id, 4);
+}
+
+#[test]
+fn test_trusted_creators_skip_the_queue() {
+    let setup = setup();
+    setup.client.set_moderation_enabled(&setup.admin, &true);
+    let creator = new_creator(&setup);
+
+    assert!(setup.client.requires_moderation(&creator));
+    setup.client.set_trusted_creator(&setup.admin, &creator, &true);
+    assert!(!setup.client.requires_moderation(&creator));
+
+    create_market(&setup, &creator, "KALE");
+    assert_eq!(setup.client.get_market_count(), 1);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1)")]
+fn test_untrusted_creators_cannot_skip_the_queue() {
+    let setup = setup();
+    setup.client.set_moderation_enabled(&setup.admin, &true);
+    let creator = new_creator(&setup);
+
+    let description = String::from_str(&setup.env, "Price above target");
+    let asset = String::from_str(&setup.env, "KALE");
+    let resolve_time = setup.env.ledger().timestamp() + DAY;
+    call_direct(&setup, |env| {
+        MarketFactoryContract::create_market(env, creator, description, asset, 100, 0, resolve_time, 10, 1_000, 100)
+    });
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #1)")]
+fn test_submissions_are_rejected_when_moderation_is_off() {
+    let setup = setup();
+    let creator = new_creator(&setup);
+
+    let description = String::from_str(&setup.env, "Price above target");
+    let asset = String::from_str(&setup.env, "KALE");
+    let resolve_time = setup.env.ledger().timestamp() + DAY;
+    call_direct(&setup, |env| {
+        MarketFactoryContract::submit_market(env, creator, description, asset, 100, 0, resolve_time, 10, 1_000, 100)
+    });
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #6)")]
+fn test_platform_fee_rate_is_capped() {
+    let setup = setup();
+    let params = FactoryParams { platform_fee_rate: 1_001, max_creator_fee_rate: 0, ..default_params(&setup.env) };
+    let admin = setup.admin.clone();
+    call_direct(&setup, |env| MarketFactoryContract::update_config(env, admin, params));
+}
+
+#[test]
+fn test_keeper_tasks_list_the_next_step_per_market() {
+    let setup = setup();
+    let creator = new_creator(&setup);
+    for _ in 0..3 {
+        create_market(&setup, &creator, "KALE");
+    }
+    let keeper = Address::generate(&setup.env);
+
+    // Nothing is due before the markets' resolve time
+    assert_eq!(setup.client.get_keeper_tasks(&MarketStatus::Active, &0, &10).len(), 0);
+
+    advance(&setup.env, DAY);
+    let tasks = setup.client.get_keeper_tasks(&MarketStatus::Active, &0, &10);
+    assert_eq!(tasks.len(), 3);
+    assert!(tasks.iter().all(|task| task.action == KeeperAction::Close));
+    assert_eq!(setup.client.get_keeper_tasks(&MarketStatus::Active, &1, &1).len(), 1);
+
+    setup.client.run_keeper_task(&keeper, &2);
+    assert_eq!(setup.client.get_keeper_tasks(&MarketStatus::Active, &0, &10).len(), 2);
+    let closed = setup.client.get_keeper_tasks(&MarketStatus::Closed, &0, &10);
+    assert_eq!(closed.len(), 1);
+    assert_eq!(closed.get(0).unwrap(), KeeperTask { market_id: 2, action: KeeperAction::Resolve });
+}
+
+#[test]
+fn test_keepers_are_paid_for_every_step_up_to_the_market_cap() {
+    let setup = setup();
+    let params = FactoryParams { max_keeper_bounty_per_market: 25, ..setup.client.get_config().params };
+    setup.client.update_config(&setup.admin, &params);
+
+    let creator = new_creator(&setup);
+    let contract_id = create_market(&setup, &creator, "KALE");
+    let market = MockMarketClient::new(&setup.env, &contract_id);
+    let closer = Address::generate(&setup.env);
+    let resolver = Address::generate(&setup.env);
+    let finalizer = Address::generate(&setup.env);
+
+    advance(&setup.env, DAY);
+    assert_eq!(setup.client.run_keeper_task(&closer, &1), KeeperAction::Close);
+    assert!(market.is_closed());
+    assert_eq!(setup.token.balance(&closer), 10);
+
+    assert_eq!(setup.client.run_keeper_task(&resolver, &1), KeeperAction::Resolve);
+    assert!(market.is_resolved());
+    assert_eq!(setup.token.balance(&resolver), 10);
+
+    // Only 5 of the 25 cap is left for the last step
+    advance(&setup.env, DAY);
+    assert_eq!(setup.client.run_keeper_task(&finalizer, &1), KeeperAction::Finalize);
+    assert_eq!(setup.token.balance(&finalizer), 5);
+    assert_eq!(setup.client.get_keeper_bounty_paid(&1), 25);
+    assert_eq!(setup.client.get_creator_bond(&1).status, BondStatus::Released);
+}
+
+#[test]
+fn test_expired_market_is_cancelled_without_penalty() {
+    let setup = setup();
+    let creator = new_creator(&setup);
+    let contract_id = create_market(&setup, &creator, "KALE");
+    let market = MockMarketClient::new(&setup.env, &contract_id);
+    let keeper = Address::generate(&setup.env);
+
+    advance(&setup.env, DAY);
+    assert_eq!(setup.client.run_keeper_task(&keeper, &1), KeeperAction::Close);
+    assert_eq!(setup.token.balance(&keeper), 10);
+
+    advance(&setup.env, 7 * DAY + 1);
+    assert_eq!(setup.client.run_keeper_task(&keeper, &1), KeeperAction::Expire);
+    assert_eq!(setup.token.balance(&keeper), 20);
+    assert_eq!(setup.client.get_keeper_bounty_paid(&1), 20);
+
+    // Bettors are refunded by the market; the creator keeps their bond and standing
+    assert!(market.is_cancelled());
+    assert_eq!(setup.client.get_market(&1).status, MarketStatus::Cancelled);
+    assert_eq!(setup.client.get_creator_bond(&1).status, BondStatus::Released);
+    assert_eq!(setup.token.balance(&creator), 100_000 - MARKET_FEE);
+    assert_eq!(setup.client.get_creator_reputation(&creator).cancelled_markets, 0);
+}
+
+#[test]
+fn test_upheld_dispute_slashes_bond_to_bettors_including_dust() {
+    let setup = setup();
+    let creator = new_creator(&setup);
+    let contract_id = create_market(&setup, &creator, "KALE");
+    let market = MockMarketClient::new(&setup.env, &contract_id);
+
+    let bettors = [Address::generate(&setup.env), Address::generate(&setup.env), Address::generate(&setup.env)];
+    for bettor in bettors.iter() {
+        market.bet(bettor, &true, &10);
+    }
+
+    setup.client.set_market_status(&setup.admin, &1, &MarketStatus::Resolved);
+    advance(&setup.env, DAY - 1);
+    setup.client.uphold_dispute(&setup.admin, &1);
+    assert_eq!(setup.client.get_creator_bond(&1).status, BondStatus::Slashed);
+    assert_eq!(setup.client.get_creator_reputation(&creator).disputed_markets, 1);
+
+    // 1_000 split three ways: the last claimant takes the remainder
+    assert_eq!(setup.client.claim_slashed_bond(&bettors[0], &1), 333);
+    assert_eq!(setup.client.claim_slashed_bond(&bettors[1], &1), 333);
+    assert_eq!(setup.client.claim_slashed_bond(&bettors[2], &1), 334);
+    assert_eq!(setup.token.balance(&setup.client.address), MARKET_FEE);
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #11)")]
+fn test_dispute_rejected_after_window() {
+    let setup = setup();
+    let creator = new_creator(&setup);
+    create_market(&setup, &creator, "KALE");
+
+    setup.client.set_market_status(&setup.admin, &1, &MarketStatus::Resolved);
+    advance(&setup.env, DAY);
+
+    let admin = setup.admin.clone();
+    call_direct(&setup, |env| MarketFactoryContract::uphold_dispute(env, admin, 1));
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #3)")]
+fn test_dispute_rejected_before_resolution() {
+    let setup = setup();
+    let creator = new_creator(&setup);
+    create_market(&setup, &creator, "KALE");
+
+    let admin = setup.admin.clone();
+    call_direct(&setup, |env| MarketFactoryContract::uphold_dispute(env, admin, 1));
+}
+
+#[test]
+fn test_markets_are_added_as_referral_reporters() {
+    use referral_registry::{ReferralRegistryContract, ReferralRegistryContractClient};
+
+    let setup = setup();
+    let registry_id = setup.env.register_contract(None, ReferralRegistryContract);
+    let registry = ReferralRegistryContractClient::new(&setup.env, &registry_id);
+    registry.initialize(&setup.admin, &2_000u32);
+    registry.set_registrar(&setup.admin, &setup.client.address, &true);
+    setup.kale.set_referral_registry(&registry_id);
+
+    let creator = new_creator(&setup);
+    let market = create_market(&setup, &creator, "BTC");
+
+    assert!(registry.is_reporter(&market));
+    assert!(!registry.is_reporter(&setup.client.address));
+}
@@ -1,20 +1,27 @@
 #![no_std]
+#![allow(clippy::too_many_arguments)]
 
 use soroban_sdk::{
     contract, contractimpl, contracttype, token, Address, Env, String, Vec, Map,
-    panic_with_error, log, symbol_short, BytesN,
+    panic_with_error, log, symbol_short, BytesN, IntoVal, Symbol, vec,
 };
+use soroban_sdk::auth::{ContractContext, InvokerContractAuthEntry, SubContractInvocation};
 use shared_types::{
-    Market, Bet, MarketStatus, MarketOutcome, ContractError, Config, 
-    MarketResolutionData, ContractCallResult, InteropError, StakingPosition
+    Market, MarketStatus, ContractError, Config, MarketParams,
+    MarketResolutionData, StakingPosition, StakingTier
 };
-use shared_types::clients::{ReflectorOracleClient, KaleIntegrationClient};
-use shared_types::validation;
+use shared_types::clients::{ReflectorOracleClient, KaleIntegrationClient, ReferralRegistryClient};
+use shared_types::{tiers, validation};
 
 const DAY_IN_LEDGERS: u32 = 17280;
 const INSTANCE_BUMP_AMOUNT: u32 = 7 * DAY_IN_LEDGERS;
 const INSTANCE_LIFETIME_THRESHOLD: u32 = INSTANCE_BUMP_AMOUNT - DAY_IN_LEDGERS;
 
+const BASIS_POINTS: i128 = 10_000;
+const MAX_FEE_RATE: u32 = 1_000; // 10% in basis points, for each of the creator and platform fees
+// Stake-backed bets are settled in one pass at resolution, so their bettors are capped
+const MAX_STAKE_BETTORS: u32 = 50;
+
 #[contracttype]
 pub enum DataKey {
     Config,
@@ -26,26 +33,49 @@ pub enum DataKey {
     Resolved,
     Outcome,
     ClaimedWinnings(Map<Address, bool>),
+    KaleIntegration,
+    CreatorFees,
+    PlatformFees,
+    YieldRecipient,
+    StakedCollateral,
+    UnbondingCollateral,
+    YieldEarned,
+    WinnersYield,
+    WinnersYieldPaid,
+    PoolPaidOut,
+    MarketParams,
+    Cancelled,
+    StakeBets,         // Map<Address, (i128, i128)> of stake-backed (YES, NO) amounts
+    UnfundedStakeBets, // Stake-backed amounts in the pools that no KALE here backs
+}
+
+/// Who receives the staking yield on a market's collateral
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum YieldRecipient {
+    Creator,
+    Platform,
+    Winners,
+}
+
+/// Where a market's collateral is held
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CollateralStatus {
+    pub staked: i128,       // Staked in KALE integration
+    pub unbonding: i128,    // Unstaked, waiting out the unbonding period
+    pub liquid: i128,       // KALE held by this contract
+    pub yield_earned: i128, // Staking rewards collected so far
+    pub liabilities: i128,  // Unpaid bets, fees and winners' yield
+    pub solvent: bool,
 }
 
 #[contracttype]
 pub enum EventType {
-    BetPlaced {
-        bettor: Address,
-        side: bool,
-        amount: i128,
-        total_for: i128,
-        total_against: i128,
-    },
-    MarketResolved {
-        outcome: bool,
-        final_price: i128,
-        total_pool: i128,
-    },
-    WinningsClaimed {
-        winner: Address,
-        amount: i128,
-    },
+    BetPlaced(Address, bool, i128, i128, i128), // bettor, side, amount, total_for, total_against
+    MarketResolved(bool, i128, i128),           // outcome, final_price, total_pool
+    WinningsClaimed(Address, i128),             // winner, amount
+    ReferralNotRecorded(Address, i128),         // user, platform fee (0 for a first bet)
 }
 
 #[contract]
@@ -53,31 +83,44 @@ pub struct PredictionMarketContract;
 
 #[contractimpl]
 impl PredictionMarketContract {
-    /// Initialize the prediction market contract
+    /// Initialize the prediction market contract. The factory sets the platform
+    /// fee rate; it and the creator's fee rate are each capped at 10%.
     pub fn initialize(
         env: Env,
         factory: Address,
         creator: Address,
-        event_description: String,
-        oracle_asset: String,
-        target_price: i128,
-        condition: u32,
-        resolve_time: u64,
-        min_bet_amount: i128,
-        max_bet_amount: i128,
-        creator_fee_rate: u32,
+        params: MarketParams,
         kale_token: Address,
         reflector_oracle: Address,
+        kale_integration: Address,
+        platform_fee_rate: u32,
     ) {
         if env.storage().instance().has(&DataKey::Config) {
             panic_with_error!(&env, ContractError::NotAuthorized);
         }
 
+        if let Err(error) = validation::validate_fee_rate(params.creator_fee_rate, MAX_FEE_RATE)
+            .and(validation::validate_fee_rate(platform_fee_rate, MAX_FEE_RATE))
+        {
+            panic_with_error!(&env, error);
+        }
+
+        env.storage().instance().set(&DataKey::MarketParams, &params);
+
+        let MarketParams {
+            event_description,
+            resolve_time,
+            min_bet_amount,
+            max_bet_amount,
+            creator_fee_rate,
+            ..
+        } = params;
+
         let config = Config {
             admin: factory,
             kale_token,
-            oracle_address: reflector_oracle,
-            platform_fee_rate: creator_fee_rate,
+            oracle_address: reflector_oracle.clone(),
+            platform_fee_rate,
             min_stake_amount: min_bet_amount,
             reward_rate_per_second: 0, // Not used in prediction markets
             max_market_duration: 30 * 24 * 60 * 60,
@@ -99,7 +142,7 @@ impl PredictionMarketContract {
             min_bet_amount,
             max_bet_amount,
             creator_fee_rate,
-            platform_fee_rate: creator_fee_rate,
+            platform_fee_rate,
         };
 
         env.storage().instance().set(&DataKey::Config, &config);
@@ -108,6 +151,9 @@ impl PredictionMarketContract {
         env.storage().instance().set(&DataKey::TotalAgainst, &0i128);
         env.storage().instance().set(&DataKey::Resolved, &false);
         env.storage().instance().set(&DataKey::Outcome, &false);
+        env.storage().instance().set(&DataKey::KaleIntegration, &kale_integration);
+        env.storage().instance().set(&DataKey::CreatorFees, &0i128);
+        env.storage().instance().set(&DataKey::PlatformFees, &0i128);
 
         env.storage()
             .instance()
@@ -122,105 +168,68 @@ impl PredictionMarketContract {
         bettor: Address,
         side: bool, // true for YES, false for NO
         amount: i128,
-    ) -> ContractCallResult<i128> {
+    ) -> Result<i128, ContractError> {
         bettor.require_auth();
-
-        // Enhanced validation
-        validation::validate_address(&env, &bettor)
-            .map_err(|e| panic_with_error!(&env, e))?;
-
-        let config: Config = env.storage().instance().get(&DataKey::Config).unwrap();
-        let mut market_info: Market = env.storage().instance().get(&DataKey::MarketInfo).unwrap();
-        let resolved: bool = env.storage().instance().get(&DataKey::Resolved).unwrap_or(false);
-
-        // Validate market state
-        if resolved {
-            return ContractCallResult {
-                success: false,
-                data: None,
-                error: Some("Market already resolved".to_string()),
-            };
-        }
-
-        if env.ledger().timestamp() >= market_info.end_time {
-            return ContractCallResult {
-                success: false,
-                data: None,
-                error: Some("Market closed for betting".to_string()),
-            };
-        }
-
-        // Enhanced bet amount validation
-        validation::validate_amount(amount, market_info.min_bet_amount, Some(market_info.max_bet_amount))
-            .map_err(|e| panic_with_error!(&env, e))?;
+        Self::validate_bet(&env, &bettor, amount)?;
 
         // Check user balance before proceeding
+        let config: Config = env.storage().instance().get(&DataKey::Config).unwrap();
         let token_client = token::Client::new(&env, &config.kale_token);
         let user_balance = token_client.balance(&bettor);
         
         if user_balance < amount {
-            return ContractCallResult {
-                success: false,
-                data: None,
-                error: Some("Insufficient balance".to_string()),
-            };
+            return Err(ContractError::InsufficientBalance);
         }
 
         // Transfer KALE tokens from bettor to contract
         token_client.transfer(&bettor, &env.current_contract_address(), &amount);
 
-        // Update bet tracking
-        if side {
-            // YES bet
-            let mut bets_for: Map<Address, i128> = env.storage().persistent()
-                .get(&DataKey::BetsFor(Map::new(&env)))
-                .unwrap_or_else(|| Map::new(&env));
-            
-            let current_bet = bets_for.get(bettor.clone()).unwrap_or(0);
-            bets_for.set(bettor.clone(), current_bet + amount);
-            env.storage().persistent().set(&DataKey::BetsFor(Map::new(&env)), &bets_for);
+        Self::record_bet(&env, &bettor, side, amount);
 
-            let mut total_for: i128 = env.storage().instance().get(&DataKey::TotalFor).unwrap_or(0);
-            total_for += amount;
-            env.storage().instance().set(&DataKey::TotalFor, &total_for);
-            market_info.total_pool_a = total_for;
-        } else {
-            // NO bet
-            let mut bets_against: Map<Address, i128> = env.storage().persistent()
-                .get(&DataKey::BetsAgainst(Map::new(&env)))
-                .unwrap_or_else(|| Map::new(&env));
-            
-            let current_bet = bets_against.get(bettor.clone()).unwrap_or(0);
-            bets_against.set(bettor.clone(), current_bet + amount);
-            env.storage().persistent().set(&DataKey::BetsAgainst(Map::new(&env)), &bets_against);
+        if Self::get_yield_recipient(env.clone()).is_some() {
+            Self::rebalance_collateral(&env);
+        }
+        Self::require_solvent(&env);
 
-            let mut total_against: i128 = env.storage().instance().get(&DataKey::TotalAgainst).unwrap_or(0);
-            total_against += amount;
-            env.storage().instance().set(&DataKey::TotalAgainst, &total_against);
-            market_info.total_pool_b = total_against;
+        log!(&env, "Bet placed: {} KALE on {} by {}", amount, if side { "YES" } else { "NO" }, bettor);
+
+        Ok(amount)
+    }
+
+    /// Bet with staked KALE as collateral instead of transferring it. KALE
+    /// integration locks the stake, which keeps earning, until the market
+    /// settles: a loss moves the collateral here, a win releases it and only
+    /// the profit is paid out.
+    pub fn bet_with_stake(
+        env: Env,
+        bettor: Address,
+        side: bool, // true for YES, false for NO
+        amount: i128,
+    ) -> Result<i128, ContractError> {
+        bettor.require_auth();
+        Self::validate_bet(&env, &bettor, amount)?;
+
+        let mut stake_bets = Self::get_stake_bets(&env);
+        let (backed_for, backed_against) = stake_bets.get(bettor.clone()).unwrap_or((0, 0));
+        if backed_for == 0 && backed_against == 0 && stake_bets.len() >= MAX_STAKE_BETTORS {
+            return Err(ContractError::InvalidAmount);
         }
 
-        // Update market info
-        env.storage().instance().set(&DataKey::MarketInfo, &market_info);
+        let kale_integration: Address = env.storage().instance().get(&DataKey::KaleIntegration).unwrap();
+        KaleIntegrationClient::new(&env, &kale_integration)
+            .lock_market_collateral(&env.current_contract_address(), &bettor, &amount);
 
-        // Emit bet placed event
-        let event = EventType::BetPlaced {
-            bettor: bettor.clone(),
-            side,
-            amount,
-            total_for: market_info.total_pool_a,
-            total_against: market_info.total_pool_b,
-        };
+        let backed = if side { (backed_for + amount, backed_against) } else { (backed_for, backed_against + amount) };
+        stake_bets.set(bettor.clone(), backed);
+        env.storage().persistent().set(&DataKey::StakeBets, &stake_bets);
+        Self::adjust_unfunded_stake_bets(&env, amount);
 
-        env.events().publish((symbol_short!("bet_placed"), event));
+        Self::record_bet(&env, &bettor, side, amount);
+        Self::require_solvent(&env);
 
-        log!(&env, "Bet placed: {} KALE on {} by {}", amount, if side { "YES" } else { "NO" }, bettor);
+        log!(&env, "Bet placed: {} staked KALE on {} by {}", amount, if side { "YES" } else { "NO" }, bettor);
 
-        ContractCallResult {
-            success: true,
-            data: Some(amount),
-            error: None,
-        }
+        Ok(amount)
     }
 
     /// Resolve the market using Reflector oracle
@@ -242,10 +251,11 @@ impl PredictionMarketContract {
         }
 
         // Call Reflector oracle to get current price
-        let final_price = Self::get_oracle_price(&env, &config.oracle_address, &market_info.event_name);
+        let params: MarketParams = env.storage().instance().get(&DataKey::MarketParams).unwrap();
+        let final_price = Self::get_oracle_price(&env, &config.oracle_address, &params.oracle_asset);
 
         // Determine outcome based on condition
-        let outcome = Self::determine_outcome(final_price, market_info.total_pool_a, market_info.total_pool_b);
+        let outcome = Self::determine_outcome(final_price, &params);
 
         // Mark market as resolved
         resolved = true;
@@ -256,19 +266,91 @@ impl PredictionMarketContract {
         market_info.status = MarketStatus::Resolved;
         env.storage().instance().set(&DataKey::MarketInfo, &market_info);
 
+        // Collect lost stake-backed bets and release the rest
+        Self::settle_stake_bets(&env, Some(outcome));
+
+        // Start returning staked collateral; if KALE integration refuses, anyone
+        // can retry with `withdraw_collateral`
+        if Self::get_staked_collateral(&env) > 0 {
+            Self::unstake_collateral(&env);
+        }
+        Self::release_unclaimable_yield(&env);
+
         // Emit market resolved event
-        let event = EventType::MarketResolved {
+        let event = EventType::MarketResolved(
             outcome,
             final_price,
-            total_pool: market_info.total_pool_a + market_info.total_pool_b,
-        };
+            market_info.total_pool_a + market_info.total_pool_b,
+        );
 
-        env.events().publish((symbol_short!("market_resolved"), event));
+        env.events().publish((symbol_short!("resolved"),), event);
 
         log!(&env, "Market resolved: outcome={}, final_price={}", outcome, final_price);
     }
 
-    /// Claim winnings for a user
+    /// Mark the market closed once betting has ended (factory only)
+    pub fn close(env: Env, factory: Address) {
+        factory.require_auth();
+
+        let config: Config = env.storage().instance().get(&DataKey::Config).unwrap();
+        if factory != config.admin {
+            panic_with_error!(&env, ContractError::NotAuthorized);
+        }
+
+        let mut market_info: Market = env.storage().instance().get(&DataKey::MarketInfo).unwrap();
+        if env.ledger().timestamp() < market_info.end_time {
+            panic_with_error!(&env, ContractError::InvalidTimestamp);
+        }
+        if market_info.status != MarketStatus::Active {
+            panic_with_error!(&env, ContractError::MarketClosed);
+        }
+
+        market_info.status = MarketStatus::Closed;
+        env.storage().instance().set(&DataKey::MarketInfo, &market_info);
+
+        log!(&env, "Market closed by {}", factory);
+    }
+
+    /// Cancel an unresolved market so every bet is refunded in full through
+    /// `claim_winnings` (factory only)
+    pub fn cancel(env: Env, factory: Address) {
+        factory.require_auth();
+
+        let config: Config = env.storage().instance().get(&DataKey::Config).unwrap();
+        if factory != config.admin {
+            panic_with_error!(&env, ContractError::NotAuthorized);
+        }
+
+        if Self::is_resolved(env.clone()) {
+            panic_with_error!(&env, ContractError::MarketAlreadyResolved);
+        }
+
+        // Settled without an outcome, so betting and resolution are closed
+        env.storage().instance().set(&DataKey::Resolved, &true);
+        env.storage().instance().set(&DataKey::Cancelled, &true);
+
+        let mut market_info: Market = env.storage().instance().get(&DataKey::MarketInfo).unwrap();
+        market_info.status = MarketStatus::Cancelled;
+        env.storage().instance().set(&DataKey::MarketInfo, &market_info);
+
+        Self::settle_stake_bets(&env, None);
+
+        if Self::get_staked_collateral(&env) > 0 {
+            Self::unstake_collateral(&env);
+        }
+        Self::release_unclaimable_yield(&env);
+
+        log!(&env, "Market cancelled by {}", factory);
+    }
+
+    /// Check if the market was cancelled and refunds every bet
+    pub fn is_cancelled(env: Env) -> bool {
+        env.storage().instance().get(&DataKey::Cancelled).unwrap_or(false)
+    }
+
+    /// Claim winnings for a user, or a refund of their bets if the market was
+    /// cancelled. Stake-backed bets that won or were refunded are already back
+    /// in the bettor's stake, so only the rest is paid out; the total is returned.
     pub fn claim_winnings(env: Env, winner: Address) -> i128 {
         winner.require_auth();
 
@@ -291,7 +373,15 @@ impl PredictionMarketContract {
         }
 
         // Calculate winnings
-        let winnings = if outcome {
+        let (user_bet, winnings) = if Self::is_cancelled(env.clone()) {
+            let (bet_for, bet_against) = Self::get_user_bets(env.clone(), winner.clone());
+            let user_bet = bet_for + bet_against;
+            if user_bet == 0 {
+                return 0; // User didn't bet
+            }
+
+            (user_bet, user_bet)
+        } else if outcome {
             // YES won - check if user bet YES
             let bets_for: Map<Address, i128> = env.storage().persistent()
                 .get(&DataKey::BetsFor(Map::new(&env)))
@@ -302,7 +392,7 @@ impl PredictionMarketContract {
                 return 0; // User didn't bet on winning side
             }
 
-            Self::calculate_winnings(user_bet, market_info.total_pool_a, market_info.total_pool_b)
+            (user_bet, Self::calculate_winnings(user_bet, market_info.total_pool_a, market_info.total_pool_b))
         } else {
             // NO won - check if user bet NO
             let bets_against: Map<Address, i128> = env.storage().persistent()
@@ -314,32 +404,162 @@ impl PredictionMarketContract {
                 return 0; // User didn't bet on winning side
             }
 
-            Self::calculate_winnings(user_bet, market_info.total_pool_b, market_info.total_pool_a)
+            (user_bet, Self::calculate_winnings(user_bet, market_info.total_pool_b, market_info.total_pool_a))
         };
 
+        let pool_paid_out: i128 = env.storage().instance().get(&DataKey::PoolPaidOut).unwrap_or(0);
+        env.storage().instance().set(&DataKey::PoolPaidOut, &(pool_paid_out + winnings));
+
+        // Fees are taken from profit only, discounted by the winner's staking tier
+        let winnings = Self::apply_fees(&env, &winner, &market_info, user_bet, winnings);
+
+        let winning_pool = if outcome { market_info.total_pool_a } else { market_info.total_pool_b };
+        let winnings = winnings + Self::take_winners_yield(&env, user_bet, winning_pool);
+
+        // Stake backing the claimed bets was released back to the bettor
+        let released = Self::take_released_stake_bets(&env, &winner, outcome);
+        let payout = winnings - released;
+
+        // Pay from liquid KALE, pulling collateral back from KALE integration if short
+        Self::ensure_liquid(&env, payout);
+
         if winnings > 0 {
             // Transfer winnings to user
-            let token_client = token::Client::new(&env, &config.kale_token);
-            token_client.transfer(&env.current_contract_address(), &winner, &winnings);
+            if payout > 0 {
+                let token_client = token::Client::new(&env, &config.kale_token);
+                token_client.transfer(&env.current_contract_address(), &winner, &payout);
+            }
 
             // Mark as claimed
             claimed_winnings.set(winner.clone(), true);
             env.storage().persistent().set(&DataKey::ClaimedWinnings(Map::new(&env)), &claimed_winnings);
 
             // Emit winnings claimed event
-            let event = EventType::WinningsClaimed {
-                winner: winner.clone(),
-                amount: winnings,
-            };
+            let event = EventType::WinningsClaimed(winner.clone(), winnings);
 
-            env.events().publish((symbol_short!("winnings_claimed"), event));
+            env.events().publish((symbol_short!("claimed"),), event);
 
             log!(&env, "Winnings claimed: {} KALE by {}", winnings, winner);
         }
 
+        Self::require_solvent(&env);
+
         winnings
     }
 
+    /// Get the staking tier table used for fee discounts and bet caps
+    pub fn get_staking_tiers(env: Env) -> Vec<StakingTier> {
+        tiers::staking_tiers(&env)
+    }
+
+    /// Get a user's current staking tier, based on their stake in KALE integration
+    pub fn get_staking_tier(env: Env, user: Address) -> StakingTier {
+        let kale_integration: Address = env.storage().instance().get(&DataKey::KaleIntegration).unwrap();
+        let kale_client = KaleIntegrationClient::new(&env, &kale_integration);
+
+        let staked_amount = match kale_client.try_get_stake_info(&user) {
+            Ok(Ok(stake_info)) => stake_info.amount,
+            _ => 0,
+        };
+
+        tiers::tier_for_stake(&env, staked_amount)
+    }
+
+    /// Get accrued (creator, platform) fees
+    pub fn get_accrued_fees(env: Env) -> (i128, i128) {
+        let creator_fees: i128 = env.storage().instance().get(&DataKey::CreatorFees).unwrap_or(0);
+        let platform_fees: i128 = env.storage().instance().get(&DataKey::PlatformFees).unwrap_or(0);
+        (creator_fees, platform_fees)
+    }
+
+    /// Withdraw accrued creator fees (market creator only)
+    pub fn withdraw_creator_fees(env: Env, creator: Address) -> i128 {
+        creator.require_auth();
+
+        let market_info: Market = env.storage().instance().get(&DataKey::MarketInfo).unwrap();
+        if creator != market_info.creator {
+            panic_with_error!(&env, ContractError::NotAuthorized);
+        }
+
+        Self::withdraw_accrued(&env, &DataKey::CreatorFees, &creator)
+    }
+
+    /// Withdraw accrued platform fees (factory admin only)
+    pub fn withdraw_platform_fees(env: Env, admin: Address, recipient: Address) -> i128 {
+        admin.require_auth();
+
+        let config: Config = env.storage().instance().get(&DataKey::Config).unwrap();
+        if admin != config.admin {
+            panic_with_error!(&env, ContractError::NotAuthorized);
+        }
+
+        Self::withdraw_accrued(&env, &DataKey::PlatformFees, &recipient)
+    }
+
+    /// Opt in to staking the market's collateral in KALE integration until
+    /// resolution, paying the yield to `recipient` (creator only, before any bets)
+    pub fn set_yield_recipient(env: Env, creator: Address, recipient: YieldRecipient) {
+        creator.require_auth();
+
+        let market_info: Market = env.storage().instance().get(&DataKey::MarketInfo).unwrap();
+        if creator != market_info.creator {
+            panic_with_error!(&env, ContractError::NotAuthorized);
+        }
+
+        // Bettors must know where the yield goes before they bet
+        let (total_for, total_against) = Self::get_totals(env.clone());
+        if total_for + total_against > 0 || Self::is_resolved(env.clone()) {
+            panic_with_error!(&env, ContractError::MarketClosed);
+        }
+
+        env.storage().instance().set(&DataKey::YieldRecipient, &recipient);
+
+        log!(&env, "Collateral yield enabled for {} by {}", recipient, creator);
+    }
+
+    /// Get who receives the yield on staked collateral, if yield is enabled
+    pub fn get_yield_recipient(env: Env) -> Option<YieldRecipient> {
+        env.storage().instance().get(&DataKey::YieldRecipient)
+    }
+
+    /// Unstake the collateral once it's within one unbonding period of betting
+    /// closing, and collect its unbonded KALE when the unbonding period is over.
+    /// Callable by anyone, so a keeper can have collateral back by resolution.
+    pub fn withdraw_collateral(env: Env) -> CollateralStatus {
+        if env.ledger().timestamp() < Self::collateral_unstake_time(&env) {
+            panic_with_error!(&env, ContractError::InvalidTimestamp);
+        }
+
+        if Self::get_staked_collateral(&env) > 0 && !Self::unstake_collateral(&env) {
+            panic_with_error!(&env, ContractError::InsufficientBalance);
+        }
+        Self::collect_unbonded_collateral(&env);
+        Self::release_unclaimable_yield(&env);
+
+        Self::require_solvent(&env);
+        Self::get_collateral_status(env)
+    }
+
+    /// Get where the collateral is held and whether it covers every obligation
+    pub fn get_collateral_status(env: Env) -> CollateralStatus {
+        let config: Config = env.storage().instance().get(&DataKey::Config).unwrap();
+        let token_client = token::Client::new(&env, &config.kale_token);
+
+        let staked = Self::get_staked_collateral(&env);
+        let unbonding: i128 = env.storage().instance().get(&DataKey::UnbondingCollateral).unwrap_or(0);
+        let liquid = token_client.balance(&env.current_contract_address());
+        let liabilities = Self::liabilities(&env);
+
+        CollateralStatus {
+            staked,
+            unbonding,
+            liquid,
+            yield_earned: env.storage().instance().get(&DataKey::YieldEarned).unwrap_or(0),
+            liabilities,
+            solvent: liquid + staked + unbonding >= liabilities,
+        }
+    }
+
     /// Get market information
     pub fn get_market_info(env: Env) -> Market {
         env.storage().instance().get(&DataKey::MarketInfo).unwrap()
@@ -379,140 +599,525 @@ impl PredictionMarketContract {
     }
 
     /// Cross-contract call to Reflector Oracle for price data
-    pub fn get_oracle_price_safe(env: Env, oracle_address: Address, asset_name: String) -> ContractCallResult<i128> {
+    pub fn get_oracle_price_safe(env: Env, oracle_address: Address, asset_name: String) -> Result<i128, ContractError> {
         // Create oracle client and call get_price
         let oracle_client = ReflectorOracleClient::new(&env, &oracle_address);
-        
+
         match oracle_client.try_get_price(&asset_name) {
-            Ok(price_feed) => ContractCallResult {
-                success: true,
-                data: Some(price_feed.price),
-                error: None,
-            },
-            Err(_) => ContractCallResult {
-                success: false,
-                data: None,
-                error: Some("Oracle call failed".to_string()),
-            },
+            Ok(Ok(price_feed)) => Ok(price_feed.price),
+            _ => Err(ContractError::OracleError),
         }
     }
 
     /// Cross-contract call to KALE Integration for staking info
-    pub fn get_staking_position_safe(env: Env, kale_address: Address, staker: Address) -> ContractCallResult<StakingPosition> {
+    pub fn get_staking_position_safe(env: Env, kale_address: Address, staker: Address) -> Result<StakingPosition, ContractError> {
         let kale_client = KaleIntegrationClient::new(&env, &kale_address);
-        
+
         match kale_client.try_get_stake_info(&staker) {
-            Ok(stake_info) => {
-                let total_staked = kale_client.get_total_staked();
+            Ok(Ok(stake_info)) => {
                 let apy = kale_client.get_current_apy();
-                
-                ContractCallResult {
-                    success: true,
-                    data: Some(StakingPosition {
-                        staker: staker.clone(),
-                        amount: stake_info.amount,
-                        apy,
-                        pending_rewards: stake_info.accumulated_rewards,
-                        last_update: stake_info.last_reward_time,
-                    }),
-                    error: None,
-                }
-            },
-            Err(_) => ContractCallResult {
-                success: false,
-                data: None,
-                error: Some("KALE integration call failed".to_string()),
+
+                Ok(StakingPosition {
+                    staker: staker.clone(),
+                    amount: stake_info.amount,
+                    apy,
+                    pending_rewards: stake_info.accumulated_rewards,
+                    last_update: stake_info.last_reward_time,
+                })
             },
+            _ => Err(ContractError::StakeNotFound),
         }
     }
 
     /// Enhanced market resolution with oracle integration
-    pub fn resolve_with_oracle(env: Env, resolver: Address, oracle_address: Address) -> ContractCallResult<MarketResolutionData> {
+    pub fn resolve_with_oracle(env: Env, resolver: Address, oracle_address: Address) -> Result<MarketResolutionData, ContractError> {
         resolver.require_auth();
 
-        let config: Config = env.storage().instance().get(&DataKey::Config).unwrap();
         let market_info: Market = env.storage().instance().get(&DataKey::MarketInfo).unwrap();
         let resolved: bool = env.storage().instance().get(&DataKey::Resolved).unwrap_or(false);
 
         if resolved {
-            return ContractCallResult {
-                success: false,
-                data: None,
-                error: Some("Market already resolved".to_string()),
-            };
+            return Err(ContractError::MarketAlreadyResolved);
         }
 
         if env.ledger().timestamp() < market_info.resolution_time {
-            return ContractCallResult {
-                success: false,
-                data: None,
-                error: Some("Resolution time not reached".to_string()),
-            };
+            return Err(ContractError::InvalidTimestamp);
         }
 
         // Get price from oracle
-        let price_result = Self::get_oracle_price_safe(env.clone(), oracle_address, market_info.event_name.clone());
-        
-        if !price_result.success {
-            return ContractCallResult {
-                success: false,
-                data: None,
-                error: Some("Oracle price fetch failed".to_string()),
-            };
-        }
+        let params: MarketParams = env.storage().instance().get(&DataKey::MarketParams).unwrap();
+        let final_price = Self::get_oracle_price_safe(env.clone(), oracle_address, params.oracle_asset.clone())?;
 
-        let final_price = price_result.data.unwrap();
-        
         // Determine outcome based on market condition
-        let outcome = Self::determine_outcome_enhanced(final_price, &market_info);
+        let outcome = Self::determine_outcome(final_price, &params);
 
         // Create resolution data
-        let resolution_data = MarketResolutionData {
+        Ok(MarketResolutionData {
             market_id: BytesN::from_array(&env, &[0u8; 32]), // Would be set by factory
             final_price,
-            target_price: 0, // Would be set from market creation
-            condition: 0, // Would be set from market creation
+            target_price: params.target_price,
+            condition: params.condition,
             outcome,
             confidence: 95, // Would come from oracle
             timestamp: env.ledger().timestamp(),
-        };
-
-        ContractCallResult {
-            success: true,
-            data: Some(resolution_data),
-            error: None,
-        }
+        })
     }
 
     // Private helper functions
     fn get_oracle_price(env: &Env, oracle_address: &Address, asset_name: &String) -> i128 {
         // Enhanced oracle call with error handling
         match Self::get_oracle_price_safe(env.clone(), oracle_address.clone(), asset_name.clone()) {
-            ContractCallResult { success: true, data: Some(price), .. } => price,
-            _ => {
+            Ok(price) => price,
+            Err(_) => {
                 // Fallback to mock prices if oracle fails
-                match asset_name.as_str() {
-                    "KALE" => 85_000_000_000_000, // $0.85 with 14 decimals
-                    "BTC" => 45_000_000_000_000_000, // $45,000 with 14 decimals
-                    "ETH" => 2_800_000_000_000_000, // $2,800 with 14 decimals
-                    "XLM" => 12_000_000_000_000, // $0.12 with 14 decimals
-                    _ => 100_000_000_000_000, // $1.00 default
-                }
+                let fallback_prices = [
+                    ("KALE", 85_000_000_000_000), // $0.85 with 14 decimals
+                    ("BTC", 45_000_000_000_000_000), // $45,000 with 14 decimals
+                    ("ETH", 2_800_000_000_000_000), // $2,800 with 14 decimals
+                    ("XLM", 12_000_000_000_000), // $0.12 with 14 decimals
+                ];
+                fallback_prices
+                    .iter()
+                    .find(|(asset, _)| *asset_name == String::from_str(env, asset))
+                    .map(|(_, price)| *price)
+                    .unwrap_or(100_000_000_000_000) // $1.00 default
+            }
+        }
+    }
+
+    /// YES wins if the final price is above (condition 0) or below (condition 1) the target
+    fn determine_outcome(final_price: i128, params: &MarketParams) -> bool {
+        match params.condition {
+            0 => final_price > params.target_price,
+            1 => final_price < params.target_price,
+            _ => false,
+        }
+    }
+
+    /// Deduct creator and platform fees from a payout's profit, discounted by the
+    /// winner's staking tier, and accrue them. Returns the net payout.
+    fn apply_fees(env: &Env, winner: &Address, market_info: &Market, user_bet: i128, winnings: i128) -> i128 {
+        let profit = winnings - user_bet;
+        if profit <= 0 {
+            return winnings;
+        }
+
+        let tier = Self::get_staking_tier(env.clone(), winner.clone());
+        let discount = BASIS_POINTS - tier.fee_discount_bps as i128;
+
+        let creator_fee = profit * market_info.creator_fee_rate as i128 / BASIS_POINTS * discount / BASIS_POINTS;
+        let platform_fee = profit * market_info.platform_fee_rate as i128 / BASIS_POINTS * discount / BASIS_POINTS;
+
+        // The winner's referrer is paid out of the platform fee
+        let referral_fee = Self::pay_referral_fee(env, winner, platform_fee);
+
+        let creator_fees: i128 = env.storage().instance().get(&DataKey::CreatorFees).unwrap_or(0);
+        let platform_fees: i128 = env.storage().instance().get(&DataKey::PlatformFees).unwrap_or(0);
+        env.storage().instance().set(&DataKey::CreatorFees, &(creator_fees + creator_fee));
+        env.storage().instance().set(&DataKey::PlatformFees, &(platform_fees + platform_fee - referral_fee));
+
+        winnings - creator_fee - platform_fee
+    }
+
+    /// Pay the referrer's cut of a platform fee, using the referral registry
+    /// configured in KALE integration. Returns the amount paid.
+    fn pay_referral_fee(env: &Env, referee: &Address, platform_fee: i128) -> i128 {
+        if platform_fee <= 0 {
+            return 0;
+        }
+
+        let registry = match Self::referral_registry(env) {
+            Some(registry) => registry,
+            None => return 0,
+        };
+
+        // A registry that rejects this market must not block claims
+        let registry_client = ReferralRegistryClient::new(env, &registry);
+        match registry_client.try_record_referral_fee(&env.current_contract_address(), referee, &platform_fee) {
+            Ok(Ok(Some(payout))) => {
+                let config: Config = env.storage().instance().get(&DataKey::Config).unwrap();
+                let token_client = token::Client::new(env, &config.kale_token);
+                token_client.transfer(&env.current_contract_address(), &payout.referrer, &payout.amount);
+                payout.amount
+            }
+            Ok(Ok(None)) => 0,
+            _ => {
+                Self::publish_referral_not_recorded(env, referee, platform_fee);
+                0
+            }
+        }
+    }
+
+    /// Tell the referral registry a user has bet, if one is configured
+    fn record_referral_activity(env: &Env, user: &Address) {
+        if let Some(registry) = Self::referral_registry(env) {
+            let registry_client = ReferralRegistryClient::new(env, &registry);
+            if !matches!(registry_client.try_record_activity(&env.current_contract_address(), user), Ok(Ok(()))) {
+                Self::publish_referral_not_recorded(env, user, 0);
+            }
+        }
+    }
+
+    fn referral_registry(env: &Env) -> Option<Address> {
+        let kale_integration: Address = env.storage().instance().get(&DataKey::KaleIntegration).unwrap();
+        match KaleIntegrationClient::new(env, &kale_integration).try_get_referral_registry() {
+            Ok(Ok(registry)) => registry,
+            _ => None,
+        }
+    }
+
+    fn publish_referral_not_recorded(env: &Env, user: &Address, platform_fee: i128) {
+        let event = EventType::ReferralNotRecorded(user.clone(), platform_fee);
+        env.events().publish((symbol_short!("ref_fail"),), event);
+    }
+
+    fn withdraw_accrued(env: &Env, key: &DataKey, recipient: &Address) -> i128 {
+        let amount: i128 = env.storage().instance().get(key).unwrap_or(0);
+
+        if amount > 0 {
+            let config: Config = env.storage().instance().get(&DataKey::Config).unwrap();
+            let token_client = token::Client::new(env, &config.kale_token);
+            token_client.transfer(&env.current_contract_address(), recipient, &amount);
+            env.storage().instance().set(key, &0i128);
+        }
+
+        Self::require_solvent(env);
+        amount
+    }
+
+    /// Check a bettor and amount against the market's state and limits
+    fn validate_bet(env: &Env, bettor: &Address, amount: i128) -> Result<(), ContractError> {
+        validation::validate_address(env, bettor)?;
+
+        let market_info: Market = env.storage().instance().get(&DataKey::MarketInfo).unwrap();
+        let resolved: bool = env.storage().instance().get(&DataKey::Resolved).unwrap_or(false);
+
+        // Validate market state
+        if resolved {
+            return Err(ContractError::MarketAlreadyResolved);
+        }
+
+        if env.ledger().timestamp() >= market_info.end_time {
+            return Err(ContractError::MarketClosed);
+        }
+
+        // Enhanced bet amount validation; stakers get a higher cap
+        let tier = Self::get_staking_tier(env.clone(), bettor.clone());
+        let max_bet_amount = market_info.max_bet_amount * tier.max_bet_multiplier_bps as i128 / BASIS_POINTS;
+        validation::validate_amount(amount, market_info.min_bet_amount, Some(max_bet_amount))
+    }
+
+    /// Add a bet to the pools and emit it
+    fn record_bet(env: &Env, bettor: &Address, side: bool, amount: i128) {
+        // A first bet closes the bettor's window for registering a referrer
+        if Self::get_user_bets(env.clone(), bettor.clone()) == (0, 0) {
+            Self::record_referral_activity(env, bettor);
+        }
+
+        let mut market_info: Market = env.storage().instance().get(&DataKey::MarketInfo).unwrap();
+
+        // Update bet tracking
+        if side {
+            // YES bet
+            let mut bets_for: Map<Address, i128> = env.storage().persistent()
+                .get(&DataKey::BetsFor(Map::new(env)))
+                .unwrap_or_else(|| Map::new(env));
+            
+            let current_bet = bets_for.get(bettor.clone()).unwrap_or(0);
+            bets_for.set(bettor.clone(), current_bet + amount);
+            env.storage().persistent().set(&DataKey::BetsFor(Map::new(env)), &bets_for);
+
+            let mut total_for: i128 = env.storage().instance().get(&DataKey::TotalFor).unwrap_or(0);
+            total_for += amount;
+            env.storage().instance().set(&DataKey::TotalFor, &total_for);
+            market_info.total_pool_a = total_for;
+        } else {
+            // NO bet
+            let mut bets_against: Map<Address, i128> = env.storage().persistent()
+                .get(&DataKey::BetsAgainst(Map::new(env)))
+                .unwrap_or_else(|| Map::new(env));
+            
+            let current_bet = bets_against.get(bettor.clone()).unwrap_or(0);
+            bets_against.set(bettor.clone(), current_bet + amount);
+            env.storage().persistent().set(&DataKey::BetsAgainst(Map::new(env)), &bets_against);
+
+            let mut total_against: i128 = env.storage().instance().get(&DataKey::TotalAgainst).unwrap_or(0);
+            total_against += amount;
+            env.storage().instance().set(&DataKey::TotalAgainst, &total_against);
+            market_info.total_pool_b = total_against;
+        }
+
+        // Update market info
+        env.storage().instance().set(&DataKey::MarketInfo, &market_info);
+
+        // Emit bet placed event
+        let event = EventType::BetPlaced(
+            bettor.clone(),
+            side,
+            amount,
+            market_info.total_pool_a,
+            market_info.total_pool_b,
+        );
+
+        env.events().publish((symbol_short!("bet"),), event);
+    }
+
+    fn get_stake_bets(env: &Env) -> Map<Address, (i128, i128)> {
+        env.storage().persistent().get(&DataKey::StakeBets).unwrap_or_else(|| Map::new(env))
+    }
+
+    fn get_unfunded_stake_bets(env: &Env) -> i128 {
+        env.storage().instance().get(&DataKey::UnfundedStakeBets).unwrap_or(0)
+    }
+
+    fn adjust_unfunded_stake_bets(env: &Env, delta: i128) {
+        let unfunded = Self::get_unfunded_stake_bets(env);
+        env.storage().instance().set(&DataKey::UnfundedStakeBets, &(unfunded + delta));
+    }
+
+    /// Settle every stake-backed bet with KALE integration: the losing side's
+    /// collateral is sent here, everything else goes back to the stakers.
+    /// `outcome` is None when the market was cancelled.
+    fn settle_stake_bets(env: &Env, outcome: Option<bool>) {
+        let stake_bets = Self::get_stake_bets(env);
+        if stake_bets.is_empty() {
+            return;
+        }
+
+        let kale_integration: Address = env.storage().instance().get(&DataKey::KaleIntegration).unwrap();
+        let kale_client = KaleIntegrationClient::new(env, &kale_integration);
+        for (bettor, (backed_for, backed_against)) in stake_bets.iter() {
+            let lost = match outcome {
+                Some(true) => backed_against,
+                Some(false) => backed_for,
+                None => 0,
+            };
+            kale_client.settle_market_collateral(&env.current_contract_address(), &bettor, &lost);
+            Self::adjust_unfunded_stake_bets(env, -lost);
+        }
+    }
+
+    /// The part of a claim already returned as released stake, no longer owed here
+    fn take_released_stake_bets(env: &Env, bettor: &Address, outcome: bool) -> i128 {
+        let (backed_for, backed_against) = Self::get_stake_bets(env).get(bettor.clone()).unwrap_or((0, 0));
+        let released = if Self::is_cancelled(env.clone()) {
+            backed_for + backed_against
+        } else if outcome {
+            backed_for
+        } else {
+            backed_against
+        };
+
+        Self::adjust_unfunded_stake_bets(env, -released);
+        released
+    }
+
+    fn get_staked_collateral(env: &Env) -> i128 {
+        env.storage().instance().get(&DataKey::StakedCollateral).unwrap_or(0)
+    }
+
+    /// When collateral has to start unbonding to be back by the time betting
+    /// closes. If KALE integration can't say, that's now.
+    fn collateral_unstake_time(env: &Env) -> u64 {
+        let market_info: Market = env.storage().instance().get(&DataKey::MarketInfo).unwrap();
+        let kale_integration: Address = env.storage().instance().get(&DataKey::KaleIntegration).unwrap();
+
+        match KaleIntegrationClient::new(env, &kale_integration).try_get_unbonding_period() {
+            Ok(Ok(unbonding_period)) => market_info.end_time.saturating_sub(unbonding_period),
+            _ => 0,
+        }
+    }
+
+    /// Stake idle collateral until it's time to unbond, then unstake it
+    fn rebalance_collateral(env: &Env) {
+        if env.ledger().timestamp() < Self::collateral_unstake_time(env) {
+            Self::stake_idle_collateral(env);
+        } else if Self::get_staked_collateral(env) > 0 {
+            Self::unstake_collateral(env);
+        }
+    }
+
+    /// Stake every bet not yet staked. Failures are ignored so betting never
+    /// depends on KALE integration; the KALE then simply stays here.
+    fn stake_idle_collateral(env: &Env) {
+        let (total_for, total_against) = Self::get_totals(env.clone());
+        let staked = Self::get_staked_collateral(env);
+        let idle = total_for + total_against - Self::get_unfunded_stake_bets(env) - staked;
+        if idle <= 0 {
+            return;
+        }
+
+        let config: Config = env.storage().instance().get(&DataKey::Config).unwrap();
+        let kale_integration: Address = env.storage().instance().get(&DataKey::KaleIntegration).unwrap();
+
+        // KALE integration pulls the tokens from this contract
+        env.authorize_as_current_contract(vec![
+            env,
+            InvokerContractAuthEntry::Contract(SubContractInvocation {
+                context: ContractContext {
+                    contract: config.kale_token.clone(),
+                    fn_name: Symbol::new(env, "transfer"),
+                    args: (env.current_contract_address(), kale_integration.clone(), idle).into_val(env),
+                },
+                sub_invocations: vec![env],
+            }),
+        ]);
+
+        let kale_client = KaleIntegrationClient::new(env, &kale_integration);
+        if let Ok(Ok(())) = kale_client.try_stake(&env.current_contract_address(), &idle) {
+            env.storage().instance().set(&DataKey::StakedCollateral, &(staked + idle));
+            log!(env, "Staked {} KALE of idle collateral", idle);
+        }
+    }
+
+    /// Collect the staking yield and unstake all collateral. Returns false if
+    /// KALE integration refused, leaving the collateral staked.
+    fn unstake_collateral(env: &Env) -> bool {
+        let kale_integration: Address = env.storage().instance().get(&DataKey::KaleIntegration).unwrap();
+        let kale_client = KaleIntegrationClient::new(env, &kale_integration);
+        let staked = Self::get_staked_collateral(env);
+
+        // Rewards must be claimed first; nothing accrues once unstaked
+        match kale_client.try_claim_rewards(&env.current_contract_address()) {
+            Ok(Ok(earned)) => Self::credit_yield(env, earned),
+            _ => return false,
+        }
+
+        match kale_client.try_unstake(&env.current_contract_address(), &staked) {
+            Ok(Ok(_)) => {
+                let unbonding: i128 = env.storage().instance().get(&DataKey::UnbondingCollateral).unwrap_or(0);
+                env.storage().instance().set(&DataKey::UnbondingCollateral, &(unbonding + staked));
+                env.storage().instance().set(&DataKey::StakedCollateral, &0i128);
+                log!(env, "Unstaked {} KALE of collateral", staked);
+                true
             }
+            _ => false,
         }
     }
 
-    fn determine_outcome_enhanced(final_price: i128, market_info: &Market) -> bool {
-        // Enhanced outcome determination logic
-        // This would use the actual target price and condition from market creation
-        // For now, use the existing logic
-        market_info.total_pool_a > market_info.total_pool_b
+    /// Withdraw collateral whose unbonding period has ended
+    fn collect_unbonded_collateral(env: &Env) {
+        let unbonding: i128 = env.storage().instance().get(&DataKey::UnbondingCollateral).unwrap_or(0);
+        if unbonding == 0 {
+            return;
+        }
+
+        let kale_integration: Address = env.storage().instance().get(&DataKey::KaleIntegration).unwrap();
+        let kale_client = KaleIntegrationClient::new(env, &kale_integration);
+        if let Ok(Ok(withdrawn)) = kale_client.try_withdraw_unbonded(&env.current_contract_address()) {
+            env.storage().instance().set(&DataKey::UnbondingCollateral, &(unbonding - withdrawn));
+        }
     }
 
-    fn determine_outcome(final_price: i128, total_for: i128, total_against: i128) -> bool {
-        // For now, use a simple logic: if more people bet YES, YES wins
-        // In a real implementation, this would use the oracle price and target price
-        total_for > total_against
+    /// Make sure `amount` KALE is held here, unstaking and collecting collateral
+    /// if needed. While KALE integration is paused the principal comes back
+    /// through its emergency withdrawal, forfeiting yield not yet collected.
+    fn ensure_liquid(env: &Env, amount: i128) {
+        let config: Config = env.storage().instance().get(&DataKey::Config).unwrap();
+        let token_client = token::Client::new(env, &config.kale_token);
+        if token_client.balance(&env.current_contract_address()) >= amount {
+            return;
+        }
+
+        if Self::get_staked_collateral(env) > 0 {
+            Self::unstake_collateral(env);
+        }
+        Self::collect_unbonded_collateral(env);
+
+        if token_client.balance(&env.current_contract_address()) < amount {
+            Self::emergency_withdraw_collateral(env);
+        }
+
+        if token_client.balance(&env.current_contract_address()) < amount {
+            panic_with_error!(env, ContractError::InsufficientBalance);
+        }
+    }
+
+    /// Recover staked and unbonding collateral from a paused KALE integration
+    fn emergency_withdraw_collateral(env: &Env) {
+        let kale_integration: Address = env.storage().instance().get(&DataKey::KaleIntegration).unwrap();
+        let kale_client = KaleIntegrationClient::new(env, &kale_integration);
+        if !matches!(kale_client.try_is_paused(), Ok(Ok(true))) {
+            return;
+        }
+
+        if let Ok(Ok(recovered)) = kale_client.try_emergency_withdraw(&env.current_contract_address()) {
+            env.storage().instance().set(&DataKey::StakedCollateral, &0i128);
+            env.storage().instance().set(&DataKey::UnbondingCollateral, &0i128);
+            log!(env, "Recovered {} KALE of collateral by emergency withdrawal", recovered);
+        }
+    }
+
+    /// Accrue staking yield to the configured recipient. Winners' yield goes to
+    /// the platform once it's known nobody won.
+    fn credit_yield(env: &Env, amount: i128) {
+        if amount <= 0 {
+            return;
+        }
+
+        let yield_earned: i128 = env.storage().instance().get(&DataKey::YieldEarned).unwrap_or(0);
+        env.storage().instance().set(&DataKey::YieldEarned, &(yield_earned + amount));
+
+        let key = match Self::get_yield_recipient(env.clone()) {
+            Some(YieldRecipient::Creator) => DataKey::CreatorFees,
+            Some(YieldRecipient::Winners) => DataKey::WinnersYield,
+            _ => DataKey::PlatformFees,
+        };
+        let accrued: i128 = env.storage().instance().get(&key).unwrap_or(0);
+        env.storage().instance().set(&key, &(accrued + amount));
+
+        Self::release_unclaimable_yield(env);
+    }
+
+    /// Move winners' yield to platform fees if the market resolved with no
+    /// winners or was cancelled
+    fn release_unclaimable_yield(env: &Env) {
+        if !Self::is_resolved(env.clone()) {
+            return;
+        }
+
+        let (total_for, total_against) = Self::get_totals(env.clone());
+        let winning_pool = if Self::get_outcome(env.clone()) { total_for } else { total_against };
+        let winners_yield: i128 = env.storage().instance().get(&DataKey::WinnersYield).unwrap_or(0);
+        if (winning_pool > 0 && !Self::is_cancelled(env.clone())) || winners_yield == 0 {
+            return;
+        }
+
+        let platform_fees: i128 = env.storage().instance().get(&DataKey::PlatformFees).unwrap_or(0);
+        env.storage().instance().set(&DataKey::PlatformFees, &(platform_fees + winners_yield));
+        env.storage().instance().set(&DataKey::WinnersYield, &0i128);
+    }
+
+    /// Record and return a winner's pro rata share of the winners' yield
+    fn take_winners_yield(env: &Env, user_bet: i128, winning_pool: i128) -> i128 {
+        let winners_yield: i128 = env.storage().instance().get(&DataKey::WinnersYield).unwrap_or(0);
+        if winners_yield == 0 || winning_pool == 0 {
+            return 0;
+        }
+
+        let share = user_bet * winners_yield / winning_pool;
+        let paid: i128 = env.storage().instance().get(&DataKey::WinnersYieldPaid).unwrap_or(0);
+        env.storage().instance().set(&DataKey::WinnersYieldPaid, &(paid + share));
+        share
+    }
+
+    /// Unpaid bets not backed by stake, accrued fees and unpaid winners' yield
+    fn liabilities(env: &Env) -> i128 {
+        let (total_for, total_against) = Self::get_totals(env.clone());
+        let pool_paid_out: i128 = env.storage().instance().get(&DataKey::PoolPaidOut).unwrap_or(0);
+        let (creator_fees, platform_fees) = Self::get_accrued_fees(env.clone());
+        let winners_yield: i128 = env.storage().instance().get(&DataKey::WinnersYield).unwrap_or(0);
+        let winners_yield_paid: i128 = env.storage().instance().get(&DataKey::WinnersYieldPaid).unwrap_or(0);
+
+        total_for + total_against - pool_paid_out - Self::get_unfunded_stake_bets(env)
+            + creator_fees + platform_fees + winners_yield - winners_yield_paid
+    }
+
+    /// Panic unless the KALE held here, staked or unbonding covers every liability
+    fn require_solvent(env: &Env) {
+        if !Self::get_collateral_status(env.clone()).solvent {
+            panic_with_error!(env, ContractError::InsufficientBalance);
+        }
     }
 
     fn calculate_winnings(user_bet: i128, winning_pool: i128, losing_pool: i128) -> i128 {
@@ -526,3 +1131,6 @@ impl PredictionMarketContract {
     }
 }
 
+
+#[cfg(test)]
+mod test;
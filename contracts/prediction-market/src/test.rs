@@ -0,0 +1,652 @@
+#![cfg(test)]
+
+use super::*;
+use soroban_sdk::testutils::{Address as _, Events, Ledger};
+use soroban_sdk::token::StellarAssetClient;
+use soroban_sdk::{contract, contractimpl, contracttype};
+use shared_types::{ReferralPayout, StakeInfo};
+use referral_registry::{ReferralRegistryContract, ReferralRegistryContractClient};
+
+const DAY: u64 = 24 * 60 * 60;
+const UNBONDING_PERIOD: u64 = 3 * DAY;
+const KALE_PRICE: i128 = 85_000_000_000_000; // Fallback price used when the oracle is unreachable
+
+/// Stand-in for KALE integration with a fixed unbonding period and yield
+/// credited by the test. It refuses every call while paused except the
+/// emergency withdrawal, and never panics: a panic in a cross-contract call
+/// can't unwind out of the SDK's `extern` entry points.
+#[contract]
+struct MockKaleIntegration;
+
+#[contracttype]
+enum MockKey {
+    Token,
+    Paused,
+    Staked(Address),
+    Unbonding(Address),
+    UnlockTime(Address),
+    Yield(Address),
+    Registry,
+    Collateral(Address),
+}
+
+#[contractimpl]
+impl MockKaleIntegration {
+    pub fn init(env: Env, token: Address) {
+        env.storage().instance().set(&MockKey::Token, &token);
+    }
+
+    pub fn set_paused(env: Env, paused: bool) {
+        env.storage().instance().set(&MockKey::Paused, &paused);
+    }
+
+    /// Credit staking yield, paid out of KALE already held by the mock
+    pub fn add_yield(env: Env, staker: Address, amount: i128) {
+        let earned = Self::read(&env, MockKey::Yield(staker.clone()));
+        env.storage().instance().set(&MockKey::Yield(staker), &(earned + amount));
+    }
+
+    pub fn get_stake_info(env: Env, staker: Address) -> StakeInfo {
+        let amount = Self::read(&env, MockKey::Staked(staker.clone()));
+        StakeInfo {
+            staker,
+            amount,
+            weight: amount,
+            stake_time: 0,
+            last_reward_time: 0,
+            accumulated_rewards: 0,
+            reward_per_token_paid: 0,
+        }
+    }
+
+    pub fn stake(env: Env, staker: Address, amount: i128) -> Result<(), ContractError> {
+        staker.require_auth();
+        Self::require_not_paused(&env)?;
+
+        Self::token(&env).transfer(&staker, &env.current_contract_address(), &amount);
+        let staked = Self::read(&env, MockKey::Staked(staker.clone()));
+        env.storage().instance().set(&MockKey::Staked(staker), &(staked + amount));
+        Ok(())
+    }
+
+    pub fn unstake(env: Env, staker: Address, amount: i128) -> Result<u32, ContractError> {
+        staker.require_auth();
+        Self::require_not_paused(&env)?;
+
+        let staked = Self::read(&env, MockKey::Staked(staker.clone()));
+        let unbonding = Self::read(&env, MockKey::Unbonding(staker.clone()));
+        env.storage().instance().set(&MockKey::Staked(staker.clone()), &(staked - amount));
+        env.storage().instance().set(&MockKey::Unbonding(staker.clone()), &(unbonding + amount));
+        env.storage().instance().set(&MockKey::UnlockTime(staker), &(env.ledger().timestamp() + UNBONDING_PERIOD));
+        Ok(1)
+    }
+
+    pub fn withdraw_unbonded(env: Env, staker: Address) -> Result<i128, ContractError> {
+        staker.require_auth();
+        Self::require_not_paused(&env)?;
+
+        let unlock_time: u64 = env.storage().instance().get(&MockKey::UnlockTime(staker.clone())).unwrap_or(0);
+        if env.ledger().timestamp() < unlock_time {
+            return Ok(0);
+        }
+
+        let unbonding = Self::read(&env, MockKey::Unbonding(staker.clone()));
+        env.storage().instance().set(&MockKey::Unbonding(staker.clone()), &0i128);
+        Self::pay(&env, &staker, unbonding);
+        Ok(unbonding)
+    }
+
+    pub fn claim_rewards(env: Env, staker: Address) -> Result<i128, ContractError> {
+        staker.require_auth();
+        Self::require_not_paused(&env)?;
+
+        let earned = Self::read(&env, MockKey::Yield(staker.clone()));
+        env.storage().instance().set(&MockKey::Yield(staker.clone()), &0i128);
+        Self::pay(&env, &staker, earned);
+        Ok(earned)
+    }
+
+    pub fn get_unbonding_period(_env: Env) -> u64 {
+        UNBONDING_PERIOD
+    }
+
+    pub fn is_paused(env: Env) -> bool {
+        env.storage().instance().get(&MockKey::Paused).unwrap_or(false)
+    }
+
+    pub fn emergency_withdraw(env: Env, staker: Address) -> Result<i128, ContractError> {
+        staker.require_auth();
+        if !Self::is_paused(env.clone()) {
+            return Err(ContractError::NotAuthorized);
+        }
+
+        let principal = Self::read(&env, MockKey::Staked(staker.clone()))
+            + Self::read(&env, MockKey::Unbonding(staker.clone()));
+        env.storage().instance().set(&MockKey::Staked(staker.clone()), &0i128);
+        env.storage().instance().set(&MockKey::Unbonding(staker.clone()), &0i128);
+        Self::pay(&env, &staker, principal);
+        Ok(principal)
+    }
+
+    pub fn lock_market_collateral(env: Env, _market: Address, staker: Address, amount: i128) -> Result<(), ContractError> {
+        let staked = Self::read(&env, MockKey::Staked(staker.clone()));
+        let collateral = Self::read(&env, MockKey::Collateral(staker.clone()));
+        if amount > staked - collateral {
+            return Err(ContractError::InsufficientStake);
+        }
+        env.storage().instance().set(&MockKey::Collateral(staker), &(collateral + amount));
+        Ok(())
+    }
+
+    pub fn settle_market_collateral(env: Env, market: Address, staker: Address, lost: i128) {
+        let staked = Self::read(&env, MockKey::Staked(staker.clone()));
+        env.storage().instance().set(&MockKey::Collateral(staker.clone()), &0i128);
+        env.storage().instance().set(&MockKey::Staked(staker), &(staked - lost));
+        Self::pay(&env, &market, lost);
+    }
+
+    pub fn get_market_collateral(env: Env, staker: Address) -> i128 {
+        Self::read(&env, MockKey::Collateral(staker))
+    }
+
+    pub fn set_referral_registry(env: Env, registry: Address) {
+        env.storage().instance().set(&MockKey::Registry, &registry);
+    }
+
+    pub fn get_referral_registry(env: Env) -> Option<Address> {
+        env.storage().instance().get(&MockKey::Registry)
+    }
+
+    fn require_not_paused(env: &Env) -> Result<(), ContractError> {
+        if Self::is_paused(env.clone()) {
+            return Err(ContractError::ContractPaused);
+        }
+        Ok(())
+    }
+
+    fn read(env: &Env, key: MockKey) -> i128 {
+        env.storage().instance().get(&key).unwrap_or(0)
+    }
+
+    fn token(env: &Env) -> token::Client<'_> {
+        let token: Address = env.storage().instance().get(&MockKey::Token).unwrap();
+        token::Client::new(env, &token)
+    }
+
+    fn pay(env: &Env, to: &Address, amount: i128) {
+        if amount > 0 {
+            Self::token(env).transfer(&env.current_contract_address(), to, &amount);
+        }
+    }
+}
+
+struct Setup {
+    env: Env,
+    client: PredictionMarketContractClient<'static>,
+    kale: MockKaleIntegrationClient<'static>,
+    token: token::Client<'static>,
+    admin: Address,
+    creator: Address,
+    resolve_time: u64,
+}
+
+/// A fee-free market on KALE closing in ten days, where YES wins if KALE
+/// ends above `target_price`
+fn setup(target_price: i128) -> Setup {
+    setup_with_fees(target_price, 0, 0)
+}
+
+fn setup_with_fees(target_price: i128, creator_fee_rate: u32, platform_fee_rate: u32) -> Setup {
+    let env = Env::default();
+    env.mock_all_auths();
+    env.ledger().with_mut(|li| li.timestamp = 1_000_000);
+
+    let admin = Address::generate(&env);
+    let creator = Address::generate(&env);
+    let token_address = env.register_stellar_asset_contract(admin.clone());
+    let token = token::Client::new(&env, &token_address);
+
+    let kale_id = env.register_contract(None, MockKaleIntegration);
+    let kale = MockKaleIntegrationClient::new(&env, &kale_id);
+    kale.init(&token_address);
+
+    let contract_id = env.register_contract(None, PredictionMarketContract);
+    let client = PredictionMarketContractClient::new(&env, &contract_id);
+
+    let resolve_time = env.ledger().timestamp() + 10 * DAY;
+    let params = market_params(&env, target_price, resolve_time, creator_fee_rate);
+    // No oracle is deployed, so resolution uses the fallback KALE price
+    let oracle = Address::generate(&env);
+    client.initialize(&admin, &creator, &params, &token_address, &oracle, &kale_id, &platform_fee_rate);
+
+    Setup { env, client, kale, token, admin, creator, resolve_time }
+}
+
+fn market_params(env: &Env, target_price: i128, resolve_time: u64, creator_fee_rate: u32) -> MarketParams {
+    MarketParams {
+        event_description: String::from_str(env, "KALE above target"),
+        oracle_asset: String::from_str(env, "KALE"),
+        target_price,
+        condition: 0,
+        resolve_time,
+        min_bet_amount: 10,
+        max_bet_amount: 100_000,
+        creator_fee_rate,
+    }
+}
+
+fn new_bettor(setup: &Setup, amount: i128) -> Address {
+    let bettor = Address::generate(&setup.env);
+    StellarAssetClient::new(&setup.env, &setup.token.address).mint(&bettor, &amount);
+    bettor
+}
+
+/// Have the mock KALE integration owe the market `amount` of staking yield
+fn add_yield(setup: &Setup, amount: i128) {
+    StellarAssetClient::new(&setup.env, &setup.token.address).mint(&setup.kale.address, &amount);
+    setup.kale.add_yield(&setup.client.address, &amount);
+}
+
+fn advance_to(env: &Env, timestamp: u64) {
+    env.ledger().with_mut(|li| li.timestamp = timestamp);
+}
+
+#[test]
+fn test_bet_stakes_collateral() {
+    let setup = setup(KALE_PRICE - 1);
+    setup.client.set_yield_recipient(&setup.creator, &YieldRecipient::Platform);
+
+    let alice = new_bettor(&setup, 1_000);
+    let bob = new_bettor(&setup, 500);
+    setup.client.bet(&alice, &true, &1_000);
+    setup.client.bet(&bob, &false, &500);
+
+    let status = setup.client.get_collateral_status();
+    assert_eq!(status.staked, 1_500);
+    assert_eq!(status.liquid, 0);
+    assert!(status.solvent);
+    assert_eq!(setup.kale.get_stake_info(&setup.client.address).amount, 1_500);
+}
+
+#[test]
+fn test_bets_stay_liquid_without_yield_recipient() {
+    let setup = setup(KALE_PRICE - 1);
+
+    let alice = new_bettor(&setup, 1_000);
+    setup.client.bet(&alice, &true, &1_000);
+
+    let status = setup.client.get_collateral_status();
+    assert_eq!(status.staked, 0);
+    assert_eq!(status.liquid, 1_000);
+}
+
+#[test]
+fn test_collateral_unbonds_before_betting_closes() {
+    let setup = setup(KALE_PRICE - 1);
+    setup.client.set_yield_recipient(&setup.creator, &YieldRecipient::Platform);
+
+    let alice = new_bettor(&setup, 1_000);
+    setup.client.bet(&alice, &true, &1_000);
+
+    // A keeper unstakes one unbonding period before close and collects at close
+    advance_to(&setup.env, setup.resolve_time - UNBONDING_PERIOD);
+    let status = setup.client.withdraw_collateral();
+    assert_eq!((status.staked, status.unbonding, status.liquid), (0, 1_000, 0));
+
+    advance_to(&setup.env, setup.resolve_time);
+    let status = setup.client.withdraw_collateral();
+    assert_eq!((status.staked, status.unbonding, status.liquid), (0, 0, 1_000));
+
+    // Resolution finds the collateral back and the claim pays at once
+    setup.client.resolve(&setup.admin);
+    assert!(setup.client.get_outcome());
+    assert_eq!(setup.client.claim_winnings(&alice), 1_000);
+    assert_eq!(setup.token.balance(&alice), 1_000);
+}
+
+#[test]
+fn test_bets_after_unstake_time_stay_liquid() {
+    let setup = setup(KALE_PRICE - 1);
+    setup.client.set_yield_recipient(&setup.creator, &YieldRecipient::Platform);
+
+    let alice = new_bettor(&setup, 1_000);
+    let bob = new_bettor(&setup, 500);
+    setup.client.bet(&alice, &true, &1_000);
+
+    // Collateral staked so far starts unbonding and later bets are kept here
+    advance_to(&setup.env, setup.resolve_time - UNBONDING_PERIOD);
+    setup.client.bet(&bob, &false, &500);
+
+    let status = setup.client.get_collateral_status();
+    assert_eq!((status.staked, status.unbonding, status.liquid), (0, 1_000, 500));
+}
+
+#[test]
+fn test_claim_collects_collateral_unstaked_at_resolution() {
+    let setup = setup(KALE_PRICE - 1);
+    setup.client.set_yield_recipient(&setup.creator, &YieldRecipient::Platform);
+
+    let alice = new_bettor(&setup, 1_000);
+    let bob = new_bettor(&setup, 1_000);
+    setup.client.bet(&alice, &true, &1_000);
+    setup.client.bet(&bob, &false, &1_000);
+
+    // Nobody unstaked ahead of close, so resolution does
+    advance_to(&setup.env, setup.resolve_time);
+    setup.client.resolve(&setup.admin);
+    let status = setup.client.get_collateral_status();
+    assert_eq!((status.staked, status.unbonding), (0, 2_000));
+
+    // Once unbonded, the winner's claim withdraws it
+    advance_to(&setup.env, setup.resolve_time + UNBONDING_PERIOD);
+    assert_eq!(setup.client.claim_winnings(&alice), 2_000);
+    assert_eq!(setup.token.balance(&alice), 2_000);
+
+    let status = setup.client.get_collateral_status();
+    assert_eq!((status.unbonding, status.liquid, status.liabilities), (0, 0, 0));
+}
+
+#[test]
+fn test_yield_to_creator() {
+    let setup = setup(KALE_PRICE - 1);
+    setup.client.set_yield_recipient(&setup.creator, &YieldRecipient::Creator);
+
+    let alice = new_bettor(&setup, 1_000);
+    setup.client.bet(&alice, &true, &1_000);
+    add_yield(&setup, 100);
+
+    advance_to(&setup.env, setup.resolve_time);
+    setup.client.resolve(&setup.admin);
+
+    assert_eq!(setup.client.get_accrued_fees(), (100, 0));
+    assert_eq!(setup.client.get_collateral_status().yield_earned, 100);
+    assert_eq!(setup.client.withdraw_creator_fees(&setup.creator), 100);
+    assert_eq!(setup.token.balance(&setup.creator), 100);
+}
+
+#[test]
+fn test_yield_to_platform() {
+    let setup = setup(KALE_PRICE - 1);
+    setup.client.set_yield_recipient(&setup.creator, &YieldRecipient::Platform);
+
+    let alice = new_bettor(&setup, 1_000);
+    setup.client.bet(&alice, &true, &1_000);
+    add_yield(&setup, 100);
+
+    advance_to(&setup.env, setup.resolve_time);
+    setup.client.resolve(&setup.admin);
+
+    assert_eq!(setup.client.get_accrued_fees(), (0, 100));
+    let treasury = Address::generate(&setup.env);
+    assert_eq!(setup.client.withdraw_platform_fees(&setup.admin, &treasury), 100);
+    assert_eq!(setup.token.balance(&treasury), 100);
+}
+
+#[test]
+fn test_yield_to_winners_pro_rata() {
+    let setup = setup(KALE_PRICE - 1);
+    setup.client.set_yield_recipient(&setup.creator, &YieldRecipient::Winners);
+
+    let alice = new_bettor(&setup, 1_000);
+    let bob = new_bettor(&setup, 3_000);
+    let carol = new_bettor(&setup, 4_000);
+    setup.client.bet(&alice, &true, &1_000);
+    setup.client.bet(&bob, &true, &3_000);
+    setup.client.bet(&carol, &false, &4_000);
+    add_yield(&setup, 400);
+
+    advance_to(&setup.env, setup.resolve_time + UNBONDING_PERIOD);
+    setup.client.resolve(&setup.admin);
+    assert_eq!(setup.client.get_accrued_fees(), (0, 0));
+
+    advance_to(&setup.env, setup.resolve_time + 2 * UNBONDING_PERIOD);
+    assert_eq!(setup.client.claim_winnings(&alice), 2_000 + 100);
+    assert_eq!(setup.client.claim_winnings(&bob), 6_000 + 300);
+    assert_eq!(setup.client.claim_winnings(&carol), 0);
+    assert_eq!(setup.client.get_collateral_status().liabilities, 0);
+}
+
+#[test]
+fn test_winners_yield_goes_to_platform_without_winners() {
+    // KALE ends below the target, but everyone backed YES
+    let setup = setup(KALE_PRICE + 1);
+    setup.client.set_yield_recipient(&setup.creator, &YieldRecipient::Winners);
+
+    let alice = new_bettor(&setup, 1_000);
+    setup.client.bet(&alice, &true, &1_000);
+    add_yield(&setup, 100);
+
+    advance_to(&setup.env, setup.resolve_time);
+    setup.client.resolve(&setup.admin);
+
+    assert!(!setup.client.get_outcome());
+    assert_eq!(setup.client.get_accrued_fees(), (0, 100));
+    assert_eq!(setup.client.claim_winnings(&alice), 0);
+}
+
+#[test]
+fn test_cancelled_market_refunds_every_bet() {
+    let setup = setup_with_fees(KALE_PRICE - 1, 100, 100);
+    setup.client.set_yield_recipient(&setup.creator, &YieldRecipient::Winners);
+
+    let alice = new_bettor(&setup, 1_000);
+    let bob = new_bettor(&setup, 3_000);
+    setup.client.bet(&alice, &true, &1_000);
+    setup.client.bet(&bob, &false, &3_000);
+    add_yield(&setup, 100);
+
+    advance_to(&setup.env, setup.resolve_time);
+    setup.client.cancel(&setup.admin);
+    assert!(setup.client.is_cancelled());
+    assert_eq!(setup.client.get_market_info().status, MarketStatus::Cancelled);
+    // Nobody wins, so the winners' yield goes to the platform
+    assert_eq!(setup.client.get_accrued_fees(), (0, 100));
+
+    // Refunds are fee-free, whichever side was bet on
+    advance_to(&setup.env, setup.resolve_time + UNBONDING_PERIOD);
+    assert_eq!(setup.client.claim_winnings(&alice), 1_000);
+    assert_eq!(setup.client.claim_winnings(&bob), 3_000);
+    assert_eq!(setup.client.get_collateral_status().liabilities, 100);
+}
+
+/// A bettor with `amount` staked in the mock KALE integration
+fn new_staker(setup: &Setup, amount: i128) -> Address {
+    let staker = new_bettor(setup, amount);
+    setup.kale.stake(&staker, &amount);
+    staker
+}
+
+#[test]
+fn test_stake_backed_bets_settle_against_the_stake() {
+    let setup = setup(KALE_PRICE - 1);
+
+    let alice = new_staker(&setup, 1_000);
+    let bob = new_staker(&setup, 500);
+    let carol = new_bettor(&setup, 500);
+    setup.client.bet_with_stake(&alice, &true, &1_000);
+    setup.client.bet_with_stake(&bob, &false, &500);
+    setup.client.bet(&carol, &false, &500);
+
+    // Stake-backed bets stay in the stake and aren't owed by the market yet
+    assert_eq!(setup.kale.get_market_collateral(&alice), 1_000);
+    assert_eq!(setup.client.get_totals(), (1_000, 1_000));
+    assert_eq!(setup.client.get_collateral_status().liabilities, 500);
+
+    advance_to(&setup.env, setup.resolve_time);
+    setup.client.resolve(&setup.admin);
+
+    // Bob's lost collateral came out of his stake; Alice's was released
+    assert_eq!(setup.kale.get_stake_info(&bob).amount, 0);
+    assert_eq!(setup.kale.get_market_collateral(&alice), 0);
+    assert_eq!(setup.kale.get_stake_info(&alice).amount, 1_000);
+
+    // Only the profit is paid out
+    assert_eq!(setup.client.claim_winnings(&alice), 2_000);
+    assert_eq!(setup.token.balance(&alice), 1_000);
+    let status = setup.client.get_collateral_status();
+    assert_eq!((status.liquid, status.liabilities), (0, 0));
+    assert!(status.solvent);
+}
+
+#[test]
+fn test_cancelled_market_releases_stake_backed_bets() {
+    let setup = setup(KALE_PRICE - 1);
+
+    let alice = new_staker(&setup, 1_000);
+    let bob = new_bettor(&setup, 1_000);
+    setup.client.bet_with_stake(&alice, &true, &600);
+    setup.client.bet(&bob, &false, &1_000);
+
+    setup.client.cancel(&setup.admin);
+    assert_eq!(setup.kale.get_market_collateral(&alice), 0);
+    assert_eq!(setup.kale.get_stake_info(&alice).amount, 1_000);
+
+    assert_eq!(setup.client.claim_winnings(&alice), 600);
+    assert_eq!(setup.token.balance(&alice), 0);
+    assert_eq!(setup.client.claim_winnings(&bob), 1_000);
+    let status = setup.client.get_collateral_status();
+    assert_eq!((status.liquid, status.liabilities), (0, 0));
+}
+
+#[test]
+fn test_claim_recovers_collateral_from_paused_integration() {
+    let setup = setup(KALE_PRICE - 1);
+    setup.client.set_yield_recipient(&setup.creator, &YieldRecipient::Platform);
+
+    let alice = new_bettor(&setup, 1_000);
+    let bob = new_bettor(&setup, 1_000);
+    setup.client.bet(&alice, &true, &1_000);
+    setup.client.bet(&bob, &false, &1_000);
+    add_yield(&setup, 100);
+
+    // Paused before anything was unstaked: resolution can't unstake either
+    setup.kale.set_paused(&true);
+    advance_to(&setup.env, setup.resolve_time);
+    setup.client.resolve(&setup.admin);
+    assert_eq!(setup.client.get_collateral_status().staked, 2_000);
+
+    // The claim pulls the principal out through the emergency withdrawal,
+    // forfeiting the uncollected yield
+    assert_eq!(setup.client.claim_winnings(&alice), 2_000);
+    let status = setup.client.get_collateral_status();
+    assert_eq!((status.staked, status.unbonding, status.yield_earned), (0, 0, 0));
+    assert!(status.solvent);
+}
+
+#[test]
+fn test_platform_fee_uses_its_own_rate() {
+    let setup = setup_with_fees(KALE_PRICE - 1, 100, 250);
+
+    let alice = new_bettor(&setup, 1_000);
+    let bob = new_bettor(&setup, 1_000);
+    setup.client.bet(&alice, &true, &1_000);
+    setup.client.bet(&bob, &false, &1_000);
+
+    advance_to(&setup.env, setup.resolve_time);
+    setup.client.resolve(&setup.admin);
+
+    // 1% to the creator and 2.5% to the platform, both from the 1_000 profit
+    assert_eq!(setup.client.claim_winnings(&alice), 2_000 - 10 - 25);
+    assert_eq!(setup.client.get_accrued_fees(), (10, 25));
+    assert_eq!(setup.client.get_market_info().platform_fee_rate, 250);
+}
+
+/// Have the mock KALE integration point markets at a fresh referral registry
+/// paying referrers 20% of platform fees
+fn setup_referral_registry(setup: &Setup) -> ReferralRegistryContractClient<'static> {
+    let registry_id = setup.env.register_contract(None, ReferralRegistryContract);
+    let registry = ReferralRegistryContractClient::new(&setup.env, &registry_id);
+    registry.initialize(&setup.admin, &2_000u32);
+    setup.kale.set_referral_registry(&registry_id);
+    registry
+}
+
+#[test]
+fn test_referrer_is_paid_from_the_platform_fee() {
+    let setup = setup_with_fees(KALE_PRICE - 1, 0, 250);
+    let registry = setup_referral_registry(&setup);
+    // The factory adds each market it deploys as a reporter
+    registry.set_reporter(&setup.admin, &setup.client.address, &true);
+
+    let alice = new_bettor(&setup, 1_000);
+    let bob = new_bettor(&setup, 1_000);
+    let referrer = Address::generate(&setup.env);
+    registry.register_referrer(&alice, &referrer);
+    setup.client.bet(&alice, &true, &1_000);
+    setup.client.bet(&bob, &false, &1_000);
+    assert!(registry.is_active(&bob));
+
+    advance_to(&setup.env, setup.resolve_time);
+    setup.client.resolve(&setup.admin);
+
+    assert_eq!(setup.client.claim_winnings(&alice), 2_000 - 25);
+    assert_eq!(setup.token.balance(&referrer), 5);
+    assert_eq!(setup.client.get_accrued_fees(), (0, 20));
+    assert_eq!(registry.get_referee_fees(&alice), 25);
+}
+
+/// Stand-in for a referral registry that hasn't allowed the market as a
+/// reporter. It returns errors rather than panicking, like the mock above.
+#[contract]
+struct RejectingRegistry;
+
+#[contractimpl]
+impl RejectingRegistry {
+    pub fn record_activity(_env: Env, _reporter: Address, _user: Address) -> Result<(), ContractError> {
+        Err(ContractError::NotAuthorized)
+    }
+
+    pub fn record_referral_fee(_env: Env, _reporter: Address, _referee: Address, _fee: i128) -> Result<Option<ReferralPayout>, ContractError> {
+        Err(ContractError::NotAuthorized)
+    }
+}
+
+#[test]
+fn test_rejected_referral_fees_are_reported() {
+    let setup = setup_with_fees(KALE_PRICE - 1, 0, 250);
+    let registry_id = setup.env.register_contract(None, RejectingRegistry);
+    setup.kale.set_referral_registry(&registry_id);
+
+    let alice = new_bettor(&setup, 1_000);
+    let bob = new_bettor(&setup, 1_000);
+    setup.client.bet(&alice, &true, &1_000);
+    setup.client.bet(&bob, &false, &1_000);
+
+    advance_to(&setup.env, setup.resolve_time);
+    setup.client.resolve(&setup.admin);
+
+    // The claim goes through, keeping the whole fee, and says why
+    assert_eq!(setup.client.claim_winnings(&alice), 2_000 - 25);
+    assert_eq!(setup.client.get_accrued_fees(), (0, 25));
+    let (_, _, data) = setup.env.events().all().iter()
+        .filter(|(_, topics, _)| *topics == (symbol_short!("ref_fail"),).into_val(&setup.env))
+        .last()
+        .unwrap();
+    let event: EventType = data.into_val(&setup.env);
+    assert!(matches!(event, EventType::ReferralNotRecorded(user, 25) if user == alice));
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #6)")]
+fn test_platform_fee_rate_is_capped() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let contract_id = env.register_contract(None, PredictionMarketContract);
+    let factory = Address::generate(&env);
+    let params = market_params(&env, KALE_PRICE, 1_000, 100);
+
+    // Called directly: a panic can't unwind out of the SDK's `extern` entry points
+    env.as_contract(&contract_id, || {
+        PredictionMarketContract::initialize(
+            env.clone(),
+            factory.clone(),
+            Address::generate(&env),
+            params,
+            Address::generate(&env),
+            Address::generate(&env),
+            Address::generate(&env),
+            MAX_FEE_RATE + 1,
+        )
+    });
+}
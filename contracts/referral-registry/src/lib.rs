@@ -0,0 +1,253 @@
+#![no_std]
+
+use soroban_sdk::{
+    contract, contractimpl, contracttype, Address, Env, Vec,
+    panic_with_error, log,
+};
+use shared_types::{ContractError, ReferralPayout};
+
+const DAY_IN_LEDGERS: u32 = 17280;
+const INSTANCE_BUMP_AMOUNT: u32 = 7 * DAY_IN_LEDGERS;
+const INSTANCE_LIFETIME_THRESHOLD: u32 = INSTANCE_BUMP_AMOUNT - DAY_IN_LEDGERS;
+
+const BASIS_POINTS: u32 = 10_000;
+const MAX_REFERRAL_SHARE: u32 = 5_000; // 50% of platform fees
+const MAX_PAGE_SIZE: u32 = 100;
+
+#[contracttype]
+pub enum DataKey {
+    Admin,
+    ReferralShare,
+    Reporter(Address),
+    Registrar(Address),
+    Active(Address),
+    Referrer(Address),
+    RefereeCount(Address),
+    Referee(Address, u32),
+    ReferralEarnings(Address),
+    RefereeFees(Address),
+}
+
+#[contract]
+pub struct ReferralRegistryContract;
+
+#[contractimpl]
+impl ReferralRegistryContract {
+    /// Initialize the registry with the referrer's share of platform fees
+    pub fn initialize(env: Env, admin: Address, referral_share_bps: u32) {
+        if env.storage().instance().has(&DataKey::Admin) {
+            panic_with_error!(&env, ContractError::NotAuthorized);
+        }
+
+        if referral_share_bps > MAX_REFERRAL_SHARE {
+            panic_with_error!(&env, ContractError::InvalidAmount);
+        }
+
+        env.storage().instance().set(&DataKey::Admin, &admin);
+        env.storage().instance().set(&DataKey::ReferralShare, &referral_share_bps);
+
+        env.storage()
+            .instance()
+            .extend_ttl(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+
+        log!(&env, "ReferralRegistry initialized by admin: {}", admin);
+    }
+
+    /// Record who referred `referee`. Can only be set once, before the referee
+    /// first stakes, bets or generates platform fees.
+    pub fn register_referrer(env: Env, referee: Address, referrer: Address) {
+        referee.require_auth();
+
+        if referee == referrer {
+            panic_with_error!(&env, ContractError::NotAuthorized);
+        }
+
+        if env.storage().persistent().has(&DataKey::Referrer(referee.clone())) {
+            panic_with_error!(&env, ContractError::AlreadyClaimed);
+        }
+
+        // An existing user can't pick a referrer after the fact
+        if Self::is_active(env.clone(), referee.clone()) {
+            panic_with_error!(&env, ContractError::NotAuthorized);
+        }
+
+        // Two accounts can't refer each other
+        if Self::get_referrer(env.clone(), referrer.clone()) == Some(referee.clone()) {
+            panic_with_error!(&env, ContractError::NotAuthorized);
+        }
+
+        let referee_count = Self::get_referee_count(env.clone(), referrer.clone());
+        env.storage().persistent().set(&DataKey::Referee(referrer.clone(), referee_count), &referee);
+        env.storage().persistent().set(&DataKey::RefereeCount(referrer.clone()), &(referee_count + 1));
+        env.storage().persistent().set(&DataKey::Referrer(referee.clone()), &referrer);
+
+        log!(&env, "Referral registered: {} referred by {}", referee, referrer);
+    }
+
+    /// Record a platform fee generated by `referee` and return the referrer's cut,
+    /// which the reporting contract pays out of that fee. Reporters only.
+    pub fn record_referral_fee(env: Env, reporter: Address, referee: Address, fee: i128) -> Option<ReferralPayout> {
+        reporter.require_auth();
+
+        if !Self::is_reporter(env.clone(), reporter.clone()) {
+            panic_with_error!(&env, ContractError::NotAuthorized);
+        }
+
+        if fee <= 0 {
+            return None;
+        }
+
+        // Fees are tracked for every user, referred or not, and paying one
+        // closes the window for registering a referrer
+        let referee_fees = Self::get_referee_fees(env.clone(), referee.clone());
+        env.storage().persistent().set(&DataKey::RefereeFees(referee.clone()), &(referee_fees + fee));
+        env.storage().persistent().set(&DataKey::Active(referee.clone()), &true);
+
+        let referrer = Self::get_referrer(env.clone(), referee.clone())?;
+        let amount = fee * Self::get_referral_share(env.clone()) as i128 / BASIS_POINTS as i128;
+        if amount == 0 {
+            return None;
+        }
+
+        let earnings = Self::get_referral_earnings(env.clone(), referrer.clone());
+        env.storage().persistent().set(&DataKey::ReferralEarnings(referrer.clone()), &(earnings + amount));
+
+        log!(&env, "Referral fee: {} to {} for {}", amount, referrer, referee);
+
+        Some(ReferralPayout { referrer, amount })
+    }
+
+    /// Record that `user` has staked or bet, after which they can no longer
+    /// register a referrer. Reporters only.
+    pub fn record_activity(env: Env, reporter: Address, user: Address) {
+        reporter.require_auth();
+
+        if !Self::is_reporter(env.clone(), reporter.clone()) {
+            panic_with_error!(&env, ContractError::NotAuthorized);
+        }
+
+        if !Self::is_active(env.clone(), user.clone()) {
+            env.storage().persistent().set(&DataKey::Active(user), &true);
+        }
+    }
+
+    /// Allow a contract that deploys reporters, such as the market factory,
+    /// to allow them itself (registrars only)
+    pub fn add_reporter(env: Env, registrar: Address, reporter: Address) {
+        registrar.require_auth();
+
+        if !Self::is_registrar(env.clone(), registrar.clone()) {
+            panic_with_error!(&env, ContractError::NotAuthorized);
+        }
+
+        env.storage().persistent().set(&DataKey::Reporter(reporter.clone()), &true);
+
+        log!(&env, "Reporter {} added by {}", reporter, registrar);
+    }
+
+    /// Allow or disallow a contract to add reporters (admin only)
+    pub fn set_registrar(env: Env, admin: Address, registrar: Address, allowed: bool) {
+        Self::require_admin(&env, &admin);
+
+        if allowed {
+            env.storage().persistent().set(&DataKey::Registrar(registrar.clone()), &true);
+        } else {
+            env.storage().persistent().remove(&DataKey::Registrar(registrar.clone()));
+        }
+
+        log!(&env, "Registrar {} allowed: {}", registrar, allowed);
+    }
+
+    /// Allow or disallow a contract to report referral fees (admin only)
+    pub fn set_reporter(env: Env, admin: Address, reporter: Address, allowed: bool) {
+        Self::require_admin(&env, &admin);
+
+        if allowed {
+            env.storage().persistent().set(&DataKey::Reporter(reporter.clone()), &true);
+        } else {
+            env.storage().persistent().remove(&DataKey::Reporter(reporter.clone()));
+        }
+
+        log!(&env, "Reporter {} allowed: {}", reporter, allowed);
+    }
+
+    /// Update the referrer's share of platform fees (admin only)
+    pub fn set_referral_share(env: Env, admin: Address, referral_share_bps: u32) {
+        Self::require_admin(&env, &admin);
+
+        if referral_share_bps > MAX_REFERRAL_SHARE {
+            panic_with_error!(&env, ContractError::InvalidAmount);
+        }
+
+        env.storage().instance().set(&DataKey::ReferralShare, &referral_share_bps);
+
+        log!(&env, "Referral share updated to {} bps", referral_share_bps);
+    }
+
+    /// Get who referred a user
+    pub fn get_referrer(env: Env, referee: Address) -> Option<Address> {
+        env.storage().persistent().get(&DataKey::Referrer(referee))
+    }
+
+    /// Get a page of the users a referrer has referred
+    pub fn get_referees(env: Env, referrer: Address, offset: u32, limit: u32) -> Vec<Address> {
+        let referee_count = Self::get_referee_count(env.clone(), referrer.clone());
+        let limit = if limit > MAX_PAGE_SIZE { MAX_PAGE_SIZE } else { limit };
+        let end = if offset.saturating_add(limit) > referee_count { referee_count } else { offset + limit };
+
+        let mut referees = Vec::new(&env);
+        for i in offset..end {
+            referees.push_back(env.storage().persistent().get(&DataKey::Referee(referrer.clone(), i)).unwrap());
+        }
+        referees
+    }
+
+    /// Get the number of users a referrer has referred
+    pub fn get_referee_count(env: Env, referrer: Address) -> u32 {
+        env.storage().persistent().get(&DataKey::RefereeCount(referrer)).unwrap_or(0)
+    }
+
+    /// Get a referrer's lifetime referral earnings
+    pub fn get_referral_earnings(env: Env, referrer: Address) -> i128 {
+        env.storage().persistent().get(&DataKey::ReferralEarnings(referrer)).unwrap_or(0)
+    }
+
+    /// Get the lifetime platform fees a user has generated
+    pub fn get_referee_fees(env: Env, referee: Address) -> i128 {
+        env.storage().persistent().get(&DataKey::RefereeFees(referee)).unwrap_or(0)
+    }
+
+    /// Get the referrer's share of platform fees in basis points
+    pub fn get_referral_share(env: Env) -> u32 {
+        env.storage().instance().get(&DataKey::ReferralShare).unwrap_or(0)
+    }
+
+    /// Check whether a contract may report referral fees
+    pub fn is_reporter(env: Env, reporter: Address) -> bool {
+        env.storage().persistent().get(&DataKey::Reporter(reporter)).unwrap_or(false)
+    }
+
+    /// Check whether a contract may add reporters
+    pub fn is_registrar(env: Env, registrar: Address) -> bool {
+        env.storage().persistent().get(&DataKey::Registrar(registrar)).unwrap_or(false)
+    }
+
+    /// Check whether a user has staked, bet or paid fees
+    pub fn is_active(env: Env, user: Address) -> bool {
+        env.storage().persistent().get(&DataKey::Active(user)).unwrap_or(false)
+    }
+
+    // Private helper functions
+
+    fn require_admin(env: &Env, admin: &Address) {
+        admin.require_auth();
+
+        let stored_admin: Address = env.storage().instance()
+            .get(&DataKey::Admin)
+            .unwrap_or_else(|| panic_with_error!(env, ContractError::NotAuthorized));
+
+        if *admin != stored_admin {
+            panic_with_error!(env, ContractError::NotAuthorized);
+        }
+    }
+}
@@ -1,13 +1,10 @@
 #![no_std]
 
 use soroban_sdk::{
-    contract, contractimpl, contracttype, Address, Env, String, Vec, Map,
+    contract, contractimpl, contracttype, Address, Env, String, Vec,
     panic_with_error, log,
 };
-use shared_types::{
-    PriceFeed, EventData, MarketOutcome, ContractError, 
-    ContractCallResult, InteropError, OracleSubscription
-};
+use shared_types::{PriceFeed, EventData, MarketOutcome, ContractError, OracleSubscription};
 
 const DAY_IN_LEDGERS: u32 = 17280;
 const INSTANCE_BUMP_AMOUNT: u32 = 7 * DAY_IN_LEDGERS;
@@ -110,7 +107,7 @@ impl ReflectorOracleContract {
             panic_with_error!(&env, ContractError::NotAuthorized);
         }
 
-        let mut oracle_nodes: Vec<OracleNode> = env.storage().instance()
+        let oracle_nodes: Vec<OracleNode> = env.storage().instance()
             .get(&DataKey::OracleNodes)
             .unwrap_or_else(|| Vec::new(&env));
 
@@ -205,7 +202,7 @@ impl ReflectorOracleContract {
 
         let event_data = EventData {
             event_id: event_id.clone(),
-            outcome,
+            outcome: outcome.clone(),
             timestamp: current_time,
             confidence,
             data_source,
@@ -216,7 +213,7 @@ impl ReflectorOracleContract {
         // Update oracle node's last update time
         Self::update_oracle_node_activity(&env, &oracle_node);
 
-        log!(&env, "Event data submitted for {}: {:?} by {}", event_id, outcome, oracle_node);
+        log!(&env, "Event data submitted for {}: {} by {}", event_id, outcome, oracle_node);
     }
 
     /// Get latest price for an asset
@@ -270,7 +267,7 @@ impl ReflectorOracleContract {
     pub fn unsubscribe(env: Env, subscriber: Address, asset_name: String) {
         subscriber.require_auth();
 
-        let mut subscriptions: Vec<Address> = env.storage().persistent()
+        let subscriptions: Vec<Address> = env.storage().persistent()
             .get(&DataKey::Subscriptions(asset_name.clone()))
             .unwrap_or_else(|| Vec::new(&env));
 
@@ -308,15 +305,13 @@ impl ReflectorOracleContract {
 
     /// Check if price data is available and fresh
     pub fn is_price_available(env: Env, asset_name: String) -> bool {
-        if let Ok(price_feed) = env.storage().persistent().try_get(&DataKey::PriceFeed(asset_name)) {
-            if let Some(feed) = price_feed {
-                let max_age: u64 = env.storage().instance()
-                    .get(&DataKey::MaxPriceAge)
-                    .unwrap_or(3600);
-                
-                let current_time = env.ledger().timestamp();
-                return current_time - feed.timestamp <= max_age;
-            }
+        if let Some(feed) = env.storage().persistent().get::<_, PriceFeed>(&DataKey::PriceFeed(asset_name)) {
+            let max_age: u64 = env.storage().instance()
+                .get(&DataKey::MaxPriceAge)
+                .unwrap_or(3600);
+
+            let current_time = env.ledger().timestamp();
+            return current_time - feed.timestamp <= max_age;
         }
         false
     }
@@ -351,16 +346,12 @@ impl ReflectorOracleContract {
         asset_name: String,
         threshold: i128,
         condition: u32, // 0: Above, 1: Below
-    ) -> ContractCallResult<OracleSubscription> {
+    ) -> Result<OracleSubscription, ContractError> {
         subscriber.require_auth();
 
         // Check if price is available for the asset
         if !Self::is_price_available(env.clone(), asset_name.clone()) {
-            return ContractCallResult {
-                success: false,
-                data: None,
-                error: Some("Price not available for asset".to_string()),
-            };
+            return Err(ContractError::OracleError);
         }
 
         // Create subscription
@@ -376,11 +367,7 @@ impl ReflectorOracleContract {
         // Store subscription
         Self::subscribe(env.clone(), subscriber.clone(), asset_name.clone());
 
-        ContractCallResult {
-            success: true,
-            data: Some(subscription),
-            error: None,
-        }
+        Ok(subscription)
     }
 
     /// Batch price update for multiple assets
@@ -391,23 +378,15 @@ impl ReflectorOracleContract {
         prices: Vec<i128>,
         confidences: Vec<u32>,
         source: String,
-    ) -> ContractCallResult<Vec<String>> {
+    ) -> Result<Vec<String>, ContractError> {
         oracle_node.require_auth();
 
         if !Self::is_authorized_oracle(&env, &oracle_node) {
-            return ContractCallResult {
-                success: false,
-                data: None,
-                error: Some("Unauthorized oracle node".to_string()),
-            };
+            return Err(ContractError::NotAuthorized);
         }
 
         if assets.len() != prices.len() || assets.len() != confidences.len() {
-            return ContractCallResult {
-                success: false,
-                data: None,
-                error: Some("Mismatched array lengths".to_string()),
-            };
+            return Err(ContractError::InvalidAmount);
         }
 
         let mut updated_assets = Vec::new(&env);
@@ -420,34 +399,31 @@ impl ReflectorOracleContract {
             let price = prices.get(i).unwrap();
             let confidence = confidences.get(i).unwrap();
 
-            if *confidence >= min_confidence {
+            if confidence >= min_confidence {
                 Self::update_price(
                     env.clone(),
                     oracle_node.clone(),
                     asset.clone(),
-                    *price,
-                    *confidence,
+                    price,
+                    confidence,
                     source.clone(),
                 );
                 updated_assets.push_back(asset.clone());
             }
         }
 
-        ContractCallResult {
-            success: true,
-            data: Some(updated_assets),
-            error: None,
-        }
+        Ok(updated_assets)
     }
 
-    /// Get price with fallback mechanism
+    /// Get price with fallback mechanism: the fallback is returned when the
+    /// price is missing or too old
     pub fn get_price_with_fallback(
         env: Env,
         asset_name: String,
         fallback_price: i128,
-    ) -> ContractCallResult<i128> {
-        match env.storage().persistent().try_get(&DataKey::PriceFeed(asset_name.clone())) {
-            Ok(Some(price_feed)) => {
+    ) -> i128 {
+        match env.storage().persistent().get::<_, PriceFeed>(&DataKey::PriceFeed(asset_name.clone())) {
+            Some(price_feed) => {
                 let max_age: u64 = env.storage().instance()
                     .get(&DataKey::MaxPriceAge)
                     .unwrap_or(3600);
@@ -455,24 +431,12 @@ impl ReflectorOracleContract {
                 let current_time = env.ledger().timestamp();
                 
                 if current_time - price_feed.timestamp <= max_age {
-                    ContractCallResult {
-                        success: true,
-                        data: Some(price_feed.price),
-                        error: None,
-                    }
+                    price_feed.price
                 } else {
-                    ContractCallResult {
-                        success: true,
-                        data: Some(fallback_price),
-                        error: Some("Price too old, using fallback".to_string()),
-                    }
+                    fallback_price
                 }
             },
-            _ => ContractCallResult {
-                success: true,
-                data: Some(fallback_price),
-                error: Some("No price data, using fallback".to_string()),
-            },
+            None => fallback_price,
         }
     }
 
@@ -483,15 +447,11 @@ impl ReflectorOracleContract {
         target_price: i128,
         condition: u32,
         required_confidence: u32,
-    ) -> ContractCallResult<bool> {
+    ) -> Result<bool, ContractError> {
         let price_feed = Self::get_price(env.clone(), asset_name.clone());
         
         if price_feed.confidence < required_confidence {
-            return ContractCallResult {
-                success: false,
-                data: None,
-                error: Some("Insufficient confidence for resolution".to_string()),
-            };
+            return Err(ContractError::OracleError);
         }
 
         let outcome = match condition {
@@ -500,11 +460,7 @@ impl ReflectorOracleContract {
             _ => false,
         };
 
-        ContractCallResult {
-            success: true,
-            data: Some(outcome),
-            error: None,
-        }
+        Ok(outcome)
     }
 
     // Private helper functions
@@ -523,7 +479,7 @@ impl ReflectorOracleContract {
     }
 
     fn update_oracle_node_activity(env: &Env, oracle_address: &Address) {
-        let mut oracle_nodes: Vec<OracleNode> = env.storage().instance()
+        let oracle_nodes: Vec<OracleNode> = env.storage().instance()
             .get(&DataKey::OracleNodes)
             .unwrap_or_else(|| Vec::new(env));
 
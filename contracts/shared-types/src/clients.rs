@@ -1,110 +1,85 @@
-#![no_std]
+//! Cross-contract client interfaces.
+//!
+//! Each trait mirrors the subset of a contract's interface that other
+//! contracts call, and `#[contractclient]` generates the matching `*Client`.
 
-use soroban_sdk::{contract, contractimpl, Address, Env, String, BytesN};
-use crate::{PriceFeed, EventData, StakeInfo, MarketInfo, ContractError};
+use soroban_sdk::{contractclient, Address, Env, String, BytesN};
+use crate::{PriceFeed, EventData, StakeInfo, MarketInfo, MarketParams, ContractError, ReferralPayout};
 
-/// Client for Reflector Oracle contract
-#[contract]
-pub struct ReflectorOracleClient;
-
-#[contractimpl]
-impl ReflectorOracleClient {
+/// Interface of the Reflector Oracle contract
+#[contractclient(name = "ReflectorOracleClient")]
+pub trait ReflectorOracleInterface {
     /// Get latest price for an asset
-    pub fn get_price(env: &Env, oracle_address: &Address, asset_name: String) -> PriceFeed {
-        let client = ReflectorOracleClient::new(env, oracle_address);
-        client.get_price(&asset_name)
-    }
-
-    /// Try to get price with error handling
-    pub fn try_get_price(env: &Env, oracle_address: &Address, asset_name: String) -> Result<PriceFeed, ContractError> {
-        let client = ReflectorOracleClient::new(env, oracle_address);
-        match client.try_get_price(&asset_name) {
-            Ok(price_feed) => Ok(price_feed),
-            Err(_) => Err(ContractError::OracleError),
-        }
-    }
+    fn get_price(env: Env, asset_name: String) -> PriceFeed;
 
     /// Get event data
-    pub fn get_event_data(env: &Env, oracle_address: &Address, event_id: String) -> EventData {
-        let client = ReflectorOracleClient::new(env, oracle_address);
-        client.get_event_data(&event_id)
-    }
+    fn get_event_data(env: Env, event_id: String) -> EventData;
 
     /// Check if price is available
-    pub fn is_price_available(env: &Env, oracle_address: &Address, asset_name: String) -> bool {
-        let client = ReflectorOracleClient::new(env, oracle_address);
-        client.is_price_available(&asset_name)
-    }
-}
+    fn is_price_available(env: Env, asset_name: String) -> bool;
 
-/// Client for KALE Integration contract
-#[contract]
-pub struct KaleIntegrationClient;
+    /// Check a market condition against the latest price
+    fn validate_market_resolution(
+        env: Env,
+        asset_name: String,
+        target_price: i128,
+        condition: u32,
+        required_confidence: u32,
+    ) -> Result<bool, ContractError>;
+}
 
-#[contractimpl]
-impl KaleIntegrationClient {
+/// Interface of the KALE Integration contract
+#[contractclient(name = "KaleIntegrationClient")]
+pub trait KaleIntegrationInterface {
     /// Get stake information for a user
-    pub fn get_stake_info(env: &Env, kale_address: &Address, staker: Address) -> StakeInfo {
-        let client = KaleIntegrationClient::new(env, kale_address);
-        client.get_stake_info(&staker)
-    }
-
-    /// Try to get stake info with error handling
-    pub fn try_get_stake_info(env: &Env, kale_address: &Address, staker: Address) -> Result<StakeInfo, ContractError> {
-        let client = KaleIntegrationClient::new(env, kale_address);
-        match client.try_get_stake_info(&staker) {
-            Ok(stake_info) => Ok(stake_info),
-            Err(_) => Err(ContractError::StakeNotFound),
-        }
-    }
+    fn get_stake_info(env: Env, staker: Address) -> StakeInfo;
 
     /// Get total staked amount
-    pub fn get_total_staked(env: &Env, kale_address: &Address) -> i128 {
-        let client = KaleIntegrationClient::new(env, kale_address);
-        client.get_total_staked()
-    }
+    fn get_total_staked(env: Env) -> i128;
 
     /// Get current APY
-    pub fn get_current_apy(env: &Env, kale_address: &Address) -> u32 {
-        let client = KaleIntegrationClient::new(env, kale_address);
-        client.get_current_apy()
-    }
+    fn get_current_apy(env: Env) -> u32;
 
     /// Get market information
-    pub fn get_market_info(env: &Env, kale_address: &Address, market_id: BytesN<32>) -> MarketInfo {
-        let client = KaleIntegrationClient::new(env, kale_address);
-        client.get_market_info(&market_id)
-    }
+    fn get_market_info(env: Env, market_id: BytesN<32>) -> MarketInfo;
 
     /// Stake KALE tokens
-    pub fn stake(env: &Env, kale_address: &Address, staker: Address, amount: i128) {
-        let client = KaleIntegrationClient::new(env, kale_address);
-        client.stake(&staker, &amount);
-    }
+    fn stake(env: Env, staker: Address, amount: i128);
+
+    /// Unstake KALE tokens into the unbonding queue
+    fn unstake(env: Env, staker: Address, amount: i128) -> u32;
 
-    /// Unstake KALE tokens
-    pub fn unstake(env: &Env, kale_address: &Address, staker: Address, amount: i128) {
-        let client = KaleIntegrationClient::new(env, kale_address);
-        client.unstake(&staker, &amount);
-    }
+    /// Withdraw unbonded KALE tokens
+    fn withdraw_unbonded(env: Env, staker: Address) -> i128;
 
     /// Claim rewards
-    pub fn claim_rewards(env: &Env, kale_address: &Address, staker: Address) -> i128 {
-        let client = KaleIntegrationClient::new(env, kale_address);
-        client.claim_rewards(&staker)
-    }
-}
+    fn claim_rewards(env: Env, staker: Address) -> i128;
 
-/// Client for Market Factory contract
-#[contract]
-pub struct MarketFactoryClient;
+    /// Get how long unstaked KALE waits before it can be withdrawn
+    fn get_unbonding_period(env: Env) -> u64;
 
-#[contractimpl]
-impl MarketFactoryClient {
+    /// Check whether the contract is paused
+    fn is_paused(env: Env) -> bool;
+
+    /// Withdraw all principal while paused, forfeiting unclaimed rewards
+    fn emergency_withdraw(env: Env, staker: Address) -> i128;
+
+    /// Get the referral registry used for platform fees, if any
+    fn get_referral_registry(env: Env) -> Option<Address>;
+
+    /// Lock free stake as collateral for a bet in the calling market
+    fn lock_market_collateral(env: Env, market: Address, staker: Address, amount: i128);
+
+    /// Release a staker's collateral in the calling market, sending it what was lost
+    fn settle_market_collateral(env: Env, market: Address, staker: Address, lost: i128);
+}
+
+/// Interface of the Market Factory contract
+#[contractclient(name = "MarketFactoryClient")]
+pub trait MarketFactoryInterface {
     /// Create a new prediction market
-    pub fn create_market(
-        env: &Env,
-        factory_address: &Address,
+    fn create_market(
+        env: Env,
         creator: Address,
         event_description: String,
         oracle_asset: String,
@@ -114,22 +89,58 @@ impl MarketFactoryClient {
         min_bet_amount: i128,
         max_bet_amount: i128,
         creator_fee_rate: u32,
+    ) -> Address;
+
+    /// Look up a market id by its deployed contract address
+    fn get_market_id(env: Env, contract_id: Address) -> u32;
+}
+
+/// Interface of the Prediction Market contract
+#[contractclient(name = "PredictionMarketClient")]
+pub trait PredictionMarketInterface {
+    /// Initialize a newly deployed market (called once by the factory)
+    fn initialize(
+        env: Env,
+        factory: Address,
+        creator: Address,
+        params: MarketParams,
         kale_token: Address,
         reflector_oracle: Address,
-    ) -> Address {
-        let client = MarketFactoryClient::new(env, factory_address);
-        client.create_market(
-            &creator,
-            &event_description,
-            &oracle_asset,
-            &target_price,
-            &condition,
-            &resolve_time,
-            &min_bet_amount,
-            &max_bet_amount,
-            &creator_fee_rate,
-            &kale_token,
-            &reflector_oracle,
-        )
-    }
+        kale_integration: Address,
+        platform_fee_rate: u32,
+    );
+
+    /// Get a user's (YES, NO) bet amounts
+    fn get_user_bets(env: Env, user: Address) -> (i128, i128);
+
+    /// Get market (YES, NO) pool totals
+    fn get_totals(env: Env) -> (i128, i128);
+
+    /// Check if market is resolved
+    fn is_resolved(env: Env) -> bool;
+
+    /// Mark the market closed once betting has ended (factory only)
+    fn close(env: Env, factory: Address);
+
+    /// Resolve the market using the Reflector oracle
+    fn resolve(env: Env, resolver: Address);
+
+    /// Cancel an unresolved market, refunding every bet (factory only)
+    fn cancel(env: Env, factory: Address);
+}
+
+/// Interface of the Referral Registry contract
+#[contractclient(name = "ReferralRegistryClient")]
+pub trait ReferralRegistryInterface {
+    /// Record a platform fee generated by a referee and get the referrer's cut
+    fn record_referral_fee(env: Env, reporter: Address, referee: Address, fee: i128) -> Option<ReferralPayout>;
+
+    /// Record that a user has staked or bet
+    fn record_activity(env: Env, reporter: Address, user: Address);
+
+    /// Allow a newly deployed contract to report referral fees
+    fn add_reporter(env: Env, registrar: Address, reporter: Address);
+
+    /// Get who referred a user
+    fn get_referrer(env: Env, referee: Address) -> Option<Address>;
 }
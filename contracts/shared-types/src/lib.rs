@@ -1,6 +1,7 @@
 #![no_std]
+#![allow(clippy::too_many_arguments)]
 
-use soroban_sdk::{contracttype, Address, String, Vec, BytesN, Option};
+use soroban_sdk::{contracterror, contracttype, Address, Env, String, Vec, BytesN};
 
 pub mod clients;
 
@@ -45,6 +46,20 @@ pub struct Market {
     pub platform_fee_rate: u32, // basis points
 }
 
+/// Terms of the market, set once by the factory
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MarketParams {
+    pub event_description: String,
+    pub oracle_asset: String,
+    pub target_price: i128,
+    pub condition: u32, // 0: Above, 1: Below
+    pub resolve_time: u64,
+    pub min_bet_amount: i128,
+    pub max_bet_amount: i128,
+    pub creator_fee_rate: u32,
+}
+
 /// Bet information structure
 #[contracttype]
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -65,9 +80,11 @@ pub struct Bet {
 pub struct StakeInfo {
     pub staker: Address,
     pub amount: i128,
+    pub weight: i128, // reward weight: amount boosted by lock multipliers
     pub stake_time: u64,
     pub last_reward_time: u64,
     pub accumulated_rewards: i128,
+    pub reward_per_token_paid: i128, // accumulator value at the last checkpoint, scaled by 1e12
 }
 
 /// Oracle price feed structure
@@ -93,22 +110,25 @@ pub struct EventData {
 }
 
 /// Error types for contracts
-#[contracttype]
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracterror]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
+#[repr(u32)]
 pub enum ContractError {
-    NotAuthorized,
-    MarketNotFound,
-    MarketClosed,
-    MarketAlreadyResolved,
-    InsufficientBalance,
-    InvalidAmount,
-    InvalidOutcome,
-    BetNotFound,
-    AlreadyClaimed,
-    OracleError,
-    InvalidTimestamp,
-    StakeNotFound,
-    InsufficientStake,
+    NotAuthorized = 1,
+    MarketNotFound = 2,
+    MarketClosed = 3,
+    MarketAlreadyResolved = 4,
+    InsufficientBalance = 5,
+    InvalidAmount = 6,
+    InvalidOutcome = 7,
+    BetNotFound = 8,
+    AlreadyClaimed = 9,
+    OracleError = 10,
+    InvalidTimestamp = 11,
+    StakeNotFound = 12,
+    InsufficientStake = 13,
+    MarketAlreadyExists = 14,
+    ContractPaused = 15,
 }
 
 /// Market information structure for KALE integration
@@ -163,15 +183,6 @@ pub struct ContractAddresses {
     pub market_factory: Address,
 }
 
-/// Cross-contract call result
-#[contracttype]
-#[derive(Clone, Debug, Eq, PartialEq)]
-pub struct ContractCallResult<T> {
-    pub success: bool,
-    pub data: Option<T>,
-    pub error: Option<String>,
-}
-
 /// Market resolution data from oracle
 #[contracttype]
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -229,93 +240,28 @@ pub enum InteropError {
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum ContractEvent {
     // Market Events
-    MarketCreated {
-        market_id: BytesN<32>,
-        creator: Address,
-        asset_symbol: String,
-        target_price: i128,
-        resolve_time: u64,
-    },
-    MarketResolved {
-        market_id: BytesN<32>,
-        outcome: bool,
-        final_price: i128,
-        confidence: u32,
-    },
-    BetPlaced {
-        market_id: BytesN<32>,
-        bettor: Address,
-        side: bool,
-        amount: i128,
-        odds: u32,
-    },
-    WinningsClaimed {
-        market_id: BytesN<32>,
-        winner: Address,
-        amount: i128,
-    },
+    MarketCreated(BytesN<32>, Address, String, i128, u64), // market_id, creator, asset_symbol, target_price, resolve_time
+    MarketResolved(BytesN<32>, bool, i128, u32), // market_id, outcome, final_price, confidence
+    BetPlaced(BytesN<32>, Address, bool, i128, u32), // market_id, bettor, side, amount, odds
+    WinningsClaimed(BytesN<32>, Address, i128), // market_id, winner, amount
     
     // Staking Events
-    TokensStaked {
-        staker: Address,
-        amount: i128,
-        total_staked: i128,
-        apy: u32,
-    },
-    TokensUnstaked {
-        staker: Address,
-        amount: i128,
-        remaining_staked: i128,
-    },
-    RewardsClaimed {
-        staker: Address,
-        amount: i128,
-        new_balance: i128,
-    },
+    TokensStaked(Address, i128, i128, u32), // staker, amount, total_staked, apy
+    TokensUnstaked(Address, i128, i128), // staker, amount, remaining_staked
+    RewardsClaimed(Address, i128, i128), // staker, amount, new_balance
     
     // Oracle Events
-    PriceUpdated {
-        asset_name: String,
-        price: i128,
-        confidence: u32,
-        source: String,
-        timestamp: u64,
-    },
-    OracleNodeAdded {
-        node_address: Address,
-        reputation_score: u32,
-    },
-    OracleNodeRemoved {
-        node_address: Address,
-        reason: String,
-    },
+    PriceUpdated(String, i128, u32, String, u64), // asset_name, price, confidence, source, timestamp
+    OracleNodeAdded(Address, u32), // node_address, reputation_score
+    OracleNodeRemoved(Address, String), // node_address, reason
     
     // Cross-Contract Events
-    CrossContractCall {
-        from_contract: Address,
-        to_contract: Address,
-        function_name: String,
-        success: bool,
-        error_message: Option<String>,
-    },
-    ContractValidationFailed {
-        contract_address: Address,
-        validation_type: String,
-        reason: String,
-    },
+    CrossContractCall(Address, Address, String, bool, String), // from_contract, to_contract, function_name, success, error_message (empty on success)
+    ContractValidationFailed(Address, String, String), // contract_address, validation_type, reason
     
     // System Events
-    FeeCollected {
-        collector: Address,
-        amount: i128,
-        fee_type: String,
-    },
-    ConfigurationUpdated {
-        contract_address: Address,
-        parameter: String,
-        old_value: String,
-        new_value: String,
-    },
+    FeeCollected(Address, i128, String), // collector, amount, fee_type
+    ConfigurationUpdated(Address, String, String, String), // contract_address, parameter, old_value, new_value
 }
 
 /// Event subscription for cross-contract notifications
@@ -329,15 +275,33 @@ pub struct EventSubscription {
     pub is_active: bool,
 }
 
-/// Event filter for querying specific events
+/// Event filter for querying specific events; an empty list matches everything
 #[contracttype]
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct EventFilter {
-    pub event_types: Option<Vec<String>>,
-    pub contract_addresses: Option<Vec<Address>>,
+    pub event_types: Vec<String>,
+    pub contract_addresses: Vec<Address>,
     pub from_timestamp: Option<u64>,
     pub to_timestamp: Option<u64>,
-    pub user_addresses: Option<Vec<Address>>,
+    pub user_addresses: Vec<Address>,
+}
+
+/// Staking tier granting fee discounts and higher bet caps in prediction markets
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StakingTier {
+    pub level: u32,
+    pub min_stake: i128,
+    pub fee_discount_bps: u32,       // share of fees waived, basis points
+    pub max_bet_multiplier_bps: u32, // applied to a market's max_bet_amount, 10000 = 1x
+}
+
+/// Referrer's cut of a platform fee, to be paid by the contract that collected it
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ReferralPayout {
+    pub referrer: Address,
+    pub amount: i128,
 }
 
 /// Utility functions
@@ -405,8 +369,8 @@ pub mod validation {
     use super::*;
     
     /// Validate address is not zero
-    pub fn validate_address(env: &Env, address: &Address) -> Result<(), ContractError> {
-        if address.to_string().is_empty() {
+    pub fn validate_address(_env: &Env, address: &Address) -> Result<(), ContractError> {
+        if address.to_string().len() == 0 {
             Err(ContractError::NotAuthorized)
         } else {
             Ok(())
@@ -443,8 +407,8 @@ pub mod validation {
     }
     
     /// Validate string is not empty and within length limits
-    pub fn validate_string(env: &Env, string: &String, max_length: u32) -> Result<(), ContractError> {
-        if string.is_empty() {
+    pub fn validate_string(_env: &Env, string: &String, max_length: u32) -> Result<(), ContractError> {
+        if string.len() == 0 {
             return Err(ContractError::NotAuthorized);
         }
         
@@ -570,3 +534,32 @@ pub mod validation {
     }
 }
 
+/// Staking tier table shared by all contracts
+pub mod tiers {
+    use super::*;
+
+    /// KALE has 7 decimals
+    pub const KALE_UNIT: i128 = 10_000_000;
+
+    /// All staking tiers, ordered by increasing `min_stake`
+    pub fn staking_tiers(env: &Env) -> Vec<StakingTier> {
+        let mut tiers = Vec::new(env);
+        tiers.push_back(StakingTier { level: 0, min_stake: 0, fee_discount_bps: 0, max_bet_multiplier_bps: 10_000 });
+        tiers.push_back(StakingTier { level: 1, min_stake: 1_000 * KALE_UNIT, fee_discount_bps: 1_000, max_bet_multiplier_bps: 20_000 });
+        tiers.push_back(StakingTier { level: 2, min_stake: 10_000 * KALE_UNIT, fee_discount_bps: 2_500, max_bet_multiplier_bps: 50_000 });
+        tiers.push_back(StakingTier { level: 3, min_stake: 100_000 * KALE_UNIT, fee_discount_bps: 5_000, max_bet_multiplier_bps: 100_000 });
+        tiers
+    }
+
+    /// Highest tier whose threshold the stake meets
+    pub fn tier_for_stake(env: &Env, staked_amount: i128) -> StakingTier {
+        let tiers = staking_tiers(env);
+        let mut current = tiers.get(0).unwrap();
+        for tier in tiers.iter() {
+            if staked_amount >= tier.min_stake {
+                current = tier;
+            }
+        }
+        current
+    }
+}